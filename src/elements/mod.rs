@@ -4,6 +4,7 @@
  * See LICENSE for more information.
  */
 
+mod serialization;
 pub mod tokens;
 
 use crate::format::PlaceholderTemplate;
@@ -12,7 +13,7 @@ use crate::references::placeholders::ProcessPlaceholders;
 use crate::references::templates::{Template, TemplateVariable};
 use crate::settings::Settings;
 use crate::utils::downloads::{DownloadManager, PendingDownload};
-use crate::utils::image_converting::{ImageConverter, PendingImage};
+use crate::utils::image_converting::{ImageConverter, ImageVariant, PendingImage};
 use asciimath_rs::elements::special::Expression;
 use bibliographix::bib_manager::BibManager;
 use bibliographix::bibliography::bibliography_entry::BibliographyEntryReference;
@@ -20,7 +21,10 @@ use bibliographix::references::bib_reference::BibRefAnchor;
 use image::ImageFormat;
 use mime::Mime;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
@@ -32,7 +36,10 @@ pub const CODE_BLOCK: &str = "code_block";
 pub const QUOTE: &str = "quote";
 pub const IMPORT: &str = "import";
 
-#[derive(Clone, Debug)]
+/// The label of numbered code listings
+pub(crate) const LISTING_LABEL: &str = "Listing";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum MetadataValue {
     String(String),
     Integer(i64),
@@ -42,14 +49,14 @@ pub enum MetadataValue {
     Template(Template),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Element {
     Block(Box<Block>),
     Line(Box<Line>),
     Inline(Box<Inline>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Block {
     Section(Section),
     Paragraph(Paragraph),
@@ -63,7 +70,7 @@ pub enum Block {
     Null,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Line {
     Text(TextLine),
     Ruler(Ruler),
@@ -73,46 +80,54 @@ pub enum Line {
     BibEntry(BibEntry),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "serialization::SerializedDocument")]
 pub struct Document {
     pub elements: Vec<Block>,
+    #[serde(skip)]
     pub(crate) is_root: bool,
     pub(crate) path: Option<String>,
+    #[serde(skip)]
     pub(crate) placeholders: Vec<Arc<RwLock<Placeholder>>>,
     pub config: Arc<Mutex<Settings>>,
+    #[serde(skip)]
     pub bibliography: BibManager,
+    #[serde(skip)]
     pub downloads: Arc<Mutex<DownloadManager>>,
+    #[serde(skip)]
     pub images: Arc<Mutex<ImageConverter>>,
+    #[serde(serialize_with = "serialization::serialize_downloads")]
     pub stylesheets: Vec<Arc<Mutex<PendingDownload>>>,
+    #[serde(skip)]
     pub glossary: Arc<Mutex<GlossaryManager>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Section {
     pub(crate) header: Header,
     pub(crate) elements: Vec<Block>,
     pub(crate) metadata: Option<InlineMetadata>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Header {
     pub(crate) size: u8,
     pub(crate) line: Line,
     pub(crate) anchor: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Paragraph {
     pub(crate) elements: Vec<Line>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct List {
     pub(crate) ordered: bool,
     pub items: Vec<ListItem>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ListItem {
     pub(crate) text: Line,
     pub(crate) level: u16,
@@ -120,59 +135,61 @@ pub struct ListItem {
     pub(crate) children: Vec<ListItem>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Table {
     pub(crate) header: Row,
     pub(crate) rows: Vec<Row>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Row {
     pub(crate) cells: Vec<Cell>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Cell {
     pub(crate) text: Line,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CodeBlock {
     pub(crate) language: String,
     pub(crate) code: String,
+    pub(crate) metadata: Option<InlineMetadata>,
+    pub(crate) number: Option<usize>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Quote {
     pub(crate) metadata: Option<InlineMetadata>,
     pub(crate) text: Vec<TextLine>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Import {
     pub(crate) path: String,
     pub(crate) anchor: Arc<RwLock<ImportAnchor>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ImportAnchor {
     pub(crate) document: Option<Document>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InlineMetadata {
     pub(crate) data: HashMap<String, MetadataValue>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Ruler {}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TextLine {
     pub subtext: Vec<Inline>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Inline {
     Plain(PlainText),
     Bold(BoldText),
@@ -197,111 +214,119 @@ pub enum Inline {
     Anchor(Anchor),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlainText {
     pub(crate) value: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BoldText {
     pub(crate) value: Vec<Inline>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ItalicText {
     pub(crate) value: Vec<Inline>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UnderlinedText {
     pub(crate) value: Vec<Inline>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StrikedText {
     pub(crate) value: Vec<Inline>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MonospaceText {
     pub(crate) value: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SuperscriptText {
     pub(crate) value: Vec<Inline>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Checkbox {
     pub(crate) value: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Url {
     pub description: Option<Vec<Inline>>,
     pub url: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "serialization::SerializedImage")]
 pub struct Image {
     pub(crate) url: Url,
     pub(crate) metadata: Option<InlineMetadata>,
+    #[serde(skip)]
     pub(crate) image_data: Arc<Mutex<PendingImage>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Placeholder {
     pub(crate) name: String,
     pub(crate) value: Option<Element>,
     pub(crate) metadata: Option<InlineMetadata>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RefLink {
     pub(crate) description: TextLine,
     pub(crate) reference: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Anchor {
     pub(crate) inner: Box<Line>,
     pub(crate) key: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Centered {
     pub(crate) line: TextLine,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Emoji {
     pub(crate) value: char,
     pub(crate) name: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Colored {
     pub(crate) value: Box<Inline>,
     pub(crate) color: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "serialization::SerializedMath")]
 pub struct Math {
+    #[serde(skip)]
     pub(crate) expression: Expression,
+    pub(crate) source: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "serialization::SerializedMath")]
 pub struct MathBlock {
+    #[serde(skip)]
     pub(crate) expression: Expression,
+    pub(crate) source: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CharacterCode {
     pub(crate) code: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Arrow {
     RightArrow,
     LeftArrow,
@@ -435,19 +460,61 @@ impl Document {
         self.elements = new_order;
     }
 
+    /// Returns the directory of the document that is used as the project root
+    pub(crate) fn get_root_dir(&self) -> PathBuf {
+        self.path
+            .as_ref()
+            .and_then(|p| Path::new(p).parent())
+            .map(|p| p.to_path_buf())
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
     pub fn post_process(&mut self) {
         self.postprocess_imports();
         if self.is_root {
             self.process_definitions();
             self.bibliography.assign_entries_to_references();
             self.glossary.lock().assign_entries_to_references();
+            self.process_listings();
             self.process_placeholders();
             self.process_media();
         }
     }
 
-    fn process_media(&self) {
+    /// Numbers the captioned code listings in the order they appear in the document
+    pub(crate) fn process_listings(&mut self) {
+        let mut count = 0;
+        self.elements
+            .iter_mut()
+            .for_each(|e| e.number_listings(&mut count));
+    }
+
+    /// Returns the numbers of all listings that can be referenced by their anchor
+    pub(crate) fn get_listing_numbers(&self) -> HashMap<String, usize> {
+        let mut listings = HashMap::new();
+        self.elements
+            .iter()
+            .for_each(|e| e.collect_listing_numbers(&mut listings));
+
+        listings
+    }
+
+    /// Creates the resized variants of the images for responsive html output
+    pub(crate) fn create_image_variants(&self) {
+        let variant_widths = self.config.lock().images.responsive_widths.clone();
+        if !variant_widths.is_empty() {
+            self.images.lock().create_all_variants(&variant_widths);
+        }
+    }
+
+    pub(crate) fn process_media(&self) {
         let downloads = Arc::clone(&self.downloads);
+        if self.config.lock().safe_mode {
+            let root = self.get_root_dir();
+            downloads.lock().retain_inside(&root);
+            self.images.lock().retain_inside(&root);
+        }
         if self.config.lock().features.embed_external {
             downloads.lock().download_all();
         }
@@ -772,28 +839,154 @@ impl Metadata for InlineMetadata {
     }
 }
 
+impl CodeBlock {
+    /// Returns the file name that is shown above the code
+    pub fn get_file_name(&self) -> Option<String> {
+        self.metadata.as_ref().and_then(|m| m.get_string("file"))
+    }
+
+    /// Returns the anchor the listing can be referenced with
+    pub fn get_anchor(&self) -> Option<String> {
+        self.metadata.as_ref().and_then(|m| m.get_string("id"))
+    }
+
+    /// Returns if the code block is a listing with a caption
+    pub fn has_caption(&self) -> bool {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get_string("caption"))
+            .is_some()
+    }
+
+    /// Returns the caption prefixed with the number of the listing
+    pub fn get_caption(&self) -> Option<String> {
+        let caption = self.metadata.as_ref()?.get_string("caption")?;
+
+        if let Some(number) = self.number {
+            Some(format!("{} {}: {}", LISTING_LABEL, number, caption))
+        } else {
+            Some(caption)
+        }
+    }
+
+    /// Returns if the lines are prefixed with their numbers
+    pub fn has_line_numbers(&self) -> bool {
+        self.metadata
+            .as_ref()
+            .map(|m| m.get_bool("line_numbers") || m.get_integer("start").is_some())
+            .unwrap_or(false)
+    }
+
+    /// Returns the number of the first line
+    pub fn get_start_line(&self) -> usize {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get_integer("start"))
+            .map(|s| s.max(0) as usize)
+            .unwrap_or(1)
+    }
+
+    /// Returns the ranges of highlighted line numbers, e.g. `highlight = "2,4-6"`
+    pub fn get_highlighted_lines(&self) -> Vec<RangeInclusive<usize>> {
+        let value = match self.metadata.as_ref().and_then(|m| m.data.get("highlight")) {
+            Some(value) => value.to_string(),
+            None => return Vec::new(),
+        };
+
+        value
+            .split(',')
+            .filter_map(|range| {
+                let mut bounds = range.splitn(2, '-').map(|b| b.trim().parse::<usize>());
+                let start = bounds.next()?.ok()?;
+                let end = bounds.next().unwrap_or(Ok(start)).ok()?;
+
+                Some(start..=end)
+            })
+            .collect()
+    }
+
+    /// Returns the gutters written in front of the lines of the code. They contain the
+    /// line numbers and mark highlighted lines with a `>`. No gutters are returned
+    /// if neither line numbers nor highlighted lines are enabled.
+    pub fn get_line_gutters(&self) -> Vec<String> {
+        let highlighted = self.get_highlighted_lines();
+        let line_numbers = self.has_line_numbers();
+
+        if !line_numbers && highlighted.is_empty() {
+            return Vec::new();
+        }
+        let count = self.code.trim_end_matches('\n').lines().count();
+        let start = self.get_start_line();
+        let width = (start + count.saturating_sub(1)).to_string().len();
+
+        (start..start + count)
+            .map(|number| {
+                let marker = if highlighted.iter().any(|r| r.contains(&number)) {
+                    ">"
+                } else if line_numbers {
+                    "|"
+                } else {
+                    " "
+                };
+                if line_numbers {
+                    format!("{:>width$} {} ", number, marker, width = width)
+                } else {
+                    format!("{} ", marker)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the lines of the code prefixed with their gutters
+    pub fn get_numbered_lines(&self) -> Vec<String> {
+        let lines = self.code.trim_end_matches('\n').lines();
+        let gutters = self.get_line_gutters();
+
+        if gutters.is_empty() {
+            lines.map(String::from).collect()
+        } else {
+            gutters
+                .into_iter()
+                .zip(lines)
+                .map(|(gutter, line)| gutter + line)
+                .collect()
+        }
+    }
+}
+
 impl Image {
+    /// Returns the converted image data. The data is kept
+    /// so that the document can be rendered multiple times
     pub fn get_content(&self) -> Option<Vec<u8>> {
-        let mut data = None;
-        std::mem::swap(&mut data, &mut self.image_data.lock().data);
-
-        data
+        self.image_data.lock().data.clone()
     }
 
     pub fn get_mime_type(&self) -> Mime {
         self.image_data.lock().mime.clone()
     }
+
+    /// Returns the width and height of the converted image
+    pub fn get_dimensions(&self) -> Option<(u32, u32)> {
+        self.image_data.lock().dimensions
+    }
+
+    /// Returns the smaller variants of the image for responsive output
+    pub fn get_variants(&self) -> Vec<ImageVariant> {
+        self.image_data.lock().variants.clone()
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BibEntry {
     pub key: String,
+    #[serde(with = "serialization::bib_entry")]
     pub entry: BibliographyEntryReference,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BibReference {
     pub(crate) key: String,
+    #[serde(rename = "entry", with = "serialization::bib_anchor")]
     pub(crate) entry_anchor: Arc<Mutex<BibRefAnchor>>,
     pub(crate) display: Option<String>,
 }
@@ -909,3 +1102,139 @@ impl Inline {
         }
     }
 }
+
+impl Block {
+    /// Returns the keys of all anchors contained in the block
+    pub fn get_anchors(&self) -> Vec<String> {
+        let mut anchors = Vec::new();
+        self.collect_anchors(&mut anchors);
+
+        anchors
+    }
+
+    fn collect_anchors(&self, anchors: &mut Vec<String>) {
+        match self {
+            Block::Section(s) => {
+                anchors.push(s.header.anchor.clone());
+                s.header.line.collect_anchors(anchors);
+                s.elements.iter().for_each(|e| e.collect_anchors(anchors));
+            }
+            Block::Paragraph(p) => p.elements.iter().for_each(|l| l.collect_anchors(anchors)),
+            Block::List(l) => l.items.iter().for_each(|i| i.collect_anchors(anchors)),
+            Block::Table(t) => std::iter::once(&t.header)
+                .chain(t.rows.iter())
+                .flat_map(|r| r.cells.iter())
+                .for_each(|c| c.text.collect_anchors(anchors)),
+            Block::Quote(q) => q
+                .text
+                .iter()
+                .flat_map(|l| l.subtext.iter())
+                .for_each(|i| i.collect_anchors(anchors)),
+            Block::Import(i) => {
+                if let Some(document) = &i.anchor.read().unwrap().document {
+                    document
+                        .elements
+                        .iter()
+                        .for_each(|e| e.collect_anchors(anchors))
+                }
+            }
+            Block::Placeholder(p) => {
+                if let Some(value) = &p.read().unwrap().value {
+                    value.collect_anchors(anchors)
+                }
+            }
+            Block::CodeBlock(c) => {
+                if let Some(anchor) = c.get_anchor() {
+                    anchors.push(anchor);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn number_listings(&mut self, count: &mut usize) {
+        match self {
+            Block::Section(s) => s.elements.iter_mut().for_each(|e| e.number_listings(count)),
+            Block::CodeBlock(c) if c.has_caption() => {
+                *count += 1;
+                c.number = Some(*count);
+            }
+            _ => {}
+        }
+    }
+
+    fn collect_listing_numbers(&self, listings: &mut HashMap<String, usize>) {
+        match self {
+            Block::Section(s) => s
+                .elements
+                .iter()
+                .for_each(|e| e.collect_listing_numbers(listings)),
+            Block::CodeBlock(c) => {
+                if let (Some(anchor), Some(number)) = (c.get_anchor(), c.number) {
+                    listings.insert(anchor, number);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ListItem {
+    fn collect_anchors(&self, anchors: &mut Vec<String>) {
+        self.text.collect_anchors(anchors);
+        self.children
+            .iter()
+            .for_each(|c| c.collect_anchors(anchors));
+    }
+}
+
+impl Element {
+    fn collect_anchors(&self, anchors: &mut Vec<String>) {
+        match self {
+            Element::Block(b) => b.collect_anchors(anchors),
+            Element::Line(l) => l.collect_anchors(anchors),
+            Element::Inline(i) => i.collect_anchors(anchors),
+        }
+    }
+}
+
+impl Line {
+    fn collect_anchors(&self, anchors: &mut Vec<String>) {
+        match self {
+            Line::Text(t) => t.subtext.iter().for_each(|i| i.collect_anchors(anchors)),
+            Line::Anchor(a) => {
+                anchors.push(a.key.clone());
+                a.inner.collect_anchors(anchors);
+            }
+            Line::Centered(c) => c
+                .line
+                .subtext
+                .iter()
+                .for_each(|i| i.collect_anchors(anchors)),
+            _ => {}
+        }
+    }
+}
+
+impl Inline {
+    fn collect_anchors(&self, anchors: &mut Vec<String>) {
+        match self {
+            Inline::Anchor(a) => {
+                anchors.push(a.key.clone());
+                a.inner.collect_anchors(anchors);
+            }
+            Inline::Placeholder(p) => {
+                if let Some(value) = &p.read().unwrap().value {
+                    value.collect_anchors(anchors)
+                }
+            }
+            Inline::Bold(b) => b.value.iter().for_each(|i| i.collect_anchors(anchors)),
+            Inline::Italic(i) => i.value.iter().for_each(|i| i.collect_anchors(anchors)),
+            Inline::Underlined(u) => u.value.iter().for_each(|i| i.collect_anchors(anchors)),
+            Inline::Striked(s) => s.value.iter().for_each(|i| i.collect_anchors(anchors)),
+            Inline::Superscript(s) => s.value.iter().for_each(|i| i.collect_anchors(anchors)),
+            Inline::Colored(c) => c.value.collect_anchors(anchors),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,262 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::*;
+use crate::settings::Settings;
+use crate::utils::downloads::PendingDownload;
+use crate::utils::image_converting::PendingImage;
+use bibliographix::bibliography::bibliography_entry::{
+    BibliographyEntry, BibliographyEntryReference,
+};
+use bibliographix::bibliography::keys::K_KEY;
+use bibliographix::bibliography::FromHashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The serialized form of a document. The managers for downloads, images, glossary
+/// and bibliography are not serialized but recreated when the document is read.
+#[derive(Deserialize)]
+pub(crate) struct SerializedDocument {
+    elements: Vec<Block>,
+    path: Option<String>,
+    config: Settings,
+    stylesheets: Vec<String>,
+}
+
+impl From<SerializedDocument> for Document {
+    fn from(other: SerializedDocument) -> Self {
+        let mut document = Document::new();
+        document.elements = other.elements;
+        document.path = other.path;
+        document.config = Arc::new(Mutex::new(other.config));
+
+        for path in other.stylesheets {
+            let download = document.downloads.lock().add_download(path);
+            document.stylesheets.push(download);
+        }
+        let mut images = Vec::new();
+        document
+            .elements
+            .iter()
+            .for_each(|e| e.collect_images(&mut images));
+        images
+            .into_iter()
+            .for_each(|i| document.images.lock().add_pending_image(i));
+
+        document
+    }
+}
+
+/// The serialized form of an image without the image data
+#[derive(Deserialize)]
+pub(crate) struct SerializedImage {
+    url: Url,
+    metadata: Option<InlineMetadata>,
+}
+
+impl From<SerializedImage> for Image {
+    fn from(other: SerializedImage) -> Self {
+        let mut pending_image = PendingImage::new(PathBuf::from(other.url.url.clone()));
+        if let Some(meta) = &other.metadata {
+            pending_image.assign_from_meta(meta)
+        }
+
+        Self {
+            url: other.url,
+            metadata: other.metadata,
+            image_data: Arc::new(Mutex::new(pending_image)),
+        }
+    }
+}
+
+/// The serialized form of math that only contains the asciimath source
+#[derive(Deserialize)]
+pub(crate) struct SerializedMath {
+    source: String,
+}
+
+impl From<SerializedMath> for Math {
+    fn from(other: SerializedMath) -> Self {
+        Self {
+            expression: asciimath_rs::parse(other.source.clone()),
+            source: other.source,
+        }
+    }
+}
+
+impl From<SerializedMath> for MathBlock {
+    fn from(other: SerializedMath) -> Self {
+        Self {
+            expression: asciimath_rs::parse(other.source.clone()),
+            source: other.source,
+        }
+    }
+}
+
+/// Serializes the stylesheets as a list of their paths
+pub(crate) fn serialize_downloads<S: Serializer>(
+    downloads: &[Arc<Mutex<PendingDownload>>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(downloads.iter().map(|d| d.lock().path.clone()))
+}
+
+/// Serializes a bibliography entry as the map of its raw fields
+pub(crate) mod bib_entry {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        entry: &BibliographyEntryReference,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        entry.lock().raw_fields.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BibliographyEntryReference, D::Error> {
+        let fields = HashMap::<String, String>::deserialize(deserializer)?;
+
+        Ok(entry_from_fields(fields))
+    }
+}
+
+/// Serializes the anchor of a bib reference as the raw fields of the assigned entry
+pub(crate) mod bib_anchor {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        anchor: &Arc<Mutex<BibRefAnchor>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        anchor
+            .lock()
+            .entry
+            .as_ref()
+            .map(|e| e.lock().raw_fields.clone())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<Mutex<BibRefAnchor>>, D::Error> {
+        let fields = Option::<HashMap<String, String>>::deserialize(deserializer)?;
+
+        Ok(Arc::new(Mutex::new(BibRefAnchor {
+            entry: fields.map(entry_from_fields),
+        })))
+    }
+}
+
+/// Creates a bibliography entry from its raw fields. Entries with an unknown type
+/// only keep the raw fields.
+fn entry_from_fields(fields: HashMap<String, String>) -> BibliographyEntryReference {
+    let entry = match BibliographyEntry::from_hash_map(&fields) {
+        Ok(entry) => *entry,
+        Err(e) => {
+            log::debug!("Failed to restore bibliography entry: {}", e);
+            let mut entry = BibliographyEntry::new(fields.get(K_KEY).cloned().unwrap_or_default());
+            entry.raw_fields = fields;
+
+            entry
+        }
+    };
+
+    Arc::new(Mutex::new(entry))
+}
+
+impl Element {
+    /// Collects the pending images of all images contained in the element
+    fn collect_images(&self, images: &mut Vec<Arc<Mutex<PendingImage>>>) {
+        match self {
+            Element::Block(b) => b.collect_images(images),
+            Element::Line(l) => l.collect_images(images),
+            Element::Inline(i) => i.collect_images(images),
+        }
+    }
+}
+
+impl Block {
+    fn collect_images(&self, images: &mut Vec<Arc<Mutex<PendingImage>>>) {
+        match self {
+            Block::Section(s) => {
+                s.header.line.collect_images(images);
+                s.elements.iter().for_each(|e| e.collect_images(images));
+            }
+            Block::Paragraph(p) => p.elements.iter().for_each(|l| l.collect_images(images)),
+            Block::List(l) => l.items.iter().for_each(|i| i.collect_images(images)),
+            Block::Table(t) => std::iter::once(&t.header)
+                .chain(t.rows.iter())
+                .flat_map(|r| r.cells.iter())
+                .for_each(|c| c.text.collect_images(images)),
+            Block::Quote(q) => q
+                .text
+                .iter()
+                .flat_map(|l| l.subtext.iter())
+                .for_each(|i| i.collect_images(images)),
+            Block::Placeholder(p) => {
+                if let Some(value) = &p.read().unwrap().value {
+                    value.collect_images(images)
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ListItem {
+    fn collect_images(&self, images: &mut Vec<Arc<Mutex<PendingImage>>>) {
+        self.text.collect_images(images);
+        self.children.iter().for_each(|c| c.collect_images(images));
+    }
+}
+
+impl Line {
+    fn collect_images(&self, images: &mut Vec<Arc<Mutex<PendingImage>>>) {
+        let subtext = match self {
+            Line::Text(t) => &t.subtext,
+            Line::Centered(c) => &c.line.subtext,
+            Line::RefLink(r) => &r.description.subtext,
+            Line::Anchor(a) => return a.inner.collect_images(images),
+            _ => return,
+        };
+        subtext.iter().for_each(|i| i.collect_images(images));
+    }
+}
+
+impl Inline {
+    fn collect_images(&self, images: &mut Vec<Arc<Mutex<PendingImage>>>) {
+        match self {
+            Inline::Image(i) => images.push(Arc::clone(&i.image_data)),
+            Inline::Url(u) => {
+                if let Some(description) = &u.description {
+                    description.iter().for_each(|i| i.collect_images(images))
+                }
+            }
+            Inline::Placeholder(p) => {
+                if let Some(value) = &p.read().unwrap().value {
+                    value.collect_images(images)
+                }
+            }
+            Inline::TemplateVar(v) => {
+                if let Some(value) = &v.read().unwrap().value {
+                    value.collect_images(images)
+                }
+            }
+            Inline::Anchor(a) => a.inner.collect_images(images),
+            Inline::Bold(b) => b.value.iter().for_each(|i| i.collect_images(images)),
+            Inline::Italic(i) => i.value.iter().for_each(|i| i.collect_images(images)),
+            Inline::Underlined(u) => u.value.iter().for_each(|i| i.collect_images(images)),
+            Inline::Striked(s) => s.value.iter().for_each(|i| i.collect_images(images)),
+            Inline::Superscript(s) => s.value.iter().for_each(|i| i.collect_images(images)),
+            Inline::Colored(c) => c.value.collect_images(images),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,117 @@
+/*!
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+(function () {
+    var MAX_RESULTS = 10;
+    var MIN_TERM_LENGTH = 2;
+    // keep in sync with the rules in search_index.rs
+    var SUFFIX_RULES = [
+        ["sses", "ss"],
+        ["ies", "y"],
+        ["ss", "ss"],
+        ["ingly", ""],
+        ["edly", ""],
+        ["ing", ""],
+        ["ed", ""],
+        ["ly", ""],
+        ["s", ""]
+    ];
+
+    function stem(word) {
+        for (var i = 0; i < SUFFIX_RULES.length; i++) {
+            var suffix = SUFFIX_RULES[i][0];
+            var stemLength = Array.from(word).length - suffix.length;
+
+            if (word.endsWith(suffix) && stemLength >= 3) {
+                return word.slice(0, word.length - suffix.length) + SUFFIX_RULES[i][1];
+            }
+        }
+        return word;
+    }
+
+    function getTerms(query) {
+        return query.toLowerCase()
+            .split(/[^\p{L}\p{N}]+/u)
+            .filter(function (word) {
+                return Array.from(word).length >= MIN_TERM_LENGTH;
+            })
+            .map(stem);
+    }
+
+    /**
+     * Returns the indices of all sections that contain a term starting with the given term
+     */
+    function findSections(index, term) {
+        var sections = new Set();
+
+        Object.keys(index.terms).forEach(function (key) {
+            if (key.startsWith(term)) {
+                index.terms[key].forEach(function (section) {
+                    sections.add(section);
+                });
+            }
+        });
+        return sections;
+    }
+
+    function search(index, query) {
+        var terms = getTerms(query);
+        if (terms.length === 0) {
+            return [];
+        }
+        var matches = null;
+
+        terms.forEach(function (term) {
+            var sections = findSections(index, term);
+            matches = matches === null ? sections : new Set(Array.from(matches).filter(function (s) {
+                return sections.has(s);
+            }));
+        });
+
+        return Array.from(matches)
+            .sort(function (a, b) {
+                return a - b;
+            })
+            .slice(0, MAX_RESULTS)
+            .map(function (i) {
+                return index.sections[i];
+            });
+    }
+
+    function renderResults(list, results) {
+        list.innerHTML = "";
+
+        results.forEach(function (section) {
+            var item = document.createElement("li");
+            var link = document.createElement("a");
+            link.href = section.anchor ? section.page + "#" + section.anchor : (section.page || "#");
+            link.textContent = section.title || "Home";
+            item.appendChild(link);
+            list.appendChild(item);
+        });
+    }
+
+    function init() {
+        var index = window.snekdownSearchIndex;
+        if (!index) {
+            return;
+        }
+        document.querySelectorAll(".searchBox").forEach(function (box) {
+            var input = box.querySelector(".searchInput");
+            var list = box.querySelector(".searchResults");
+
+            input.addEventListener("input", function () {
+                renderResults(list, search(index, input.value));
+            });
+        });
+    }
+
+    if (document.readyState === "loading") {
+        document.addEventListener("DOMContentLoaded", init);
+    } else {
+        init();
+    }
+})();
@@ -0,0 +1,70 @@
+/*!
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+(function () {
+    var slides = Array.from(document.querySelectorAll(".slide"));
+    var counter = document.querySelector(".slideCounter");
+    var current = 0;
+
+    function show(index) {
+        current = Math.max(0, Math.min(slides.length - 1, index));
+        slides.forEach(function (slide, i) {
+            slide.classList.toggle("current", i === current);
+        });
+        if (counter) {
+            counter.textContent = (current + 1) + " / " + slides.length;
+        }
+        if (slides.length > 0 && location.hash !== "#" + slides[current].id) {
+            history.replaceState(null, "", "#" + slides[current].id);
+        }
+    }
+
+    /**
+     * Shows the slide that contains the element referenced by the location hash
+     */
+    function showHash() {
+        var id = decodeURIComponent(location.hash.slice(1));
+        var element = id ? document.getElementById(id) : null;
+        var slide = element ? element.closest(".slide") : null;
+
+        show(slide ? slides.indexOf(slide) : current);
+    }
+
+    document.addEventListener("keydown", function (event) {
+        if (event.ctrlKey || event.altKey || event.metaKey) {
+            return;
+        }
+        switch (event.key) {
+            case "ArrowRight":
+            case "ArrowDown":
+            case "PageDown":
+            case " ":
+                show(current + 1);
+                break;
+            case "ArrowLeft":
+            case "ArrowUp":
+            case "PageUp":
+            case "Backspace":
+                show(current - 1);
+                break;
+            case "Home":
+                show(0);
+                break;
+            case "End":
+                show(slides.length - 1);
+                break;
+            case "n":
+            case "s":
+                document.body.classList.toggle("showNotes");
+                break;
+            default:
+                return;
+        }
+        event.preventDefault();
+    });
+    window.addEventListener("hashchange", showHash);
+    showHash();
+})();
@@ -0,0 +1,49 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::format::PlaceholderTemplate;
+use crate::settings::Settings;
+use htmlescape::encode_minimal;
+
+/// The placeholder that is replaced with the title of the current chapter
+const P_CHAPTER: &str = "chapter";
+
+/// Renders a header or footer template replacing placeholders with the
+/// document metadata, the custom attributes and the current chapter title
+pub fn render_header_footer(template: &str, settings: &Settings, chapter: &str) -> String {
+    let mut template = PlaceholderTemplate::new(template.to_string());
+    let metadata = &settings.metadata;
+
+    for (key, value) in &settings.custom_attributes {
+        template.add_replacement(key, &encode_minimal(value));
+    }
+    if let Some(title) = &metadata.title {
+        template.add_replacement("title", &encode_minimal(title));
+    }
+    if let Some(author) = &metadata.author {
+        template.add_replacement("author", &encode_minimal(author));
+    }
+    if let Some(description) = &metadata.description {
+        template.add_replacement("description", &encode_minimal(description));
+    }
+    template.add_replacement("keywords", &encode_minimal(&metadata.keywords.join(", ")));
+    template.add_replacement("language", &encode_minimal(&metadata.language));
+    template.add_replacement(P_CHAPTER, &encode_minimal(chapter));
+
+    template.render()
+}
+
+/// Returns if the header or footer contains the title of the current chapter
+pub fn has_running_header(settings: &Settings) -> bool {
+    let placeholder = format!("{{{{{}}}}}", P_CHAPTER);
+
+    settings
+        .pdf
+        .header_template
+        .iter()
+        .chain(settings.pdf.footer_template.iter())
+        .any(|t| t.contains(&placeholder))
+}
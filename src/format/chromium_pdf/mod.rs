@@ -5,73 +5,359 @@
  */
 
 use crate::elements::Document;
+use crate::format::chromium_pdf::header_footer::{has_running_header, render_header_footer};
+use crate::format::chromium_pdf::postprocess::{
+    add_outline_and_info, create_destination_links_script, get_anchor_pages, replace_page_contents,
+};
 use crate::format::chromium_pdf::result::{PdfRenderingError, PdfRenderingResult};
 use crate::format::html::html_writer::HTMLWriter;
 use crate::format::html::to_html::ToHtml;
+use crate::format::pdf_outline::{create_outline, OutlineEntry};
+use crate::format::slides::render_to_slides;
+use crate::format::style::CodeStyle;
+use crate::format::{RenderResult, Renderer};
+use crate::settings::pdf_settings::{PDFMarginSettings, PDFSettings};
 use crate::settings::Settings;
 use crate::utils::caching::CacheStorage;
 use bibliographix::Mutex;
+use headless_chrome::browser::default_executable;
 use headless_chrome::protocol::page::PrintToPdfOptions;
-use headless_chrome::{Browser, Tab};
+use headless_chrome::{Browser, LaunchOptionsBuilder, Tab};
 use std::env;
 use std::fs;
 use std::fs::OpenOptions;
-use std::io::BufWriter;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 
+pub mod header_footer;
+pub mod postprocess;
 pub mod result;
 
+/// The time the browser is kept alive without any events.
+/// This needs to be long for the browser to survive between renders in watch mode.
+const BROWSER_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24);
+
 /// Renders the document to pdf and returns the resulting bytes
 pub fn render_to_pdf(document: Document) -> PdfRenderingResult<Vec<u8>> {
-    let cache = CacheStorage::new();
-    let mut file_path = PathBuf::from("tmp-document.html");
-    file_path = cache.get_file_path(&file_path);
-
-    if !file_path.parent().map(|p| p.exists()).unwrap_or(false) {
-        file_path = env::current_dir()?;
-        file_path.push(PathBuf::from(".tmp-document.html"))
-    }
-
-    let config = document.config.clone();
-    let mathjax = config.lock().features.include_mathjax;
-
-    let handle = thread::spawn({
-        let file_path = file_path.clone();
-        move || {
-            log::info!("Rendering html...");
-            let writer = BufWriter::new(
-                OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(file_path)?,
-            );
-            let mut html_writer =
-                HTMLWriter::new(Box::new(writer), document.config.lock().style.theme.clone());
-            document.to_html(&mut html_writer)?;
-            log::info!("Successfully rendered temporary html file!");
-            html_writer.flush()
+    PdfRenderer::new().render(document)
+}
+
+/// A pdf renderer that keeps the browser and its tab alive between renders.
+/// If the browser crashed or the connection to it was lost it gets relaunched.
+#[derive(Default)]
+pub struct PdfRenderer {
+    browser: Option<Browser>,
+    tab: Option<Arc<Tab>>,
+}
+
+impl PdfRenderer {
+    /// Creates a new renderer. The browser is launched on the first render.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders the document to pdf and returns the resulting bytes
+    pub fn render(&mut self, document: Document) -> PdfRenderingResult<Vec<u8>> {
+        self.render_document(document, false)
+    }
+
+    /// Renders the document as a slide deck to pdf with one slide per page.
+    /// The page size is taken from the print stylesheet of the slides.
+    pub fn render_slides(&mut self, mut document: Document) -> PdfRenderingResult<Vec<u8>> {
+        // the settings are replaced instead of modified because they are shared with clones of the document
+        let mut settings = document.config.lock().clone();
+        settings.pdf.prefer_css_page_size = true;
+        settings.pdf.display_header_footer = false;
+        settings.pdf.header_template = None;
+        settings.pdf.footer_template = None;
+        settings.pdf.margin = PDFMarginSettings {
+            top: Some(0.0),
+            bottom: Some(0.0),
+            left: Some(0.0),
+            right: Some(0.0),
+        };
+        document.config = Arc::new(Mutex::new(settings));
+
+        self.render_document(document, true)
+    }
+
+    fn render_document(&mut self, document: Document, slides: bool) -> PdfRenderingResult<Vec<u8>> {
+        let cache = CacheStorage::new();
+        let mut file_path = PathBuf::from("tmp-document.html");
+        file_path = cache.get_file_path(&file_path);
+
+        if !file_path.parent().map(|p| p.exists()).unwrap_or(false) {
+            file_path = env::current_dir()?;
+            file_path.push(PathBuf::from(".tmp-document.html"))
+        }
+
+        let config = document.config.clone();
+        let outline = create_outline(&document);
+        let _temporary_file = TemporaryFile(file_path.clone());
+
+        let handle = thread::spawn({
+            let file_path = file_path.clone();
+            move || {
+                log::info!("Rendering html...");
+                let writer = BufWriter::new(
+                    OpenOptions::new()
+                        .create(true)
+                        .write(true)
+                        .truncate(true)
+                        .open(file_path)?,
+                );
+                if slides {
+                    render_to_slides(document, Box::new(writer))?;
+                    log::info!("Successfully rendered temporary html file!");
+                    return Ok(());
+                }
+                let settings = document.config.lock().clone();
+                let mut html_writer =
+                    HTMLWriter::new(Box::new(writer), settings.style.theme.clone());
+                html_writer.set_safe_mode(settings.safe_mode);
+                html_writer.set_code_style(CodeStyle::load(&settings, &document.get_root_dir()));
+                document.to_html(&mut html_writer)?;
+                log::info!("Successfully rendered temporary html file!");
+                html_writer.flush()
+            }
+        });
+
+        let pdf_settings = config.lock().pdf.clone();
+        let tab = self.get_tab(&pdf_settings);
+        handle.join().unwrap()?;
+        let tab = tab?;
+
+        let result = match print_file(&tab, &file_path, &config, &outline) {
+            Err(PdfRenderingError::ChromiumError(e)) if !self.is_alive() => {
+                log::warn!("Lost connection to the browser: {}", e);
+                log::info!("Relaunching browser...");
+                let tab = self.get_tab(&pdf_settings)?;
+                print_file(&tab, &file_path, &config, &outline)
+            }
+            result => result,
+        }?;
+        log::info!("Adding outline and document info...");
+        let metadata = config.lock().metadata.clone();
+        let result = add_outline_and_info(result, &outline, &metadata)?;
+
+        Ok(result)
+    }
+
+    /// Closes the browser
+    pub fn close(&mut self) {
+        self.tab = None;
+        self.browser = None;
+    }
+
+    /// Returns if the browser is running and responds
+    fn is_alive(&self) -> bool {
+        self.browser
+            .as_ref()
+            .map(|b| b.get_version().is_ok())
+            .unwrap_or(false)
+    }
+
+    /// Returns the tab used for rendering and launches the browser if
+    /// it isn't running or doesn't respond anymore
+    fn get_tab(&mut self, settings: &PDFSettings) -> PdfRenderingResult<Arc<Tab>> {
+        if !self.is_alive() {
+            if self.browser.is_some() {
+                log::warn!("The browser doesn't respond anymore. Relaunching...");
+            }
+            self.close();
+            log::debug!("Launching browser...");
+            let browser = launch_browser(settings)?;
+            self.tab = Some(browser.wait_for_initial_tab()?);
+            self.browser = Some(browser);
+        }
+        match &self.tab {
+            Some(tab) => Ok(Arc::clone(tab)),
+            None => Err(PdfRenderingError::ChromiumError(failure::err_msg(
+                "No tab available",
+            ))),
+        }
+    }
+}
+
+impl Renderer for PdfRenderer {
+    fn name(&self) -> &str {
+        "pdf"
+    }
+
+    fn extension(&self) -> &str {
+        "pdf"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        mut writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        writer.write_all(&PdfRenderer::render(self, document)?)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Renders documents as slide decks to pdf
+#[derive(Default)]
+pub struct SlidesPdfRenderer {
+    renderer: PdfRenderer,
+}
+
+impl SlidesPdfRenderer {
+    /// Creates a new renderer. The browser is launched on the first render.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Renderer for SlidesPdfRenderer {
+    fn name(&self) -> &str {
+        "slides-pdf"
+    }
+
+    fn extension(&self) -> &str {
+        "pdf"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        mut writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        writer.write_all(&self.renderer.render_slides(document)?)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// The temporary html file that is printed. It is removed when it goes out of scope.
+struct TemporaryFile(PathBuf);
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        if self.0.exists() {
+            log::info!("Removing temporary html...");
+            if let Err(e) = fs::remove_file(&self.0) {
+                log::error!("Failed to remove temporary html {:?}: {}", self.0, e);
+            }
         }
+    }
+}
+
+/// Launches a new headless browser that stays alive until it's dropped
+fn launch_browser(settings: &PDFSettings) -> PdfRenderingResult<Browser> {
+    let executable = match &settings.browser_path {
+        Some(path) => PathBuf::from(path),
+        None => default_executable().map_err(failure::err_msg)?,
+    };
+    log::debug!("Using browser executable {:?}", executable);
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let browser = LaunchOptionsBuilder::default()
+            .path(Some(executable))
+            .idle_browser_timeout(BROWSER_IDLE_TIMEOUT)
+            .build()
+            .map_err(failure::err_msg)
+            .and_then(Browser::new);
+        let _ = tx.send(browser);
     });
 
-    let browser = Browser::default()?;
-    let tab = browser.wait_for_initial_tab()?;
-    handle.join().unwrap()?;
+    match rx.recv_timeout(Duration::from_secs(settings.browser_launch_timeout)) {
+        Ok(browser) => Ok(browser?),
+        Err(_) => {
+            log::error!("The browser didn't start within the launch timeout");
+            Err(PdfRenderingError::Timeout)
+        }
+    }
+}
+
+/// Loads the html file in the given tab and prints it to pdf
+fn print_file(
+    tab: &Tab,
+    file_path: &Path,
+    config: &Arc<Mutex<Settings>>,
+    outline: &[OutlineEntry],
+) -> PdfRenderingResult<Vec<u8>> {
+    let settings = config.lock().clone();
     tab.navigate_to(format!("file:///{}", file_path.to_string_lossy()).as_str())?;
     tab.wait_until_navigated()?;
 
-    if mathjax {
-        wait_for_mathjax(&tab, Duration::from_secs(60))?;
+    if settings.features.include_mathjax {
+        wait_for_mathjax(tab, Duration::from_secs(settings.pdf.mathjax_timeout))?;
     }
+    tab.evaluate(&create_destination_links_script(outline), false)?;
     log::info!("Rendering pdf...");
-    let result = tab.print_to_pdf(Some(get_pdf_options(config)))?;
-    log::info!("Removing temporary html...");
-    fs::remove_file(file_path)?;
+    let result = tab.print_to_pdf(Some(get_pdf_options(&settings, "")))?;
+
+    if !has_running_header(&settings) || outline.is_empty() {
+        return Ok(result);
+    }
+    if settings.pdf.page_ranges.is_some() {
+        log::warn!("Running headers are not supported in combination with page ranges");
+        return Ok(result);
+    }
+
+    print_chapters(tab, result, &settings, outline)
+}
+
+/// Prints the pages of each top level chapter with the title of
+/// the chapter in the header and footer and combines them into one document
+fn print_chapters(
+    tab: &Tab,
+    document: Vec<u8>,
+    settings: &Settings,
+    outline: &[OutlineEntry],
+) -> PdfRenderingResult<Vec<u8>> {
+    let anchors = outline
+        .iter()
+        .map(|e| e.anchor.clone())
+        .collect::<Vec<String>>();
+    let (anchor_pages, page_count) = get_anchor_pages(&document, &anchors)?;
+    let mut chapters: Vec<(usize, &str)> = outline
+        .iter()
+        .filter_map(|e| anchor_pages.get(&e.anchor).map(|p| (*p, e.title.as_str())))
+        .collect();
+    chapters.dedup_by(|next, previous| {
+        if next.0 == previous.0 {
+            // the last chapter starting on a page is the one that continues on the next pages
+            previous.1 = next.1;
+            true
+        } else {
+            false
+        }
+    });
+    let mut parts = Vec::new();
+
+    for (index, (start, title)) in chapters.iter().enumerate() {
+        let end = chapters
+            .get(index + 1)
+            .map(|(next, _)| *next)
+            .unwrap_or(page_count);
+        if end <= *start {
+            continue;
+        }
+        log::debug!(
+            "Rendering pages {} to {} of chapter {}",
+            start + 1,
+            end,
+            title
+        );
+        let mut options = get_pdf_options(settings, title);
+        options.page_ranges = Some(format!("{}-{}", start + 1, end));
+        parts.push((*start, tab.print_to_pdf(Some(options))?));
+    }
 
-    Ok(result)
+    replace_page_contents(document, parts)
 }
 
 /// Waits for mathjax to be finished
@@ -112,23 +398,28 @@ fn wait_for_mathjax(tab: &Tab, timeout: Duration) -> PdfRenderingResult<()> {
     Ok(())
 }
 
-fn get_pdf_options(config: Arc<Mutex<Settings>>) -> PrintToPdfOptions {
-    let config = config.lock().pdf.clone();
+fn get_pdf_options(settings: &Settings, chapter: &str) -> PrintToPdfOptions {
+    let config = settings.pdf.clone();
+    let (paper_width, paper_height) = config.page_dimensions();
     PrintToPdfOptions {
-        landscape: None,
+        landscape: Some(config.landscape),
         display_header_footer: Some(config.display_header_footer),
         print_background: Some(true),
         scale: Some(config.page_scale),
-        paper_width: config.page_width,
-        paper_height: config.page_height,
+        paper_width,
+        paper_height,
         margin_top: config.margin.top,
         margin_bottom: config.margin.bottom,
         margin_left: config.margin.left,
         margin_right: config.margin.right,
-        page_ranges: None,
+        page_ranges: config.page_ranges,
         ignore_invalid_page_ranges: None,
-        header_template: config.header_template,
-        footer_template: config.footer_template,
-        prefer_css_page_size: None,
+        header_template: config
+            .header_template
+            .map(|t| render_header_footer(&t, settings, chapter)),
+        footer_template: config
+            .footer_template
+            .map(|t| render_header_footer(&t, settings, chapter)),
+        prefer_css_page_size: Some(config.prefer_css_page_size),
     }
 }
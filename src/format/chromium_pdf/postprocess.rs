@@ -0,0 +1,261 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::format::chromium_pdf::result::PdfRenderingResult;
+use crate::format::pdf_outline::{apply_document_info, write_outline, OutlineEntry};
+use crate::settings::metadata_settings::MetadataSettings;
+use lopdf::{Dictionary, Object, ObjectId};
+use std::collections::HashMap;
+
+/// Adds the outline and the document information to a pdf rendered by chromium
+pub fn add_outline_and_info(
+    data: Vec<u8>,
+    outline: &[OutlineEntry],
+    metadata: &MetadataSettings,
+) -> PdfRenderingResult<Vec<u8>> {
+    let mut doc = lopdf::Document::load_mem(&data)?;
+    apply_document_info(&mut doc, metadata);
+
+    if let Some(first_page) = doc.get_pages().values().next().cloned() {
+        let destinations = get_named_destinations(&doc);
+        log::debug!("Found {} named destinations", destinations.len());
+        let fallback = Object::Array(vec![first_page.into(), "Fit".into()]);
+
+        if let Some(outline_id) = write_outline(&mut doc, outline, &destinations, fallback) {
+            let catalog_id = doc.trailer.get(b"Root")?.as_reference()?;
+            let catalog = doc.get_object_mut(catalog_id)?.as_dict_mut()?;
+            catalog.set("Outlines", outline_id);
+            catalog.set("PageMode", "UseOutlines");
+        }
+    }
+    let mut result = Vec::new();
+    doc.save_to(&mut result)?;
+
+    Ok(result)
+}
+
+/// Returns the index of the page each of the given anchors is located on
+/// together with the total number of pages
+pub fn get_anchor_pages(
+    data: &[u8],
+    anchors: &[String],
+) -> PdfRenderingResult<(HashMap<String, usize>, usize)> {
+    let doc = lopdf::Document::load_mem(data)?;
+    let page_indices = doc
+        .get_pages()
+        .values()
+        .enumerate()
+        .map(|(index, id)| (*id, index))
+        .collect::<HashMap<ObjectId, usize>>();
+    let destinations = get_named_destinations(&doc);
+    let mut anchor_pages = HashMap::new();
+
+    for anchor in anchors {
+        let page = destinations
+            .get(anchor)
+            .and_then(|d| d.as_array().ok())
+            .and_then(|d| d.first())
+            .and_then(|p| p.as_reference().ok())
+            .and_then(|p| page_indices.get(&p));
+        if let Some(page) = page {
+            anchor_pages.insert(anchor.clone(), *page);
+        }
+    }
+
+    Ok((anchor_pages, page_indices.len()))
+}
+
+/// Replaces the contents of pages of the base document with the pages of the given parts.
+/// Each part is a pdf that contains a continuous range of pages of the same layout
+/// starting at the given page index.
+pub fn replace_page_contents(
+    base: Vec<u8>,
+    parts: Vec<(usize, Vec<u8>)>,
+) -> PdfRenderingResult<Vec<u8>> {
+    let mut doc = lopdf::Document::load_mem(&base)?;
+    let base_pages = doc.get_pages().values().cloned().collect::<Vec<ObjectId>>();
+
+    for (start, data) in parts {
+        let mut part = lopdf::Document::load_mem(&data)?;
+        part.renumber_objects_with(doc.max_id + 1);
+        let part_pages = part
+            .get_pages()
+            .values()
+            .cloned()
+            .collect::<Vec<ObjectId>>();
+        let mut replacements = Vec::new();
+
+        for (offset, part_page) in part_pages.iter().enumerate() {
+            let base_page = match base_pages.get(start + offset) {
+                Some(page) => *page,
+                None => break,
+            };
+            let part_dict = part.get_dictionary(*part_page)?;
+            let mut entries = Vec::new();
+
+            for key in [b"Contents".as_ref(), b"Resources", b"Annots"].iter() {
+                if let Ok(value) = part_dict.get(key) {
+                    entries.push((key.to_vec(), value.clone()));
+                }
+            }
+            replacements.push((base_page, entries));
+        }
+        doc.max_id = doc.max_id.max(part.max_id);
+        doc.objects.append(&mut part.objects);
+
+        for (page, entries) in replacements {
+            let page = doc.get_object_mut(page)?.as_dict_mut()?;
+            for (key, value) in entries {
+                page.set(key, value);
+            }
+        }
+    }
+    doc.prune_objects();
+    let mut result = Vec::new();
+    doc.save_to(&mut result)?;
+
+    Ok(result)
+}
+
+/// Creates the javascript that inserts invisible links to all outline entries.
+/// Chromium only emits named destinations for elements that are targets of a link
+/// so the outline can only point to anchors that are linked somewhere in the document.
+pub fn create_destination_links_script(outline: &[OutlineEntry]) -> String {
+    let mut anchors = Vec::new();
+    collect_anchors(outline, &mut anchors);
+    let anchors = anchors
+        .iter()
+        .map(|a| format!("\"{}\"", escape_js_string(a)))
+        .collect::<Vec<String>>()
+        .join(",");
+
+    format!(
+        "(function(anchors) {{\
+            var container = document.createElement('div');\
+            container.style.cssText = 'position:absolute;top:0;left:0;width:0;height:0;overflow:hidden;';\
+            anchors.forEach(function(anchor) {{\
+                var link = document.createElement('a');\
+                link.href = '#' + anchor;\
+                container.appendChild(link);\
+            }});\
+            document.body.appendChild(container);\
+            return true;\
+        }})([{}]);",
+        anchors
+    )
+}
+
+fn collect_anchors(entries: &[OutlineEntry], anchors: &mut Vec<String>) {
+    for entry in entries {
+        anchors.push(entry.anchor.clone());
+        collect_anchors(&entry.children, anchors);
+    }
+}
+
+/// Escapes a string to be used inside a double quoted javascript string
+fn escape_js_string(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                c.to_string()
+            } else {
+                c.encode_utf16(&mut [0; 2])
+                    .iter()
+                    .map(|u| format!("\\u{:04x}", u))
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+/// Returns the named destinations of the document from the catalogs
+/// Dests dictionary and the Dests name tree
+fn get_named_destinations(doc: &lopdf::Document) -> HashMap<String, Object> {
+    let mut destinations = HashMap::new();
+    let catalog = match doc.catalog() {
+        Ok(catalog) => catalog,
+        Err(_) => return destinations,
+    };
+
+    if let Ok(dests) = catalog
+        .get(b"Dests")
+        .and_then(|d| doc.dereference(d))
+        .and_then(|(_, d)| d.as_dict())
+    {
+        for (name, dest) in dests.iter() {
+            if let Some(dest) = resolve_destination(doc, dest) {
+                destinations.insert(String::from_utf8_lossy(name).to_string(), dest);
+            }
+        }
+    }
+    if let Ok(tree) = catalog
+        .get(b"Names")
+        .and_then(|n| doc.dereference(n))
+        .and_then(|(_, n)| n.as_dict())
+        .and_then(|n| n.get(b"Dests"))
+        .and_then(|d| doc.dereference(d))
+        .and_then(|(_, d)| d.as_dict())
+    {
+        collect_name_tree(doc, tree, &mut destinations, 0);
+    }
+
+    destinations
+}
+
+/// Collects the entries of a name tree node and its children
+fn collect_name_tree(
+    doc: &lopdf::Document,
+    node: &Dictionary,
+    destinations: &mut HashMap<String, Object>,
+    depth: usize,
+) {
+    if depth > 32 {
+        return;
+    }
+    if let Ok(names) = node
+        .get(b"Names")
+        .and_then(|n| doc.dereference(n))
+        .and_then(|(_, n)| n.as_array())
+    {
+        for pair in names.chunks(2) {
+            if let [name, dest] = pair {
+                if let (Ok(name), Some(dest)) = (name.as_str(), resolve_destination(doc, dest)) {
+                    destinations.insert(String::from_utf8_lossy(name).to_string(), dest);
+                }
+            }
+        }
+    }
+    if let Ok(kids) = node
+        .get(b"Kids")
+        .and_then(|k| doc.dereference(k))
+        .and_then(|(_, k)| k.as_array())
+    {
+        for kid in kids {
+            if let Ok(kid) = doc.dereference(kid).and_then(|(_, k)| k.as_dict()) {
+                collect_name_tree(doc, kid, destinations, depth + 1);
+            }
+        }
+    }
+}
+
+/// Returns the explicit destination array of a destination entry
+fn resolve_destination(doc: &lopdf::Document, dest: &Object) -> Option<Object> {
+    let (_, dest) = doc.dereference(dest).ok()?;
+
+    match dest {
+        Object::Array(_) => Some(dest.clone()),
+        Object::Dictionary(dict) => {
+            let (_, d) = doc.dereference(dict.get(b"D").ok()?).ok()?;
+            if let Object::Array(_) = d {
+                Some(d.clone())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
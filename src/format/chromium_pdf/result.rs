@@ -16,6 +16,7 @@ pub enum PdfRenderingError {
     ChromiumError(failure::Error),
     Timeout,
     HtmlRenderingError,
+    PdfError(lopdf::Error),
 }
 
 impl Display for PdfRenderingError {
@@ -25,6 +26,7 @@ impl Display for PdfRenderingError {
             PdfRenderingError::Timeout => write!(f, "Rendering timed out"),
             PdfRenderingError::ChromiumError(e) => write!(f, "Chromium Error: {}", e),
             PdfRenderingError::HtmlRenderingError => write!(f, "Failed to render html"),
+            PdfRenderingError::PdfError(e) => write!(f, "PDF Error: {}", e),
         }
     }
 }
@@ -42,3 +44,9 @@ impl From<io::Error> for PdfRenderingError {
         Self::IoError(other)
     }
 }
+
+impl From<lopdf::Error> for PdfRenderingError {
+    fn from(other: lopdf::Error) -> Self {
+        Self::PdfError(other)
+    }
+}
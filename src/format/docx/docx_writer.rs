@@ -0,0 +1,447 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::format::html::asset_store::{Asset, AssetStore};
+use crate::format::page_setup::PageSetup;
+use htmlescape::{encode_attribute, encode_minimal};
+use mime::Mime;
+use sha2::Digest;
+
+/// The directory inside the word directory images are stored in
+const MEDIA_DIR: &str = "media";
+
+pub const RELATIONSHIP_HYPERLINK: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+pub const RELATIONSHIP_IMAGE: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+
+/// The character formatting of the text that is currently written
+#[derive(Clone, Debug, Default)]
+pub struct RunStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underlined: bool,
+    pub striked: bool,
+    pub superscript: bool,
+    pub monospace: bool,
+    pub hyperlink: bool,
+    pub color: Option<String>,
+}
+
+impl RunStyle {
+    /// Returns the run properties for the style.
+    /// The order of the elements is defined by the schema.
+    fn to_properties(&self) -> String {
+        let mut properties = String::new();
+
+        if self.hyperlink {
+            properties.push_str("<w:rStyle w:val=\"Hyperlink\"/>");
+        }
+        if self.monospace {
+            properties.push_str(
+                "<w:rFonts w:ascii=\"Consolas\" w:hAnsi=\"Consolas\" w:cs=\"Consolas\"/>",
+            );
+        }
+        if self.bold {
+            properties.push_str("<w:b/>");
+        }
+        if self.italic {
+            properties.push_str("<w:i/>");
+        }
+        if self.striked {
+            properties.push_str("<w:strike/>");
+        }
+        if let Some(color) = &self.color {
+            properties.push_str(&format!("<w:color w:val=\"{}\"/>", color));
+        }
+        if self.underlined {
+            properties.push_str("<w:u w:val=\"single\"/>");
+        }
+        if self.superscript {
+            properties.push_str("<w:vertAlign w:val=\"superscript\"/>");
+        }
+
+        if properties.is_empty() {
+            properties
+        } else {
+            format!("<w:rPr>{}</w:rPr>", properties)
+        }
+    }
+}
+
+/// A relationship of the main document part to another part or an external url
+#[derive(Clone, Debug)]
+pub struct Relationship {
+    pub id: String,
+    pub rel_type: &'static str,
+    pub target: String,
+    pub external: bool,
+}
+
+/// The state of the body that is stored while a footnote is written
+struct SavedBody {
+    body: String,
+    style: RunStyle,
+    in_paragraph: bool,
+    line_has_content: bool,
+    pending_break: bool,
+}
+
+pub struct DocxWriter {
+    body: String,
+    style: RunStyle,
+    in_paragraph: bool,
+    line_has_content: bool,
+    pending_break: bool,
+    saved_bodies: Vec<SavedBody>,
+    relationships: Vec<Relationship>,
+    images: AssetStore,
+    numbering: Vec<bool>,
+    footnotes: Vec<String>,
+    bookmark_count: usize,
+    drawing_count: usize,
+    page: PageSetup,
+}
+
+impl DocxWriter {
+    /// Creates a new writer for pages with the given dimensions
+    pub fn new(page: PageSetup) -> Self {
+        Self {
+            body: String::new(),
+            style: RunStyle::default(),
+            in_paragraph: false,
+            line_has_content: false,
+            pending_break: false,
+            saved_bodies: Vec::new(),
+            relationships: Vec::new(),
+            images: AssetStore::new(MEDIA_DIR),
+            numbering: Vec::new(),
+            footnotes: Vec::new(),
+            bookmark_count: 0,
+            drawing_count: 0,
+            page,
+        }
+    }
+
+    /// Writes raw wordprocessingml markup
+    pub fn write_raw(&mut self, xml: &str) {
+        self.body.push_str(xml);
+    }
+
+    /// Starts a new paragraph with the given paragraph properties
+    pub fn start_paragraph(&mut self, properties: &str) {
+        if self.in_paragraph {
+            self.end_paragraph();
+        }
+        self.in_paragraph = true;
+        self.line_has_content = false;
+        self.pending_break = false;
+
+        if properties.is_empty() {
+            self.body.push_str("<w:p>");
+        } else {
+            self.body
+                .push_str(&format!("<w:p><w:pPr>{}</w:pPr>", properties));
+        }
+    }
+
+    /// Ends the current paragraph discarding a trailing line break
+    pub fn end_paragraph(&mut self) {
+        if self.in_paragraph {
+            self.body.push_str("</w:p>");
+            self.in_paragraph = false;
+        }
+        self.pending_break = false;
+    }
+
+    /// Writes text with the current style
+    pub fn write_text(&mut self, text: &str) {
+        let mut content = String::new();
+
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                content.push_str("<w:br/>");
+            }
+            for (index, part) in line.split('\t').enumerate() {
+                if index > 0 {
+                    content.push_str("<w:tab/>");
+                }
+                if !part.is_empty() {
+                    content.push_str(&format!(
+                        "<w:t xml:space=\"preserve\">{}</w:t>",
+                        encode_minimal(part)
+                    ));
+                }
+            }
+        }
+        // whitespace is only written between text of the same line
+        if !text.trim().is_empty() || (self.line_has_content && !self.pending_break) {
+            self.write_run(&content);
+        }
+    }
+
+    /// Writes a run with the given content and the current style
+    pub fn write_run(&mut self, content: &str) {
+        if !self.in_paragraph {
+            self.start_paragraph("");
+        }
+        if self.pending_break {
+            self.pending_break = false;
+            if self.line_has_content {
+                self.body.push_str("<w:r><w:br/></w:r>");
+            }
+        }
+        self.line_has_content = true;
+        let properties = self.style.to_properties();
+
+        self.body
+            .push_str(&format!("<w:r>{}{}</w:r>", properties, content));
+    }
+
+    /// Writes a space between two lines of a paragraph
+    /// unless they are already separated by a line break
+    pub fn write_separator(&mut self) {
+        if !self.pending_break && self.line_has_content {
+            self.write_text(" ");
+        }
+    }
+
+    /// Adds a line break that is only written if more text follows in the same paragraph
+    pub fn line_break(&mut self) {
+        self.pending_break = true;
+    }
+
+    /// Returns the current character style
+    pub fn style(&self) -> RunStyle {
+        self.style.clone()
+    }
+
+    /// Replaces the current character style
+    pub fn set_style(&mut self, style: RunStyle) {
+        self.style = style;
+    }
+
+    /// Writes a bookmark that internal hyperlinks can point to
+    pub fn write_bookmark(&mut self, key: &str) {
+        self.bookmark_count += 1;
+        let bookmark = format!(
+            "<w:bookmarkStart w:id=\"{id}\" w:name=\"{name}\"/><w:bookmarkEnd w:id=\"{id}\"/>",
+            id = self.bookmark_count,
+            name = encode_attribute(&bookmark_name(key))
+        );
+        if !self.in_paragraph {
+            self.start_paragraph("");
+        }
+
+        self.body.push_str(&bookmark);
+    }
+
+    /// Starts a hyperlink to an external url
+    pub fn start_hyperlink(&mut self, url: &str) {
+        let id = self.add_relationship(RELATIONSHIP_HYPERLINK, url, true);
+        if !self.in_paragraph {
+            self.start_paragraph("");
+        }
+        self.body
+            .push_str(&format!("<w:hyperlink r:id=\"{}\" w:history=\"1\">", id));
+        self.style.hyperlink = true;
+    }
+
+    /// Starts a hyperlink to a bookmark inside the document
+    pub fn start_internal_hyperlink(&mut self, key: &str) {
+        if !self.in_paragraph {
+            self.start_paragraph("");
+        }
+        self.body.push_str(&format!(
+            "<w:hyperlink w:anchor=\"{}\" w:history=\"1\">",
+            encode_attribute(&bookmark_name(key))
+        ));
+        self.style.hyperlink = true;
+    }
+
+    /// Ends the current hyperlink
+    pub fn end_hyperlink(&mut self) {
+        self.body.push_str("</w:hyperlink>");
+        self.style.hyperlink = false;
+    }
+
+    /// Adds a relationship and returns its id.
+    /// Relationships with the same target are only added once.
+    pub fn add_relationship(
+        &mut self,
+        rel_type: &'static str,
+        target: &str,
+        external: bool,
+    ) -> String {
+        if let Some(relationship) = self
+            .relationships
+            .iter()
+            .find(|r| r.rel_type == rel_type && r.target == target)
+        {
+            return relationship.id.clone();
+        }
+        let id = format!("rId{}", self.relationships.len() + 1);
+        self.relationships.push(Relationship {
+            id: id.clone(),
+            rel_type,
+            target: target.to_string(),
+            external,
+        });
+
+        id
+    }
+
+    /// Returns all relationships that have been added
+    pub fn relationships(&self) -> &[Relationship] {
+        &self.relationships
+    }
+
+    /// Adds an image and returns the id of its relationship
+    pub fn add_image(&mut self, data: Vec<u8>, mime: Mime) -> String {
+        let target = self.images.add(data, mime);
+
+        self.add_relationship(RELATIONSHIP_IMAGE, &target, false)
+    }
+
+    /// Returns all images that have been added
+    pub fn images(&self) -> &[Asset] {
+        self.images.assets()
+    }
+
+    /// Returns the directory the images are stored in
+    pub fn image_directory(&self) -> &str {
+        self.images.directory()
+    }
+
+    /// Returns a new id for a drawing
+    pub fn next_drawing_id(&mut self) -> usize {
+        self.drawing_count += 1;
+
+        self.drawing_count
+    }
+
+    /// Returns the dimensions of the page
+    pub fn page(&self) -> &PageSetup {
+        &self.page
+    }
+
+    /// Adds a numbering instance for a list so that every list starts counting at one
+    /// and returns its id
+    pub fn add_numbering(&mut self, ordered: bool) -> usize {
+        self.numbering.push(ordered);
+
+        self.numbering.len()
+    }
+
+    /// Returns for every numbering instance if it belongs to an ordered list
+    pub fn numbering(&self) -> &[bool] {
+        &self.numbering
+    }
+
+    /// Starts writing the content of a footnote instead of the body
+    pub fn start_footnote(&mut self) {
+        self.saved_bodies.push(SavedBody {
+            body: std::mem::take(&mut self.body),
+            style: std::mem::take(&mut self.style),
+            in_paragraph: self.in_paragraph,
+            line_has_content: self.line_has_content,
+            pending_break: self.pending_break,
+        });
+        self.in_paragraph = false;
+        self.line_has_content = false;
+        self.pending_break = false;
+    }
+
+    /// Stores the written footnote content, continues writing the body
+    /// and returns the id of the footnote
+    pub fn end_footnote(&mut self) -> usize {
+        self.end_paragraph();
+        let content = if let Some(saved) = self.saved_bodies.pop() {
+            self.style = saved.style;
+            self.in_paragraph = saved.in_paragraph;
+            self.line_has_content = saved.line_has_content;
+            self.pending_break = saved.pending_break;
+            std::mem::replace(&mut self.body, saved.body)
+        } else {
+            std::mem::take(&mut self.body)
+        };
+        self.footnotes.push(content);
+
+        self.footnotes.len()
+    }
+
+    /// Returns the content of all footnotes in the order of their ids
+    pub fn footnotes(&self) -> &[String] {
+        &self.footnotes
+    }
+
+    /// Returns the written body and ends an unclosed paragraph
+    pub fn take_body(&mut self) -> String {
+        self.end_paragraph();
+
+        std::mem::take(&mut self.body)
+    }
+}
+
+/// Returns a valid bookmark name for an anchor.
+/// Bookmark names may only contain letters, digits and underscores
+/// and must not be longer than 40 characters.
+pub fn bookmark_name(key: &str) -> String {
+    let name = key
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let name = if name.starts_with(char::is_alphabetic) {
+        name
+    } else {
+        format!("a{}", name)
+    };
+
+    if name.chars().count() <= 40 {
+        name
+    } else {
+        let mut hasher = sha2::Sha256::default();
+        hasher.update(key);
+        let hash = format!("{:x}", hasher.finalize());
+
+        format!(
+            "{}_{}",
+            name.chars().take(31).collect::<String>(),
+            &hash[..8]
+        )
+    }
+}
+
+/// Returns the hex value of a color for the color run property
+pub fn docx_color(color: &str) -> Option<String> {
+    let color = color.trim().to_lowercase();
+    let hex = match color.as_str() {
+        "black" => "000000",
+        "white" => "ffffff",
+        "red" => "ff0000",
+        "green" => "008000",
+        "blue" => "0000ff",
+        "yellow" => "ffff00",
+        "orange" => "ffa500",
+        "purple" => "800080",
+        "gray" | "grey" => "808080",
+        _ => color.trim_start_matches('#'),
+    };
+
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        6 => Some(hex.to_uppercase()),
+        3 => Some(
+            hex.chars()
+                .flat_map(|c| vec![c, c])
+                .collect::<String>()
+                .to_uppercase(),
+        ),
+        _ => None,
+    }
+}
@@ -0,0 +1,390 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::Document;
+use crate::format::docx::docx_writer::{
+    DocxWriter, Relationship, RELATIONSHIP_HYPERLINK, RELATIONSHIP_IMAGE,
+};
+use crate::format::docx::result::DocxResult;
+use crate::format::docx::to_docx::ToDocx;
+use crate::format::page_setup::PageSetup;
+use crate::format::{RenderResult, Renderer};
+use crate::settings::metadata_settings::MetadataSettings;
+use htmlescape::{encode_attribute, encode_minimal};
+use std::io::{Cursor, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+pub mod docx_writer;
+pub mod result;
+pub mod to_docx;
+
+const STYLES_XML: &str = include_str!("assets/styles.xml");
+
+const RELATIONSHIP_STYLES: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles";
+const RELATIONSHIP_NUMBERING: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering";
+const RELATIONSHIP_FOOTNOTES: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/footnotes";
+const RELATIONSHIP_SETTINGS: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/settings";
+
+const ROOT_RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/>
+<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/extended-properties" Target="docProps/app.xml"/>
+</Relationships>"#;
+
+const APP_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/extended-properties"><Application>Snekdown</Application></Properties>"#;
+
+const SETTINGS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:settings xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:footnotePr><w:footnote w:id="-1"/><w:footnote w:id="0"/></w:footnotePr>
+</w:settings>"#;
+
+const WORD_NAMESPACES: &str =
+    "xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\" \
+    xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\" \
+    xmlns:wp=\"http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing\" \
+    xmlns:a=\"http://schemas.openxmlformats.org/drawingml/2006/main\" \
+    xmlns:pic=\"http://schemas.openxmlformats.org/drawingml/2006/picture\"";
+
+const TWIPS_PER_INCH: f32 = 1440.0;
+
+/// Renders the document to an Office Open XML document and returns the bytes of the archive
+pub fn render_to_docx(document: Document) -> DocxResult<Vec<u8>> {
+    let settings = document.config.lock().clone();
+    let page = PageSetup::new(&settings.pdf);
+
+    let mut writer = DocxWriter::new(page.clone());
+    writer.add_relationship(RELATIONSHIP_STYLES, "styles.xml", false);
+    writer.add_relationship(RELATIONSHIP_NUMBERING, "numbering.xml", false);
+    writer.add_relationship(RELATIONSHIP_FOOTNOTES, "footnotes.xml", false);
+    writer.add_relationship(RELATIONSHIP_SETTINGS, "settings.xml", false);
+
+    log::info!("Rendering docx document...");
+    document.to_docx(&mut writer)?;
+    let body = writer.take_body();
+
+    log::info!("Packaging docx...");
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file("[Content_Types].xml", deflated)?;
+    zip.write_all(create_content_types(&writer).as_bytes())?;
+    zip.start_file("_rels/.rels", deflated)?;
+    zip.write_all(ROOT_RELATIONSHIPS.as_bytes())?;
+    zip.start_file("docProps/core.xml", deflated)?;
+    zip.write_all(create_core_properties(&settings.metadata).as_bytes())?;
+    zip.start_file("docProps/app.xml", deflated)?;
+    zip.write_all(APP_XML.as_bytes())?;
+
+    zip.start_file("word/document.xml", deflated)?;
+    zip.write_all(create_document(&body, &page).as_bytes())?;
+    zip.start_file("word/_rels/document.xml.rels", deflated)?;
+    zip.write_all(create_relationships(writer.relationships().iter()).as_bytes())?;
+    // footnotes can contain the same hyperlinks and images as the document
+    zip.start_file("word/_rels/footnotes.xml.rels", deflated)?;
+    zip.write_all(
+        create_relationships(
+            writer.relationships().iter().filter(|r| {
+                r.rel_type == RELATIONSHIP_HYPERLINK || r.rel_type == RELATIONSHIP_IMAGE
+            }),
+        )
+        .as_bytes(),
+    )?;
+    zip.start_file("word/styles.xml", deflated)?;
+    zip.write_all(STYLES_XML.as_bytes())?;
+    zip.start_file("word/settings.xml", deflated)?;
+    zip.write_all(SETTINGS_XML.as_bytes())?;
+    zip.start_file("word/numbering.xml", deflated)?;
+    zip.write_all(create_numbering(writer.numbering()).as_bytes())?;
+    zip.start_file("word/footnotes.xml", deflated)?;
+    zip.write_all(create_footnotes(writer.footnotes()).as_bytes())?;
+
+    for image in writer.images() {
+        zip.start_file(
+            format!("word/{}/{}", writer.image_directory(), image.name),
+            stored,
+        )?;
+        zip.write_all(&image.data)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Renders documents to word documents
+pub struct DocxRenderer;
+
+impl Renderer for DocxRenderer {
+    fn name(&self) -> &str {
+        "docx"
+    }
+
+    fn extension(&self) -> &str {
+        "docx"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        mut writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        writer.write_all(&render_to_docx(document)?)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Returns the section properties with the page size and margins
+fn create_section_properties(page: &PageSetup) -> String {
+    let twips = |inches: f32| (inches * TWIPS_PER_INCH) as u32;
+    let orientation = if page.landscape {
+        " w:orient=\"landscape\""
+    } else {
+        ""
+    };
+
+    format!(
+        "<w:sectPr><w:pgSz w:w=\"{}\" w:h=\"{}\"{}/>\
+        <w:pgMar w:top=\"{}\" w:right=\"{}\" w:bottom=\"{}\" w:left=\"{}\" \
+        w:header=\"720\" w:footer=\"720\" w:gutter=\"0\"/></w:sectPr>",
+        twips(page.width),
+        twips(page.height),
+        orientation,
+        twips(page.margin_top),
+        twips(page.margin_right),
+        twips(page.margin_bottom),
+        twips(page.margin_left)
+    )
+}
+
+/// Creates the main document part from the rendered body
+fn create_document(body: &str, page: &PageSetup) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+        <w:document {}><w:body>{}{}</w:body></w:document>",
+        WORD_NAMESPACES,
+        body,
+        create_section_properties(page)
+    )
+}
+
+/// Creates the content types of all parts in the package
+fn create_content_types(writer: &DocxWriter) -> String {
+    let mut types = String::new();
+    types.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    types.push_str(
+        "<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+        <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+        <Default Extension=\"xml\" ContentType=\"application/xml\"/>",
+    );
+    let overrides = [
+        (
+            "/word/document.xml",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml",
+        ),
+        (
+            "/word/styles.xml",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml",
+        ),
+        (
+            "/word/settings.xml",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.settings+xml",
+        ),
+        (
+            "/word/numbering.xml",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml",
+        ),
+        (
+            "/word/footnotes.xml",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml",
+        ),
+        (
+            "/docProps/core.xml",
+            "application/vnd.openxmlformats-package.core-properties+xml",
+        ),
+        (
+            "/docProps/app.xml",
+            "application/vnd.openxmlformats-officedocument.extended-properties+xml",
+        ),
+    ];
+    for (part, content_type) in overrides.iter() {
+        types.push_str(&format!(
+            "<Override PartName=\"{}\" ContentType=\"{}\"/>",
+            part, content_type
+        ));
+    }
+    for image in writer.images() {
+        types.push_str(&format!(
+            "<Override PartName=\"/word/{}/{}\" ContentType=\"{}\"/>",
+            writer.image_directory(),
+            image.name,
+            image.mime.essence_str()
+        ));
+    }
+    types.push_str("</Types>");
+
+    types
+}
+
+/// Creates the relationships part of a document part
+fn create_relationships<'a, I: Iterator<Item = &'a Relationship>>(relationships: I) -> String {
+    let mut part = String::new();
+    part.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    part.push_str(
+        "<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">",
+    );
+
+    for relationship in relationships {
+        let target_mode = if relationship.external {
+            " TargetMode=\"External\""
+        } else {
+            ""
+        };
+        part.push_str(&format!(
+            "<Relationship Id=\"{}\" Type=\"{}\" Target=\"{}\"{}/>",
+            relationship.id,
+            relationship.rel_type,
+            encode_attribute(&relationship.target),
+            target_mode
+        ));
+    }
+    part.push_str("</Relationships>");
+
+    part
+}
+
+/// Creates the numbering definitions with one bullet and one decimal definition
+/// and an instance for every list
+fn create_numbering(instances: &[bool]) -> String {
+    let mut numbering = String::new();
+    numbering.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    numbering.push_str(&format!("<w:numbering {}>", WORD_NAMESPACES));
+
+    for (id, ordered) in [false, true].iter().enumerate() {
+        numbering.push_str(&format!(
+            "<w:abstractNum w:abstractNumId=\"{}\"><w:multiLevelType w:val=\"hybridMultilevel\"/>",
+            id
+        ));
+        for level in 0..9 {
+            let (format, text) = if *ordered {
+                ("decimal", format!("%{}.", level + 1))
+            } else {
+                (
+                    "bullet",
+                    ["\u{2022}", "\u{25E6}", "\u{25AA}"][level % 3].to_string(),
+                )
+            };
+            numbering.push_str(&format!(
+                "<w:lvl w:ilvl=\"{}\"><w:start w:val=\"1\"/><w:numFmt w:val=\"{}\"/>\
+                <w:lvlText w:val=\"{}\"/><w:lvlJc w:val=\"left\"/>\
+                <w:pPr><w:ind w:left=\"{}\" w:hanging=\"360\"/></w:pPr></w:lvl>",
+                level,
+                format,
+                text,
+                720 * (level + 1)
+            ));
+        }
+        numbering.push_str("</w:abstractNum>");
+    }
+    for (index, ordered) in instances.iter().enumerate() {
+        numbering.push_str(&format!(
+            "<w:num w:numId=\"{}\"><w:abstractNumId w:val=\"{}\"/>",
+            index + 1,
+            *ordered as u8
+        ));
+        if *ordered {
+            // restarts the numbering for every list
+            for level in 0..9 {
+                numbering.push_str(&format!(
+                    "<w:lvlOverride w:ilvl=\"{}\"><w:startOverride w:val=\"1\"/></w:lvlOverride>",
+                    level
+                ));
+            }
+        }
+        numbering.push_str("</w:num>");
+    }
+    numbering.push_str("</w:numbering>");
+
+    numbering
+}
+
+/// Creates the footnotes part with the separators that are required by word
+fn create_footnotes(footnotes: &[String]) -> String {
+    let mut part = String::new();
+    part.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    part.push_str(&format!("<w:footnotes {}>", WORD_NAMESPACES));
+    part.push_str(
+        "<w:footnote w:type=\"separator\" w:id=\"-1\"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>\
+        <w:footnote w:type=\"continuationSeparator\" w:id=\"0\"><w:p><w:r><w:continuationSeparator/></w:r></w:p></w:footnote>",
+    );
+
+    for (index, content) in footnotes.iter().enumerate() {
+        part.push_str(&format!(
+            "<w:footnote w:id=\"{}\">{}</w:footnote>",
+            index + 1,
+            content
+        ));
+    }
+    part.push_str("</w:footnotes>");
+
+    part
+}
+
+/// Creates the core properties from the metadata of the document
+fn create_core_properties(metadata: &MetadataSettings) -> String {
+    let mut properties = String::new();
+    properties.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    properties.push_str(
+        "<cp:coreProperties \
+        xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+        xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:dcterms=\"http://purl.org/dc/terms/\" \
+        xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">",
+    );
+
+    if let Some(title) = &metadata.title {
+        properties.push_str(&format!("<dc:title>{}</dc:title>", encode_minimal(title)));
+    }
+    if let Some(author) = &metadata.author {
+        properties.push_str(&format!(
+            "<dc:creator>{}</dc:creator>",
+            encode_minimal(author)
+        ));
+    }
+    if let Some(description) = &metadata.description {
+        properties.push_str(&format!(
+            "<dc:description>{}</dc:description>",
+            encode_minimal(description)
+        ));
+    }
+    if !metadata.keywords.is_empty() {
+        properties.push_str(&format!(
+            "<cp:keywords>{}</cp:keywords>",
+            encode_minimal(&metadata.keywords.join(", "))
+        ));
+    }
+    properties.push_str(&format!(
+        "<dc:language>{}</dc:language>",
+        encode_minimal(&metadata.language)
+    ));
+    let now = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+    properties.push_str(&format!(
+        "<dcterms:created xsi:type=\"dcterms:W3CDTF\">{now}</dcterms:created>\
+        <dcterms:modified xsi:type=\"dcterms:W3CDTF\">{now}</dcterms:modified>",
+        now = now
+    ));
+    properties.push_str("</cp:coreProperties>");
+
+    properties
+}
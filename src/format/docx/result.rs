@@ -0,0 +1,40 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+
+pub type DocxResult<T> = Result<T, DocxError>;
+
+#[derive(Debug)]
+pub enum DocxError {
+    IoError(io::Error),
+    ZipError(zip::result::ZipError),
+}
+
+impl Display for DocxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocxError::IoError(e) => write!(f, "IO Error: {}", e),
+            DocxError::ZipError(e) => write!(f, "Zip Error: {}", e),
+        }
+    }
+}
+
+impl Error for DocxError {}
+
+impl From<io::Error> for DocxError {
+    fn from(other: io::Error) -> Self {
+        Self::IoError(other)
+    }
+}
+
+impl From<zip::result::ZipError> for DocxError {
+    fn from(other: zip::result::ZipError) -> Self {
+        Self::ZipError(other)
+    }
+}
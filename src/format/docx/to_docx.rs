@@ -0,0 +1,676 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::*;
+use crate::format::docx::docx_writer::{docx_color, DocxWriter, RunStyle};
+use crate::format::pdf_outline::inline_to_string;
+use crate::format::PlaceholderTemplate;
+use crate::references::bibliography::get_item_for_entry;
+use crate::references::glossary::{GlossaryDisplay, GlossaryReference};
+use crate::references::templates::{Template, TemplateVariable};
+use crate::utils::entities::decode_entity;
+use htmlescape::encode_attribute;
+use image::GenericImageView;
+use std::io;
+
+const EMU_PER_INCH: f32 = 914400.0;
+
+pub trait ToDocx {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()>;
+}
+
+impl ToDocx for Element {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        match self {
+            Element::Block(block) => block.to_docx(writer),
+            Element::Inline(inline) => inline.to_docx(writer),
+            Element::Line(line) => line.to_docx(writer),
+        }
+    }
+}
+
+impl ToDocx for Line {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        match self {
+            Line::Text(text) => text.to_docx(writer),
+            Line::Ruler(ruler) => ruler.to_docx(writer),
+            Line::RefLink(anchor) => anchor.to_docx(writer),
+            Line::Centered(centered) => centered.to_docx(writer),
+            Line::Anchor(a) => a.to_docx(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToDocx for Inline {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        match self {
+            Inline::Url(url) => url.to_docx(writer),
+            Inline::Monospace(mono) => mono.to_docx(writer),
+            Inline::Striked(striked) => write_styled(writer, |s| s.striked = true, &striked.value),
+            Inline::Plain(plain) => plain.to_docx(writer),
+            Inline::Italic(italic) => write_styled(writer, |s| s.italic = true, &italic.value),
+            Inline::Underlined(under) => {
+                write_styled(writer, |s| s.underlined = true, &under.value)
+            }
+            Inline::Bold(bold) => write_styled(writer, |s| s.bold = true, &bold.value),
+            Inline::Image(img) => img.to_docx(writer),
+            Inline::Placeholder(placeholder) => placeholder.read().unwrap().to_docx(writer),
+            Inline::Superscript(superscript) => {
+                write_styled(writer, |s| s.superscript = true, &superscript.value)
+            }
+            Inline::Checkbox(checkbox) => checkbox.to_docx(writer),
+            Inline::Emoji(emoji) => emoji.to_docx(writer),
+            Inline::Colored(colored) => colored.to_docx(writer),
+            Inline::BibReference(bibref) => bibref.read().unwrap().to_docx(writer),
+            Inline::TemplateVar(var) => var.read().unwrap().to_docx(writer),
+            Inline::Math(m) => m.to_docx(writer),
+            Inline::LineBreak => {
+                writer.line_break();
+                Ok(())
+            }
+            Inline::CharacterCode(code) => code.to_docx(writer),
+            Inline::GlossaryReference(gloss) => gloss.lock().to_docx(writer),
+            Inline::Arrow(a) => a.to_docx(writer),
+            Inline::Anchor(a) => a.to_docx(writer),
+        }
+    }
+}
+
+impl ToDocx for Block {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        match self {
+            Block::Paragraph(para) => para.to_docx(writer),
+            Block::List(list) => list.to_docx(writer),
+            Block::Table(table) => table.to_docx(writer),
+            Block::CodeBlock(code) => code.to_docx(writer),
+            Block::Quote(quote) => quote.to_docx(writer),
+            Block::Section(section) => section.to_docx(writer),
+            Block::Placeholder(placeholder) => placeholder.read().unwrap().to_docx(writer),
+            Block::MathBlock(m) => m.to_docx(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToDocx for MetadataValue {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        match self {
+            MetadataValue::Placeholder(ph) => ph.read().unwrap().to_docx(writer),
+            MetadataValue::Template(t) => t.to_docx(writer),
+            _ => {
+                writer.write_text(&self.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ToDocx for Document {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        for element in &self.elements {
+            element.to_docx(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Math {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.set_style(RunStyle {
+            italic: true,
+            ..previous.clone()
+        });
+        writer.write_text(self.source.trim());
+        writer.set_style(previous);
+
+        Ok(())
+    }
+}
+
+impl ToDocx for MathBlock {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.start_paragraph("<w:jc w:val=\"center\"/>");
+        writer.set_style(RunStyle {
+            italic: true,
+            ..previous.clone()
+        });
+        writer.write_text(self.source.trim());
+        writer.set_style(previous);
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Section {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.start_paragraph(&format!(
+            "<w:pStyle w:val=\"Heading{}\"/>",
+            self.header.size.clamp(1, 6)
+        ));
+        writer.write_bookmark(&self.header.anchor);
+        self.header.line.to_docx(writer)?;
+        writer.end_paragraph();
+
+        for element in &self.elements {
+            element.to_docx(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Paragraph {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let mut open = false;
+
+        for element in &self.elements {
+            match element {
+                // paragraphs can't be nested so these lines are written as separate paragraphs
+                Line::Centered(_) | Line::Ruler(_) => {
+                    writer.end_paragraph();
+                    open = false;
+                    element.to_docx(writer)?;
+                }
+                _ => {
+                    if open {
+                        writer.write_separator();
+                    } else {
+                        writer.start_paragraph("");
+                        open = true;
+                    }
+                    element.to_docx(writer)?;
+                }
+            }
+        }
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToDocx for List {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        write_list(writer, self.ordered, &self.items, 0)
+    }
+}
+
+/// Writes the items of a list as numbered paragraphs with the children on the next level
+fn write_list(
+    writer: &mut DocxWriter,
+    ordered: bool,
+    items: &[ListItem],
+    level: usize,
+) -> io::Result<()> {
+    let numbering = writer.add_numbering(ordered);
+
+    for item in items {
+        writer.start_paragraph(&format!(
+            "<w:pStyle w:val=\"ListParagraph\"/><w:numPr><w:ilvl w:val=\"{}\"/>\
+            <w:numId w:val=\"{}\"/></w:numPr>",
+            level.min(8),
+            numbering
+        ));
+        item.text.to_docx(writer)?;
+        writer.end_paragraph();
+
+        if let Some(first) = item.children.first() {
+            write_list(writer, first.ordered, &item.children, level + 1)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl ToDocx for Table {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let columns = std::iter::once(&self.header)
+            .chain(self.rows.iter())
+            .map(|r| r.cells.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let column_width = 9000 / columns;
+
+        writer.write_raw(
+            "<w:tbl><w:tblPr><w:tblStyle w:val=\"TableGrid\"/>\
+            <w:tblW w:w=\"5000\" w:type=\"pct\"/></w:tblPr><w:tblGrid>",
+        );
+        for _ in 0..columns {
+            writer.write_raw(&format!("<w:gridCol w:w=\"{}\"/>", column_width));
+        }
+        writer.write_raw("</w:tblGrid>");
+
+        write_row(writer, &self.header, columns, true)?;
+        for row in &self.rows {
+            write_row(writer, row, columns, false)?;
+        }
+        writer.write_raw("</w:tbl>");
+
+        // a paragraph between tables prevents word from merging them
+        writer.start_paragraph("");
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+/// Writes a table row filling up missing cells so that every row has the same number of cells
+fn write_row(writer: &mut DocxWriter, row: &Row, columns: usize, header: bool) -> io::Result<()> {
+    writer.write_raw("<w:tr>");
+    if header {
+        writer.write_raw("<w:trPr><w:tblHeader/></w:trPr>");
+    }
+
+    for index in 0..columns {
+        writer.write_raw("<w:tc>");
+        writer.start_paragraph("");
+        if let Some(cell) = row.cells.get(index) {
+            let previous = writer.style();
+            writer.set_style(RunStyle {
+                bold: header,
+                ..previous.clone()
+            });
+            cell.text.to_docx(writer)?;
+            writer.set_style(previous);
+        }
+        writer.end_paragraph();
+        writer.write_raw("</w:tc>");
+    }
+    writer.write_raw("</w:tr>");
+
+    Ok(())
+}
+
+impl ToDocx for CodeBlock {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        if let Some(file_name) = self.get_file_name() {
+            writer.start_paragraph("<w:keepNext/>");
+            let previous = writer.style();
+            writer.set_style(RunStyle {
+                bold: true,
+                monospace: true,
+                ..previous.clone()
+            });
+            writer.write_text(&file_name);
+            writer.set_style(previous);
+            writer.end_paragraph();
+        }
+        writer.start_paragraph("<w:pStyle w:val=\"Code\"/>");
+        if let Some(anchor) = self.get_anchor() {
+            writer.write_bookmark(&anchor);
+        }
+        writer.write_text(self.get_numbered_lines().join("\n").trim_matches('\n'));
+        writer.end_paragraph();
+
+        if let Some(caption) = self.get_caption() {
+            writer.start_paragraph("<w:pStyle w:val=\"Caption\"/>");
+            writer.write_text(&caption);
+            writer.end_paragraph();
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Quote {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.start_paragraph("<w:pStyle w:val=\"Quote\"/>");
+        for line in &self.text {
+            writer.write_separator();
+            line.to_docx(writer)?;
+        }
+        writer.end_paragraph();
+
+        if let Some(meta) = &self.metadata {
+            writer.start_paragraph("<w:pStyle w:val=\"Quote\"/><w:jc w:val=\"right\"/>");
+            writer.write_text("\u{2014} ");
+            meta.to_docx(writer)?;
+            writer.end_paragraph();
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Ruler {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.start_paragraph(
+            "<w:pBdr><w:bottom w:val=\"single\" w:sz=\"6\" w:space=\"1\" w:color=\"auto\"/></w:pBdr>",
+        );
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToDocx for TextLine {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        for text in &self.subtext {
+            text.to_docx(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Image {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let description = self
+            .url
+            .description
+            .as_ref()
+            .map(|d| d.iter().map(inline_to_string).collect::<String>())
+            .unwrap_or_default();
+        let content = self.get_content();
+        let dimensions = content
+            .as_ref()
+            .and_then(|c| image::load_from_memory(c).ok())
+            .map(|i| i.dimensions());
+
+        let (content, (pixel_width, pixel_height)) = match (content, dimensions) {
+            (Some(content), Some(dimensions)) => (content, dimensions),
+            _ => {
+                log::warn!(
+                    "The image {} can't be embedded in the docx document",
+                    self.url.url
+                );
+                return self.url.to_docx(writer);
+            }
+        };
+        let (width, height) =
+            writer
+                .page()
+                .image_size(self.metadata.as_ref(), pixel_width, pixel_height);
+        let width = (width * EMU_PER_INCH) as u64;
+        let height = (height * EMU_PER_INCH) as u64;
+        let id = writer.add_image(content, self.get_mime_type());
+        let drawing_id = writer.next_drawing_id();
+
+        writer.write_run(&format!(
+            "<w:drawing><wp:inline distT=\"0\" distB=\"0\" distL=\"0\" distR=\"0\">\
+            <wp:extent cx=\"{width}\" cy=\"{height}\"/>\
+            <wp:docPr id=\"{drawing}\" name=\"Image {drawing}\" descr=\"{description}\"/>\
+            <wp:cNvGraphicFramePr><a:graphicFrameLocks noChangeAspect=\"1\"/></wp:cNvGraphicFramePr>\
+            <a:graphic><a:graphicData uri=\"http://schemas.openxmlformats.org/drawingml/2006/picture\">\
+            <pic:pic><pic:nvPicPr><pic:cNvPr id=\"{drawing}\" name=\"Image {drawing}\"/><pic:cNvPicPr/></pic:nvPicPr>\
+            <pic:blipFill><a:blip r:embed=\"{id}\"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill>\
+            <pic:spPr><a:xfrm><a:off x=\"0\" y=\"0\"/><a:ext cx=\"{width}\" cy=\"{height}\"/></a:xfrm>\
+            <a:prstGeom prst=\"rect\"><a:avLst/></a:prstGeom></pic:spPr></pic:pic>\
+            </a:graphicData></a:graphic></wp:inline></w:drawing>",
+            width = width,
+            height = height,
+            drawing = drawing_id,
+            description = encode_attribute(&description),
+            id = id
+        ));
+
+        if let Some(description) = &self.url.description {
+            writer.line_break();
+            write_styled(writer, |s| s.italic = true, description)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the inline elements with a modified character style
+fn write_styled<F: FnOnce(&mut RunStyle)>(
+    writer: &mut DocxWriter,
+    change: F,
+    value: &[Inline],
+) -> io::Result<()> {
+    let previous = writer.style();
+    let mut style = previous.clone();
+    change(&mut style);
+    writer.set_style(style);
+
+    for element in value {
+        element.to_docx(writer)?;
+    }
+    writer.set_style(previous);
+
+    Ok(())
+}
+
+impl ToDocx for MonospaceText {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.set_style(RunStyle {
+            monospace: true,
+            ..previous.clone()
+        });
+        writer.write_text(&self.value);
+        writer.set_style(previous);
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Url {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        if let Some(anchor) = self.url.strip_prefix('#') {
+            writer.start_internal_hyperlink(anchor);
+        } else {
+            writer.start_hyperlink(&self.url);
+        }
+        if let Some(description) = &self.description {
+            for desc in description {
+                desc.to_docx(writer)?;
+            }
+        } else {
+            writer.write_text(&self.url);
+        }
+        writer.end_hyperlink();
+
+        Ok(())
+    }
+}
+
+impl ToDocx for PlainText {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.write_text(&self.value);
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Placeholder {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            value.to_docx(writer)
+        } else {
+            log::debug!("Unknown placeholder [[{}]]", self.name.clone());
+            writer.write_text(&format!("[[{}]]", self.name));
+
+            Ok(())
+        }
+    }
+}
+
+impl ToDocx for RefLink {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.start_internal_hyperlink(&self.reference);
+        self.description.to_docx(writer)?;
+        writer.end_hyperlink();
+
+        Ok(())
+    }
+}
+
+impl ToDocx for InlineMetadata {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        if let Some(MetadataValue::String(format)) = self.data.get("display") {
+            let mut template = PlaceholderTemplate::new(format.clone());
+            self.data
+                .iter()
+                .for_each(|(k, v)| template.add_replacement(k, &v.to_string()));
+
+            writer.write_text(&template.render());
+        } else {
+            for (k, v) in &self.data {
+                writer.write_text(&format!("{}={},", k, v.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Centered {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.start_paragraph("<w:jc w:val=\"center\"/>");
+        self.line.to_docx(writer)?;
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Checkbox {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        if self.value {
+            writer.write_text("\u{2612}");
+        } else {
+            writer.write_text("\u{2610}");
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Emoji {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.write_text(&self.value.to_string());
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Colored {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.set_style(RunStyle {
+            color: docx_color(&self.color).or_else(|| previous.color.clone()),
+            ..previous.clone()
+        });
+        self.value.to_docx(writer)?;
+        writer.set_style(previous);
+
+        Ok(())
+    }
+}
+
+impl ToDocx for BibReference {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let entry = self.entry_anchor.lock().entry.clone();
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                writer.write_text(&self.get_formatted());
+                return Ok(());
+            }
+        };
+        // the anchor of the entry belongs to the bibliography list
+        let line = match get_item_for_entry(entry).text {
+            Line::Anchor(anchor) => *anchor.inner,
+            line => line,
+        };
+
+        writer.start_footnote();
+        writer.start_paragraph("<w:pStyle w:val=\"FootnoteText\"/>");
+        writer.write_run("<w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr><w:footnoteRef/>");
+        writer.write_text(" ");
+        line.to_docx(writer)?;
+        let id = writer.end_footnote();
+
+        writer.write_run(&format!(
+            "<w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr><w:footnoteReference w:id=\"{}\"/>",
+            id
+        ));
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Template {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        for element in &self.text {
+            element.to_docx(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for TemplateVariable {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            writer.write_text(&self.prefix);
+            value.to_docx(writer)?;
+            writer.write_text(&self.suffix);
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for CharacterCode {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        match decode_entity(&self.code) {
+            Some(decoded) => writer.write_text(&decoded),
+            None => writer.write_text(&format!("&{};", self.code)),
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Anchor {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        writer.write_bookmark(&self.key);
+
+        self.inner.to_docx(writer)
+    }
+}
+
+impl ToDocx for GlossaryReference {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        if let Some(entry) = &self.entry {
+            let entry = entry.lock();
+            writer.start_internal_hyperlink(&self.short);
+            match self.display {
+                GlossaryDisplay::Short => writer.write_text(&entry.short),
+                GlossaryDisplay::Long => writer.write_text(&entry.long),
+            }
+            writer.end_hyperlink();
+        } else {
+            writer.write_text(&format!("~{}", self.short));
+        }
+
+        Ok(())
+    }
+}
+
+impl ToDocx for Arrow {
+    fn to_docx(&self, writer: &mut DocxWriter) -> io::Result<()> {
+        let arrow = match self {
+            Arrow::RightArrow => "\u{27F6}",
+            Arrow::LeftArrow => "\u{27F5}",
+            Arrow::LeftRightArrow => "\u{27F7}",
+            Arrow::BigRightArrow => "\u{27F9}",
+            Arrow::BigLeftArrow => "\u{27F8}",
+            Arrow::BigLeftRightArrow => "\u{27FA}",
+        };
+        writer.write_text(arrow);
+
+        Ok(())
+    }
+}
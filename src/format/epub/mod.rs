@@ -0,0 +1,316 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::{Block, Document};
+use crate::format::epub::result::EpubResult;
+use crate::format::html::asset_store::{Asset, AssetStore};
+use crate::format::html::html_writer::HTMLWriter;
+use crate::format::html::shared_buffer::SharedBuffer;
+use crate::format::html::to_html::ToHtml;
+use crate::format::pdf_outline::line_to_string;
+use crate::format::style::{get_css_for_theme, CodeStyle};
+use crate::format::{RenderResult, Renderer};
+use crate::settings::metadata_settings::MetadataSettings;
+use htmlescape::{encode_attribute, encode_minimal};
+use sha2::Digest;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+pub mod result;
+
+const CONTENT_DIR: &str = "EPUB";
+const IMAGE_DIR: &str = "images";
+const STYLE_FILE: &str = "style.css";
+const NAV_FILE: &str = "nav.xhtml";
+const PACKAGE_FILE: &str = "content.opf";
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles><rootfile full-path="EPUB/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#;
+
+/// A single xhtml file of the book
+struct Chapter {
+    file_name: String,
+    title: String,
+    content: String,
+}
+
+/// Renders the document to an epub with one chapter per top level section
+/// and returns the bytes of the resulting archive
+pub fn render_to_epub(document: Document) -> EpubResult<Vec<u8>> {
+    let settings = document.config.lock().clone();
+    let metadata = &settings.metadata;
+    let groups = split_chapters(&document.elements);
+
+    let mut anchor_pages = HashMap::new();
+    for (file_name, _, blocks) in &groups {
+        for anchor in blocks.iter().flat_map(|b| b.get_anchors()) {
+            anchor_pages
+                .entry(anchor)
+                .or_insert_with(|| file_name.clone());
+        }
+    }
+
+    let buffer = SharedBuffer::default();
+    let mut writer = HTMLWriter::new(Box::new(buffer.clone()), settings.style.theme.clone());
+    writer.set_xhtml(true);
+    writer.set_safe_mode(settings.safe_mode);
+    writer.set_code_style(CodeStyle::load(&settings, &document.get_root_dir()));
+    writer.set_asset_store(AssetStore::new(IMAGE_DIR));
+    writer.set_anchor_pages(anchor_pages);
+    let mut chapters = Vec::new();
+
+    log::info!("Rendering epub chapters...");
+    for (file_name, title, blocks) in groups {
+        writer.set_current_page(&file_name);
+        for block in blocks {
+            block.to_html(&mut writer)?;
+        }
+        writer.flush()?;
+        chapters.push(Chapter {
+            file_name,
+            title,
+            content: buffer.take_string(),
+        });
+    }
+
+    let toc = document.create_toc(true);
+    writer.set_current_page(NAV_FILE);
+    toc.to_html(&mut writer)?;
+    let nav = buffer.take_string();
+    let images = writer
+        .take_asset_store()
+        .map(|s| s.assets().to_vec())
+        .unwrap_or_default();
+
+    let mut style = get_css_for_theme(settings.style.theme.clone());
+    style.push_str(&writer.get_code_style().get_css());
+    for stylesheet in &document.stylesheets {
+        if let Some(data) = &stylesheet.lock().data {
+            style.push('\n');
+            style.push_str(&String::from_utf8_lossy(data));
+        }
+    }
+
+    log::info!("Packaging epub...");
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // the mimetype needs to be the first file and must not be compressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+
+    zip.start_file(format!("{}/{}", CONTENT_DIR, PACKAGE_FILE), deflated)?;
+    zip.write_all(create_package(metadata, &chapters, &images).as_bytes())?;
+    zip.start_file(format!("{}/{}", CONTENT_DIR, NAV_FILE), deflated)?;
+    zip.write_all(create_nav(metadata, &nav).as_bytes())?;
+    zip.start_file(format!("{}/{}", CONTENT_DIR, STYLE_FILE), deflated)?;
+    zip.write_all(style.as_bytes())?;
+
+    for chapter in &chapters {
+        zip.start_file(format!("{}/{}", CONTENT_DIR, chapter.file_name), deflated)?;
+        zip.write_all(create_xhtml(metadata, &chapter.title, &chapter.content, "").as_bytes())?;
+    }
+    for image in &images {
+        zip.start_file(
+            format!("{}/{}/{}", CONTENT_DIR, IMAGE_DIR, image.name),
+            stored,
+        )?;
+        zip.write_all(&image.data)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Renders documents to epub e-books
+pub struct EpubRenderer;
+
+impl Renderer for EpubRenderer {
+    fn name(&self) -> &str {
+        "epub"
+    }
+
+    fn extension(&self) -> &str {
+        "epub"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        mut writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        writer.write_all(&render_to_epub(document)?)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Splits the top level blocks into chapters. Blocks before the first section
+/// are put into a separate chapter so that every section starts on a new file.
+fn split_chapters(blocks: &[Block]) -> Vec<(String, String, Vec<&Block>)> {
+    let mut chapters: Vec<(String, String, Vec<&Block>)> = Vec::new();
+    let mut front_matter = Vec::new();
+
+    for block in blocks {
+        match block {
+            Block::Section(section) => chapters.push((
+                format!("chapter-{}.xhtml", chapters.len() + 1),
+                line_to_string(&section.header.line),
+                vec![block],
+            )),
+            Block::Null => {}
+            _ => match chapters.last_mut() {
+                Some((_, _, chapter)) => chapter.push(block),
+                None => front_matter.push(block),
+            },
+        }
+    }
+    if !front_matter.is_empty() || chapters.is_empty() {
+        chapters.insert(0, ("front.xhtml".to_string(), String::new(), front_matter));
+    }
+
+    chapters
+}
+
+/// Wraps html content into an xhtml document
+fn create_xhtml(metadata: &MetadataSettings, title: &str, content: &str, nav: &str) -> String {
+    let title = if title.is_empty() {
+        metadata.title.clone().unwrap_or_default()
+    } else {
+        title.to_string()
+    };
+    let language = encode_attribute(&metadata.language);
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+        <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+        lang=\"{lang}\" xml:lang=\"{lang}\"><head><meta charset=\"UTF-8\"/><title>{title}</title>\
+        <link rel=\"stylesheet\" type=\"text/css\" href=\"{style}\"/></head>\
+        <body>{nav}<div class=\"content\">{content}</div></body></html>",
+        lang = language,
+        title = encode_minimal(&title),
+        style = STYLE_FILE,
+        nav = nav,
+        content = content
+    )
+}
+
+/// Creates the navigation document from the rendered table of contents
+fn create_nav(metadata: &MetadataSettings, toc: &str) -> String {
+    let nav = format!("<nav epub:type=\"toc\" id=\"toc\">{}</nav>", toc);
+
+    create_xhtml(metadata, "", "", &nav)
+}
+
+/// Creates the package document with the metadata, manifest and spine of the book
+fn create_package(metadata: &MetadataSettings, chapters: &[Chapter], images: &[Asset]) -> String {
+    let mut package = String::new();
+    package.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    package.push_str(
+        "<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"uid\">",
+    );
+    package.push_str("<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">");
+    package.push_str(&format!(
+        "<dc:identifier id=\"uid\">{}</dc:identifier>",
+        create_identifier(metadata)
+    ));
+    package.push_str(&format!(
+        "<dc:title>{}</dc:title>",
+        encode_minimal(metadata.title.as_deref().unwrap_or("Untitled"))
+    ));
+    package.push_str(&format!(
+        "<dc:language>{}</dc:language>",
+        encode_minimal(&metadata.language)
+    ));
+    if let Some(author) = &metadata.author {
+        package.push_str(&format!(
+            "<dc:creator>{}</dc:creator>",
+            encode_minimal(author)
+        ));
+    }
+    if let Some(description) = &metadata.description {
+        package.push_str(&format!(
+            "<dc:description>{}</dc:description>",
+            encode_minimal(description)
+        ));
+    }
+    for keyword in &metadata.keywords {
+        package.push_str(&format!(
+            "<dc:subject>{}</dc:subject>",
+            encode_minimal(keyword)
+        ));
+    }
+    package.push_str(&format!(
+        "<meta property=\"dcterms:modified\">{}</meta>",
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+    ));
+    package.push_str("</metadata><manifest>");
+    package.push_str(&format!(
+        "<item id=\"nav\" href=\"{}\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>",
+        NAV_FILE
+    ));
+    package.push_str(&format!(
+        "<item id=\"style\" href=\"{}\" media-type=\"text/css\"/>",
+        STYLE_FILE
+    ));
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        let properties = if chapter.content.contains("<math") {
+            " properties=\"mathml\""
+        } else {
+            ""
+        };
+        package.push_str(&format!(
+            "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"{}/>",
+            index, chapter.file_name, properties
+        ));
+    }
+    for (index, image) in images.iter().enumerate() {
+        package.push_str(&format!(
+            "<item id=\"image-{}\" href=\"{}/{}\" media-type=\"{}\"/>",
+            index,
+            IMAGE_DIR,
+            image.name,
+            image.mime.essence_str()
+        ));
+    }
+    package.push_str("</manifest><spine>");
+
+    for index in 0..chapters.len() {
+        package.push_str(&format!("<itemref idref=\"chapter-{}\"/>", index));
+    }
+    package.push_str("</spine></package>");
+
+    package
+}
+
+/// Creates a unique identifier that stays the same for books with the same metadata
+fn create_identifier(metadata: &MetadataSettings) -> String {
+    let mut hasher = sha2::Sha256::default();
+    hasher.update(metadata.title.as_deref().unwrap_or_default());
+    hasher.update(metadata.author.as_deref().unwrap_or_default());
+    hasher.update(metadata.description.as_deref().unwrap_or_default());
+    hasher.update(&metadata.language);
+    let hash = format!("{:x}", hasher.finalize());
+
+    format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hash[0..8],
+        &hash[8..12],
+        &hash[12..16],
+        &hash[16..20],
+        &hash[20..32]
+    )
+}
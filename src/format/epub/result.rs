@@ -0,0 +1,40 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+
+pub type EpubResult<T> = Result<T, EpubError>;
+
+#[derive(Debug)]
+pub enum EpubError {
+    IoError(io::Error),
+    ZipError(zip::result::ZipError),
+}
+
+impl Display for EpubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpubError::IoError(e) => write!(f, "IO Error: {}", e),
+            EpubError::ZipError(e) => write!(f, "Zip Error: {}", e),
+        }
+    }
+}
+
+impl Error for EpubError {}
+
+impl From<io::Error> for EpubError {
+    fn from(other: io::Error) -> Self {
+        Self::IoError(other)
+    }
+}
+
+impl From<zip::result::ZipError> for EpubError {
+    fn from(other: zip::result::ZipError) -> Self {
+        Self::ZipError(other)
+    }
+}
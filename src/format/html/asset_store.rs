@@ -0,0 +1,103 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use mime::Mime;
+use sha2::Digest;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A file that is referenced by the rendered html instead of being embedded
+#[derive(Clone, Debug)]
+pub struct Asset {
+    pub name: String,
+    pub mime: Mime,
+    pub data: Vec<u8>,
+}
+
+/// Collects assets with names derived from the hash of their content
+/// so that identical files are only stored once
+#[derive(Clone, Debug)]
+pub struct AssetStore {
+    directory: String,
+    assets: Vec<Asset>,
+    names: HashMap<String, usize>,
+}
+
+impl AssetStore {
+    /// Creates a new store with urls relative to the given directory
+    pub fn new(directory: &str) -> Self {
+        Self {
+            directory: directory.trim_end_matches('/').to_string(),
+            assets: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Adds an asset and returns the url it can be referenced with
+    pub fn add(&mut self, data: Vec<u8>, mime: Mime) -> String {
+        let mut hasher = sha2::Sha256::default();
+        hasher.update(&data);
+        let hash = format!("{:x}", hasher.finalize());
+        let extension = get_extension(&mime)
+            .map(|e| format!(".{}", e))
+            .unwrap_or_default();
+        let name = format!("{}{}", &hash[..16], extension);
+
+        if !self.names.contains_key(&name) {
+            self.names.insert(name.clone(), self.assets.len());
+            self.assets.push(Asset {
+                name: name.clone(),
+                mime,
+                data,
+            });
+        }
+
+        self.get_url(&name)
+    }
+
+    /// Returns the relative url for an asset with the given name
+    pub fn get_url(&self, name: &str) -> String {
+        if self.directory.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.directory, name)
+        }
+    }
+
+    /// Returns the directory the assets are stored in
+    pub fn directory(&self) -> &str {
+        &self.directory
+    }
+
+    /// Returns all collected assets
+    pub fn assets(&self) -> &[Asset] {
+        &self.assets
+    }
+
+    /// Writes all assets into the asset directory inside the given directory
+    pub fn write_to(&self, base_dir: &Path) -> io::Result<()> {
+        let directory = base_dir.join(&self.directory);
+        fs::create_dir_all(&directory)?;
+
+        for asset in &self.assets {
+            fs::write(directory.join(&asset.name), &asset.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the common file extension for a mime type
+fn get_extension(mime: &Mime) -> Option<&'static str> {
+    match mime.subtype().as_str() {
+        "jpeg" => Some("jpg"),
+        "svg" => Some("svg"),
+        "css" => Some("css"),
+        _ => mime_guess::get_mime_extensions(mime).and_then(|e| e.first().copied()),
+    }
+}
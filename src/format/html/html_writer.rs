@@ -4,19 +4,46 @@
  * See LICENSE for more information.
  */
 
+use crate::format::html::asset_store::AssetStore;
+use crate::format::html::shared_buffer::SharedBuffer;
+use crate::format::style::CodeStyle;
 use crate::settings::style_settings::Theme;
+use crate::utils::entities::decode_entity;
+use crate::utils::sanitizing::is_safe_url;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
 use std::io;
 use std::io::Write;
+use std::sync::Arc;
+
+lazy_static::lazy_static! {
+    static ref NAMED_ENTITY: Regex = Regex::new(r"&([a-zA-Z][a-zA-Z0-9]*);").unwrap();
+}
 
 pub struct HTMLWriter {
     inner: Box<dyn Write>,
     theme: Theme,
+    code_style: Arc<CodeStyle>,
+    xhtml: bool,
+    safe_mode: bool,
+    assets: Option<AssetStore>,
+    anchor_pages: HashMap<String, String>,
+    current_page: String,
 }
 
 impl HTMLWriter {
     /// Creates a new writer
     pub fn new(inner: Box<dyn Write>, theme: Theme) -> Self {
-        Self { inner, theme }
+        Self {
+            inner,
+            code_style: Arc::new(CodeStyle::for_theme(theme.clone())),
+            theme,
+            xhtml: false,
+            safe_mode: false,
+            assets: None,
+            anchor_pages: HashMap::new(),
+            current_page: String::new(),
+        }
     }
 
     /// Writes a raw string
@@ -24,6 +51,26 @@ impl HTMLWriter {
         self.inner.write_all(html.as_bytes())
     }
 
+    /// Writes generated markup. In xhtml mode named entities
+    /// that aren't defined in xml are replaced with numeric ones
+    pub fn write_markup(&mut self, html: String) -> io::Result<()> {
+        if !self.xhtml {
+            return self.write(html);
+        }
+        let html = NAMED_ENTITY.replace_all(&html, |c: &Captures| match &c[1] {
+            "lt" | "gt" | "amp" | "quot" | "apos" => c[0].to_string(),
+            name => match decode_entity(name) {
+                Some(decoded) => decoded
+                    .chars()
+                    .map(|c| format!("&#x{:X};", c as u32))
+                    .collect(),
+                None => format!("&amp;{};", name),
+            },
+        });
+
+        self.write(html.to_string())
+    }
+
     /// Writes an escaped string
     pub fn write_escaped(&mut self, html: String) -> io::Result<()> {
         self.write(htmlescape::encode_minimal(html.as_str()))
@@ -34,6 +81,41 @@ impl HTMLWriter {
         self.write(htmlescape::encode_attribute(attribute_value.as_str()))
     }
 
+    /// Writes an escaped url attribute. In safe mode urls
+    /// with schemes that can execute code are replaced with an empty anchor
+    pub fn write_url(&mut self, url: String) -> io::Result<()> {
+        if self.safe_mode && !is_safe_url(&url) {
+            log::warn!("Removed unsafe url \"{}\"", url);
+            return self.write("#".to_string());
+        }
+        self.write_attribute(url)
+    }
+
+    /// Writes the content of a style tag. In safe mode the content
+    /// is prevented from closing the style tag
+    pub fn write_style_content(&mut self, style: String) -> io::Result<()> {
+        if self.safe_mode {
+            self.write(style.replace("</", "<\\/"))
+        } else {
+            self.write(style)
+        }
+    }
+
+    /// Runs the given function with the output redirected into a buffer
+    /// and returns the html that has been written
+    pub fn capture<F>(&mut self, render: F) -> io::Result<String>
+    where
+        F: FnOnce(&mut Self) -> io::Result<()>,
+    {
+        let buffer = SharedBuffer::default();
+        let inner = std::mem::replace(&mut self.inner, Box::new(buffer.clone()));
+        let result = render(self);
+        self.inner = inner;
+        result?;
+
+        Ok(buffer.take_string())
+    }
+
     /// Flushes the writer
     pub fn flush(&mut self) -> io::Result<()> {
         self.inner.flush()
@@ -43,4 +125,68 @@ impl HTMLWriter {
     pub fn get_theme(&mut self) -> Theme {
         self.theme.clone()
     }
+
+    /// Sets the syntaxes and themes code blocks are highlighted with
+    pub fn set_code_style(&mut self, code_style: CodeStyle) {
+        self.code_style = Arc::new(code_style);
+    }
+
+    /// Returns the syntaxes and themes code blocks are highlighted with
+    pub fn get_code_style(&self) -> Arc<CodeStyle> {
+        Arc::clone(&self.code_style)
+    }
+
+    /// Sets if the output needs to be well formed xhtml
+    pub fn set_xhtml(&mut self, xhtml: bool) {
+        self.xhtml = xhtml;
+    }
+
+    /// Returns if the output needs to be well formed xhtml
+    pub fn is_xhtml(&self) -> bool {
+        self.xhtml
+    }
+
+    /// Sets if untrusted content needs to be sanitized
+    pub fn set_safe_mode(&mut self, safe_mode: bool) {
+        self.safe_mode = safe_mode;
+    }
+
+    /// Returns if untrusted content needs to be sanitized
+    pub fn is_safe_mode(&self) -> bool {
+        self.safe_mode
+    }
+
+    /// Sets the store that embedded files are written to instead of inlining them
+    pub fn set_asset_store(&mut self, store: AssetStore) {
+        self.assets = Some(store);
+    }
+
+    /// Removes the asset store from the writer and returns it
+    pub fn take_asset_store(&mut self) -> Option<AssetStore> {
+        self.assets.take()
+    }
+
+    /// Returns the asset store if one is set
+    pub fn get_asset_store(&mut self) -> Option<&mut AssetStore> {
+        self.assets.as_mut()
+    }
+
+    /// Sets the pages the anchors are located on for documents split into multiple pages
+    pub fn set_anchor_pages(&mut self, anchor_pages: HashMap<String, String>) {
+        self.anchor_pages = anchor_pages;
+    }
+
+    /// Sets the page that is currently written
+    pub fn set_current_page(&mut self, page: &str) {
+        self.current_page = page.to_string();
+    }
+
+    /// Returns the url for an anchor which includes the page
+    /// if the anchor is located on a different page
+    pub fn get_anchor_url(&self, anchor: &str) -> String {
+        match self.anchor_pages.get(anchor) {
+            Some(page) if *page != self.current_page => format!("{}#{}", page, anchor),
+            _ => format!("#{}", anchor),
+        }
+    }
 }
@@ -4,5 +4,80 @@
  * See LICENSE for more information.
  */
 
+use crate::elements::Document;
+use crate::format::html::asset_store::AssetStore;
+use crate::format::html::html_writer::HTMLWriter;
+use crate::format::html::to_html::ToHtml;
+use crate::format::style::CodeStyle;
+use crate::format::{RenderResult, Renderer};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub mod asset_store;
 pub mod html_writer;
+pub mod search_index;
+pub mod shared_buffer;
 pub mod to_html;
+
+/// The directory assets are written to when they aren't embedded into the html
+const ASSET_DIR: &str = "assets";
+
+/// Renders the document to a single html file. If external assets are enabled images and
+/// styles are written to an asset directory inside the output directory instead of being embedded.
+pub fn render_to_html(
+    document: Document,
+    inner: Box<dyn Write>,
+    output_dir: Option<&Path>,
+) -> io::Result<()> {
+    let settings = document.config.lock().clone();
+    let mut writer = HTMLWriter::new(inner, settings.style.theme.clone());
+    writer.set_safe_mode(settings.safe_mode);
+    writer.set_code_style(CodeStyle::load(&settings, &document.get_root_dir()));
+
+    let output_dir = match output_dir {
+        Some(dir) if settings.html.external_assets => Some(dir),
+        None if settings.html.external_assets => {
+            log::warn!("Assets are embedded when rendering html to stdout");
+            None
+        }
+        _ => None,
+    };
+    if output_dir.is_some() {
+        document.create_image_variants();
+        writer.set_asset_store(AssetStore::new(ASSET_DIR));
+    }
+    document.to_html(&mut writer)?;
+    writer.flush()?;
+
+    if let (Some(dir), Some(assets)) = (output_dir, writer.take_asset_store()) {
+        log::info!("Writing {} assets...", assets.assets().len());
+        assets.write_to(dir)?;
+    }
+
+    Ok(())
+}
+
+/// Renders documents to html
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+    fn name(&self) -> &str {
+        "html"
+    }
+
+    fn extension(&self) -> &str {
+        "html"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        writer: Box<dyn Write>,
+        output: Option<&Path>,
+    ) -> RenderResult<()> {
+        render_to_html(document, writer, output.and_then(|o| o.parent()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::{Block, Element, ListItem};
+use crate::format::pdf_outline::{inline_to_string, line_to_string};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The script that queries the search index in the browser
+pub const SEARCH_SCRIPT: &str = include_str!("../assets/search.js");
+
+/// The html of the search box that is filled by the search script
+pub const SEARCH_BOX: &str = "<div class=\"searchBox\">\
+    <input class=\"searchInput\" type=\"search\" placeholder=\"Search\" aria-label=\"Search\">\
+    <ul class=\"searchResults\"></ul></div>";
+
+/// Words that are shorter than this are not indexed
+const MIN_TERM_LENGTH: usize = 2;
+
+/// Suffixes that are removed from words together with their replacements.
+/// The same rules are used by the search script to stem the query.
+const SUFFIX_RULES: &[(&str, &str)] = &[
+    ("sses", "ss"),
+    ("ies", "y"),
+    ("ss", "ss"),
+    ("ingly", ""),
+    ("edly", ""),
+    ("ing", ""),
+    ("ed", ""),
+    ("ly", ""),
+    ("s", ""),
+];
+
+/// A section of the document that can be found with the search
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchSection {
+    pub page: String,
+    pub anchor: String,
+    pub title: String,
+}
+
+/// An index that maps stemmed terms to the sections they occur in
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SearchIndex {
+    sections: Vec<SearchSection>,
+    terms: BTreeMap<String, BTreeSet<usize>>,
+}
+
+impl SearchIndex {
+    /// Adds the blocks of a page to the index. Every section gets its own entry
+    /// while the content outside of sections is indexed with the given title.
+    pub fn add_blocks(&mut self, page: &str, title: &str, blocks: &[Block]) {
+        let mut text = String::new();
+
+        for block in blocks {
+            self.add_block(page, block, &mut text);
+        }
+        if !text.trim().is_empty() {
+            let index = self.sections.len();
+            self.sections.push(SearchSection {
+                page: page.to_string(),
+                anchor: String::new(),
+                title: title.to_string(),
+            });
+            self.add_terms(index, &text);
+        }
+    }
+
+    /// Returns the index as a script that assigns it to a global variable
+    pub fn to_script(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string());
+
+        // prevent the script tag from being closed by the content when embedded into html
+        format!("var snekdownSearchIndex={};", json.replace("</", "<\\/"))
+    }
+
+    fn add_block(&mut self, page: &str, block: &Block, text: &mut String) {
+        match block {
+            Block::Section(section) => {
+                let title = line_to_string(&section.header.line);
+                let index = self.sections.len();
+                self.sections.push(SearchSection {
+                    page: page.to_string(),
+                    anchor: section.header.anchor.clone(),
+                    title: title.clone(),
+                });
+                let mut section_text = title;
+
+                for element in &section.elements {
+                    self.add_block(page, element, &mut section_text);
+                }
+                self.add_terms(index, &section_text);
+            }
+            Block::Import(import) => {
+                if let Some(document) = &import.anchor.read().unwrap().document {
+                    for element in &document.elements {
+                        self.add_block(page, element, text);
+                    }
+                }
+            }
+            // block placeholders are generated content like the table of contents
+            Block::Placeholder(placeholder) => match &placeholder.read().unwrap().value {
+                Some(Element::Line(line)) => push_text(&line_to_string(line), text),
+                Some(Element::Inline(inline)) => push_text(&inline_to_string(inline), text),
+                _ => {}
+            },
+            block => push_block_text(block, text),
+        }
+    }
+
+    fn add_terms(&mut self, index: usize, text: &str) {
+        for term in get_terms(text) {
+            self.terms.entry(term).or_default().insert(index);
+        }
+    }
+}
+
+/// Appends the text of a block that doesn't contain any sections
+fn push_block_text(block: &Block, text: &mut String) {
+    match block {
+        Block::Paragraph(paragraph) => paragraph
+            .elements
+            .iter()
+            .for_each(|l| push_text(&line_to_string(l), text)),
+        Block::List(list) => list.items.iter().for_each(|i| push_list_item_text(i, text)),
+        Block::Table(table) => std::iter::once(&table.header)
+            .chain(table.rows.iter())
+            .flat_map(|r| r.cells.iter())
+            .for_each(|c| push_text(&line_to_string(&c.text), text)),
+        Block::Quote(quote) => quote
+            .text
+            .iter()
+            .flat_map(|l| l.subtext.iter())
+            .for_each(|i| push_text(&inline_to_string(i), text)),
+        Block::CodeBlock(code) => push_text(&code.code, text),
+        Block::MathBlock(math) => push_text(&math.source, text),
+        _ => {}
+    }
+}
+
+fn push_list_item_text(item: &ListItem, text: &mut String) {
+    push_text(&line_to_string(&item.text), text);
+    item.children
+        .iter()
+        .for_each(|c| push_list_item_text(c, text));
+}
+
+fn push_text(value: &str, text: &mut String) {
+    text.push(' ');
+    text.push_str(value);
+}
+
+/// Returns the distinct stemmed terms of a text
+fn get_terms(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_TERM_LENGTH)
+        .map(|w| stem(&w.to_lowercase()))
+        .collect()
+}
+
+/// Removes common english suffixes from a lowercase word
+pub fn stem(word: &str) -> String {
+    let length = word.chars().count();
+
+    for (suffix, replacement) in SUFFIX_RULES {
+        if word.ends_with(suffix) && length - suffix.len() >= 3 {
+            return format!("{}{}", &word[..word.len() - suffix.len()], replacement);
+        }
+    }
+
+    word.to_string()
+}
@@ -0,0 +1,33 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use parking_lot::Mutex;
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+
+/// A writer that stores the written data in a buffer that can be
+/// accessed after the html writer has been dropped
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Returns the written data and clears the buffer
+    pub fn take_string(&self) -> String {
+        String::from_utf8_lossy(&std::mem::take(&mut *self.0.lock())).to_string()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -6,17 +6,30 @@
 
 use crate::elements::*;
 use crate::format::html::html_writer::HTMLWriter;
-use crate::format::style::{get_code_theme_for_theme, get_css_for_theme};
+use crate::format::html::search_index::{SearchIndex, SEARCH_BOX, SEARCH_SCRIPT};
+use crate::format::pdf_outline::inline_to_string;
+use crate::format::style::{get_css_for_theme, CODE_CLASS_STYLE};
 use crate::format::PlaceholderTemplate;
 use crate::references::glossary::{GlossaryDisplay, GlossaryReference};
 use crate::references::templates::{Template, TemplateVariable};
+use crate::settings::style_settings::CodeHighlighting;
+use crate::settings::Settings;
+use crate::utils::sanitizing::{is_inside_root, is_safe_color, is_safe_length};
 use asciimath_rs::format::mathml::ToMathML;
-use htmlescape::encode_attribute;
+use htmlescape::{encode_attribute, encode_minimal};
 use minify::html::minify;
+use std::fs;
 use std::io;
-use syntect::html::highlighted_html_for_string;
+use std::path::{Path, PathBuf};
+use syntect::easy::HighlightLines;
+use syntect::html::{
+    highlighted_html_for_string, start_highlighted_html_snippet, styled_line_to_highlighted_html,
+    ClassedHTMLGenerator, IncludeBackground,
+};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
 
-const MATHJAX_URL: &str = "https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js";
+pub(crate) const MATHJAX_URL: &str = "https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js";
 
 pub trait ToHtml {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()>;
@@ -64,7 +77,7 @@ impl ToHtml for Inline {
             Inline::BibReference(bibref) => bibref.read().unwrap().to_html(writer),
             Inline::TemplateVar(var) => var.read().unwrap().to_html(writer),
             Inline::Math(m) => m.to_html(writer),
-            Inline::LineBreak => writer.write("<br>".to_string()),
+            Inline::LineBreak => writer.write("<br/>".to_string()),
             Inline::CharacterCode(code) => code.to_html(writer),
             Inline::GlossaryReference(gloss) => gloss.lock().to_html(writer),
             Inline::Arrow(a) => a.to_html(writer),
@@ -112,78 +125,34 @@ impl ToHtml for Document {
         };
 
         if self.is_root {
-            let metadata = self.config.lock().metadata.clone();
+            let settings = self.config.lock().clone();
 
-            let style = minify(get_css_for_theme(writer.get_theme()).as_str());
+            if let Some(layout) = self.read_layout(&settings) {
+                return self.write_layout(writer, &settings, layout);
+            }
             writer.write("<!DOCTYPE html>".to_string())?;
             writer.write("<html lang=\"".to_string())?;
-            writer.write_attribute(metadata.language)?;
+            writer.write_attribute(settings.metadata.language.clone())?;
             writer.write("\"><head>".to_string())?;
-            writer.write("<meta charset=\"UTF-8\">".to_string())?;
-
-            if let Some(author) = metadata.author {
-                writer.write("<meta name=\"author\" content=\"".to_string())?;
-                writer.write_attribute(author)?;
-                writer.write("\">".to_string())?;
-            }
+            self.write_metadata(writer, &settings)?;
 
-            if let Some(title) = metadata.title {
+            if let Some(title) = settings.metadata.title.clone() {
                 writer.write("<title>".to_string())?;
-                writer.write_escaped(title.clone())?;
+                writer.write_escaped(title)?;
                 writer.write("</title>".to_string())?;
-                writer.write("<meta name=\"title\" content=\"".to_string())?;
-                writer.write_attribute(title)?;
-                writer.write("\">".to_string())?;
-            }
-
-            if let Some(description) = metadata.description {
-                writer.write("<meta name=\"description\" content=\"".to_string())?;
-                writer.write_attribute(description)?;
-                writer.write("\">".to_string())?;
-            }
-
-            if !metadata.keywords.is_empty() {
-                writer.write("<meta name=\"keywords\" content=\"".to_string())?;
-                writer.write_attribute(
-                    metadata
-                        .keywords
-                        .iter()
-                        .fold("".to_string(), |a, b| format!("{}, {}", a, b))
-                        .trim_start_matches(", ")
-                        .to_string(),
-                )?;
-                writer.write("\">".to_string())?;
-            }
-
-            writer.write("<style>".to_string())?;
-            writer.write(style)?;
-            writer.write("</style>".to_string())?;
-
-            if self.config.lock().features.include_mathjax {
-                writer.write(format!(
-                    "<script id=\"MathJax-script\" type=\"text/javascript\" async src={}></script>",
-                    MATHJAX_URL
-                ))?;
-            }
-
-            for stylesheet in &self.stylesheets {
-                let mut stylesheet = stylesheet.lock();
-                let data = std::mem::replace(&mut stylesheet.data, None);
-                if let Some(data) = data {
-                    writer.write("<style>".to_string())?;
-                    writer.write(minify(String::from_utf8(data).unwrap().as_str()))?;
-                    writer.write("</style>".to_string())?;
-                } else {
-                    writer.write("<link rel=\"stylsheet\" href=\"".to_string())?;
-                    writer.write_attribute(stylesheet.path.clone())?;
-                    writer.write("\">".to_string())?;
-                }
             }
+            self.write_styles(writer)?;
+            self.write_scripts(writer, &settings)?;
             writer.write("</head><body><div class=\"content\">".to_string())?;
+            if settings.features.include_search {
+                writer.write(SEARCH_BOX.to_string())?;
+            }
             for element in &self.elements {
                 element.to_html(writer)?;
             }
-            writer.write("</div></body></html>".to_string())?;
+            writer.write("</div>".to_string())?;
+            self.write_search_script(writer, &settings)?;
+            writer.write("</body></html>".to_string())?;
         } else {
             writer.write("<div class=\"documentImport\" document-import=\"true\" ".to_string())?;
             writer.write(path)?;
@@ -199,10 +168,196 @@ impl ToHtml for Document {
     }
 }
 
+impl Document {
+    /// Reads the custom layout file of the html output. Relative paths are
+    /// resolved from the directory of the document.
+    fn read_layout(&self, settings: &Settings) -> Option<String> {
+        let mut path = PathBuf::from(settings.html.layout.as_ref()?);
+
+        if path.is_relative() {
+            if let Some(dir) = self.path.as_ref().and_then(|p| Path::new(p).parent()) {
+                path = dir.join(path);
+            }
+        }
+        if settings.safe_mode && !is_inside_root(&path, &self.get_root_dir()) {
+            log::error!(
+                "Layout {:?} is ignored: Layouts outside of the project root are disabled in safe mode",
+                path
+            );
+            return None;
+        }
+        match fs::read_to_string(&path) {
+            Ok(layout) => Some(layout),
+            Err(e) => {
+                log::error!("Failed to read layout {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Writes the document into the slots of a custom layout
+    fn write_layout(
+        &self,
+        writer: &mut HTMLWriter,
+        settings: &Settings,
+        layout: String,
+    ) -> io::Result<()> {
+        let metadata = &settings.metadata;
+        let mut template = PlaceholderTemplate::new(layout);
+        let attributes = settings
+            .custom_attributes
+            .iter()
+            .map(|(k, v)| (k.clone(), encode_minimal(v)))
+            .collect();
+        template.set_replacements(attributes);
+
+        template.add_replacement("language", &encode_attribute(&metadata.language));
+        template.add_replacement(
+            "title",
+            &encode_minimal(metadata.title.as_deref().unwrap_or_default()),
+        );
+        template.add_replacement(
+            "metadata",
+            &writer.capture(|w| self.write_metadata(w, settings))?,
+        );
+        template.add_replacement("styles", &writer.capture(|w| self.write_styles(w))?);
+        template.add_replacement(
+            "scripts",
+            &writer.capture(|w| {
+                self.write_scripts(w, settings)?;
+                self.write_search_script(w, settings)
+            })?,
+        );
+        if settings.features.include_search {
+            template.add_replacement("search", SEARCH_BOX);
+        }
+        template.add_replacement(
+            "toc",
+            &writer.capture(|w| self.create_toc(true).to_html(w))?,
+        );
+        template.add_replacement(
+            "body",
+            &writer.capture(|w| {
+                for element in &self.elements {
+                    element.to_html(w)?;
+                }
+                Ok(())
+            })?,
+        );
+
+        writer.write(template.render())
+    }
+
+    /// Writes the meta tags of the document
+    fn write_metadata(&self, writer: &mut HTMLWriter, settings: &Settings) -> io::Result<()> {
+        let metadata = settings.metadata.clone();
+        writer.write("<meta charset=\"UTF-8\">".to_string())?;
+
+        if let Some(author) = metadata.author {
+            writer.write("<meta name=\"author\" content=\"".to_string())?;
+            writer.write_attribute(author)?;
+            writer.write("\">".to_string())?;
+        }
+
+        if let Some(title) = metadata.title {
+            writer.write("<meta name=\"title\" content=\"".to_string())?;
+            writer.write_attribute(title)?;
+            writer.write("\">".to_string())?;
+        }
+
+        if let Some(description) = metadata.description {
+            writer.write("<meta name=\"description\" content=\"".to_string())?;
+            writer.write_attribute(description)?;
+            writer.write("\">".to_string())?;
+        }
+
+        if !metadata.keywords.is_empty() {
+            writer.write("<meta name=\"keywords\" content=\"".to_string())?;
+            writer.write_attribute(metadata.keywords.join(", "))?;
+            writer.write("\">".to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the style of the theme and the imported stylesheets.
+    /// If the writer has an asset store the styles are linked instead of embedded.
+    fn write_styles(&self, writer: &mut HTMLWriter) -> io::Result<()> {
+        let style = minify(get_css_for_theme(writer.get_theme()).as_str());
+        write_style(writer, style)?;
+        let code_style = writer.get_code_style().get_css();
+        if !code_style.is_empty() {
+            write_style(writer, code_style)?;
+        }
+
+        for stylesheet in &self.stylesheets {
+            let stylesheet = stylesheet.lock();
+            if let Some(data) = &stylesheet.data {
+                write_style(writer, minify(String::from_utf8_lossy(data).as_ref()))?;
+            } else if writer.is_safe_mode() {
+                // stylesheets that are remote or outside of the project root aren't loaded in safe mode
+                log::warn!(
+                    "Removed stylesheet \"{}\" that couldn't be embedded",
+                    stylesheet.path
+                );
+            } else {
+                write_stylesheet_link(writer, stylesheet.path.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the scripts that are loaded in the head of the document
+    fn write_scripts(&self, writer: &mut HTMLWriter, settings: &Settings) -> io::Result<()> {
+        if settings.features.include_mathjax {
+            writer.write(format!(
+                "<script id=\"MathJax-script\" type=\"text/javascript\" async src={}></script>",
+                MATHJAX_URL
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the search index and the script querying it if the search is enabled
+    fn write_search_script(&self, writer: &mut HTMLWriter, settings: &Settings) -> io::Result<()> {
+        if settings.features.include_search {
+            let mut index = SearchIndex::default();
+            let title = settings.metadata.title.clone().unwrap_or_default();
+            index.add_blocks("", &title, &self.elements);
+            writer.write("<script>".to_string())?;
+            writer.write(index.to_script())?;
+            writer.write(SEARCH_SCRIPT.to_string())?;
+            writer.write("</script>".to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a style that is either embedded or added to the asset store of the writer
+fn write_style(writer: &mut HTMLWriter, style: String) -> io::Result<()> {
+    if let Some(assets) = writer.get_asset_store() {
+        let url = assets.add(style.into_bytes(), mime::TEXT_CSS);
+        write_stylesheet_link(writer, url)
+    } else {
+        writer.write("<style>".to_string())?;
+        writer.write_style_content(style)?;
+        writer.write("</style>".to_string())
+    }
+}
+
+fn write_stylesheet_link(writer: &mut HTMLWriter, url: String) -> io::Result<()> {
+    writer.write("<link rel=\"stylesheet\" href=\"".to_string())?;
+    writer.write_attribute(url)?;
+    writer.write("\">".to_string())
+}
+
 impl ToHtml for Math {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
         writer.write("<math xmlns='http://www.w3.org/1998/Math/MathML'>".to_string())?;
-        writer.write(self.expression.to_mathml())?;
+        writer.write_markup(self.expression.to_mathml())?;
 
         writer.write("</math>".to_string())
     }
@@ -213,7 +368,7 @@ impl ToHtml for MathBlock {
         writer.write(
             "<math xmlns='http://www.w3.org/1998/Math/MathML' display='block'>".to_string(),
         )?;
-        writer.write(self.expression.to_mathml())?;
+        writer.write_markup(self.expression.to_mathml())?;
 
         writer.write("</math>".to_string())
     }
@@ -238,19 +393,30 @@ impl ToHtml for Section {
 
 impl ToHtml for Header {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
-        writer.write(format!("<h{}", self.size))?;
+        // html only defines six heading levels, deeper ones keep their level for assistive technology
+        let level = self.size.clamp(1, 6);
+        writer.write(format!("<h{}", level))?;
+        if self.size > level {
+            writer.write(format!(" aria-level=\"{}\"", self.size))?;
+        }
         writer.write(" id=\"".to_string())?;
         writer.write_attribute(self.anchor.clone())?;
         writer.write("\">".to_string())?;
         self.line.to_html(writer)?;
 
-        writer.write(format!("</h{}>", self.size))
+        writer.write(format!("</h{}>", level))
     }
 }
 
 impl ToHtml for Paragraph {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
-        writer.write("<div class=\"paragraph\">".to_string())?;
+        // paragraphs containing figures, anchors or other blocks can't be written as p elements
+        let tag = if self.elements.iter().all(is_phrasing_line) {
+            "p"
+        } else {
+            "div"
+        };
+        writer.write(format!("<{} class=\"paragraph\">", tag))?;
 
         if let Some(first) = self.elements.first() {
             first.to_html(writer)?;
@@ -262,7 +428,7 @@ impl ToHtml for Paragraph {
             }
         }
 
-        writer.write("</div>".to_string())
+        writer.write(format!("</{}>", tag))
     }
 }
 
@@ -312,19 +478,19 @@ impl ToHtml for ListItem {
 
 impl ToHtml for Table {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
-        writer.write("<div class=\"tableWrapper\"><table><tr>".to_string())?;
+        writer.write("<div class=\"tableWrapper\"><table><thead><tr>".to_string())?;
 
         for cell in &self.header.cells {
-            writer.write("<th>".to_string())?;
+            writer.write("<th scope=\"col\">".to_string())?;
             cell.text.to_html(writer)?;
             writer.write("</th>".to_string())?;
         }
-        writer.write("</tr>".to_string())?;
+        writer.write("</tr></thead><tbody>".to_string())?;
         for row in &self.rows {
             row.to_html(writer)?;
         }
 
-        writer.write("</table></div>".to_string())
+        writer.write("</tbody></table></div>".to_string())
     }
 }
 
@@ -351,33 +517,166 @@ impl ToHtml for Cell {
 
 impl ToHtml for CodeBlock {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
+        let file_name = self.get_file_name();
+        let caption = self.get_caption();
+        let anchor = self.get_anchor();
+        let is_listing = file_name.is_some() || caption.is_some() || anchor.is_some();
+
+        if is_listing {
+            writer.write("<figure class=\"codeListing\"".to_string())?;
+            if let Some(anchor) = anchor {
+                writer.write(" id=\"".to_string())?;
+                writer.write_attribute(anchor)?;
+                writer.write("\"".to_string())?;
+            }
+            writer.write(">".to_string())?;
+            if let Some(file_name) = file_name {
+                writer.write("<div class=\"codeFileName\">".to_string())?;
+                writer.write_escaped(file_name)?;
+                writer.write("</div>".to_string())?;
+            }
+        }
         writer.write("<div><code".to_string())?;
-
         if self.language.len() > 0 {
             writer.write(" lang=\"".to_string())?;
             writer.write_attribute(self.language.clone())?;
-            writer.write("\">".to_string())?;
-            let (theme, syntax_set) = get_code_theme_for_theme(writer.get_theme());
-
-            if let Some(syntax) = syntax_set.find_syntax_by_token(self.language.as_str()) {
-                writer.write(highlighted_html_for_string(
-                    self.code.as_str(),
-                    &syntax_set,
-                    syntax,
-                    &theme,
-                ))?;
-            } else {
-                writer.write("<pre>".to_string())?;
-                writer.write_escaped(self.code.clone())?;
-                writer.write("</pre>".to_string())?;
+            writer.write("\"".to_string())?;
+        }
+        writer.write(">".to_string())?;
+
+        if self.has_line_numbers() || !self.get_highlighted_lines().is_empty() {
+            write_code_lines(self, writer)?;
+        } else {
+            write_code(self, writer)?;
+        }
+        writer.write("</code></div>".to_string())?;
+
+        if is_listing {
+            if let Some(caption) = caption {
+                writer.write("<figcaption class=\"codeCaption\">".to_string())?;
+                writer.write_escaped(caption)?;
+                writer.write("</figcaption>".to_string())?;
             }
+            writer.write("</figure>".to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the highlighted code of a code block
+fn write_code(code_block: &CodeBlock, writer: &mut HTMLWriter) -> io::Result<()> {
+    let code_style = writer.get_code_style();
+    let syntax_set = &code_style.syntax_set;
+
+    if let Some(syntax) = find_code_syntax(code_block, syntax_set) {
+        if code_style.highlighting == CodeHighlighting::Inline {
+            writer.write(highlighted_html_for_string(
+                code_block.code.as_str(),
+                syntax_set,
+                syntax,
+                &code_style.theme,
+            ))?;
         } else {
-            writer.write("><pre>".to_string())?;
-            writer.write_escaped(self.code.clone())?;
+            let mut generator =
+                ClassedHTMLGenerator::new_with_class_style(syntax, syntax_set, CODE_CLASS_STYLE);
+            for line in LinesWithEndings::from(&code_block.code) {
+                generator.parse_html_for_line_which_includes_newline(line);
+            }
+            writer.write("<pre class=\"syntax-code\">".to_string())?;
+            writer.write(generator.finalize())?;
             writer.write("</pre>".to_string())?;
         }
+    } else {
+        writer.write("<pre>".to_string())?;
+        writer.write_escaped(code_block.code.clone())?;
+        writer.write("</pre>".to_string())?;
+    }
+
+    Ok(())
+}
 
-        writer.write("</code></div>".to_string())
+/// Writes the highlighted code of a code block with every line wrapped in its own element
+/// so that lines can be numbered and highlighted
+fn write_code_lines(code_block: &CodeBlock, writer: &mut HTMLWriter) -> io::Result<()> {
+    let code_style = writer.get_code_style();
+    let syntax_set = &code_style.syntax_set;
+    let code = code_block.code.trim_end_matches('\n');
+    let mut classes = Vec::new();
+    let mut style = String::new();
+
+    let lines = if let Some(syntax) = find_code_syntax(code_block, syntax_set) {
+        if code_style.highlighting == CodeHighlighting::Inline {
+            let (_, background) = start_highlighted_html_snippet(&code_style.theme);
+            style = format!(
+                "background-color:#{:02x}{:02x}{:02x};",
+                background.r, background.g, background.b
+            );
+            let mut highlighter = HighlightLines::new(syntax, &code_style.theme);
+
+            code.lines()
+                .map(|line| {
+                    styled_line_to_highlighted_html(
+                        &highlighter.highlight(line, syntax_set),
+                        IncludeBackground::IfDifferent(background),
+                    )
+                })
+                .collect()
+        } else {
+            classes.push("syntax-code");
+            let mut generator =
+                ClassedHTMLGenerator::new_with_class_style(syntax, syntax_set, CODE_CLASS_STYLE);
+            for line in LinesWithEndings::from(code) {
+                generator.parse_html_for_line_which_includes_newline(line);
+            }
+            split_highlighted_lines(&generator.finalize())
+        }
+    } else {
+        code.lines().map(encode_minimal).collect::<Vec<String>>()
+    };
+    let line_numbers = code_block.has_line_numbers();
+    if line_numbers {
+        classes.push("numberedLines");
+    }
+    let start = code_block.get_start_line();
+    let highlighted = code_block.get_highlighted_lines();
+
+    writer.write("<pre".to_string())?;
+    if !classes.is_empty() {
+        writer.write(format!(" class=\"{}\"", classes.join(" ")))?;
+    }
+    if !style.is_empty() {
+        writer.write(format!(" style=\"{}\"", style))?;
+    }
+    writer.write(">".to_string())?;
+
+    for (index, line) in lines.into_iter().enumerate() {
+        let number = start + index;
+        if highlighted.iter().any(|r| r.contains(&number)) {
+            writer.write("<span class=\"codeLine highlightedLine\"".to_string())?;
+        } else {
+            writer.write("<span class=\"codeLine\"".to_string())?;
+        }
+        if line_numbers {
+            writer.write(format!(" data-line=\"{}\"", number))?;
+        }
+        writer.write(">".to_string())?;
+        writer.write(line)?;
+        writer.write("</span>\n".to_string())?;
+    }
+
+    writer.write("</pre>".to_string())
+}
+
+/// Returns the syntax of the language of a code block
+fn find_code_syntax<'a>(
+    code_block: &CodeBlock,
+    syntax_set: &'a SyntaxSet,
+) -> Option<&'a SyntaxReference> {
+    if code_block.language.is_empty() {
+        None
+    } else {
+        syntax_set.find_syntax_by_token(code_block.language.as_str())
     }
 }
 
@@ -416,46 +715,102 @@ impl ToHtml for TextLine {
 impl ToHtml for Image {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
         let mut style = String::new();
-
-        let url = if let Some(content) = self.get_content() {
+        let mut srcset = Vec::new();
+        let dimensions = self.get_dimensions();
+        let alt = self
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get_string("alt"))
+            .or_else(|| {
+                self.url
+                    .description
+                    .as_ref()
+                    .map(|d| d.iter().map(inline_to_string).collect::<String>())
+            })
+            .unwrap_or_default();
+        let content = self.get_content();
+
+        if content.is_none() && writer.is_safe_mode() {
+            // the image is remote or outside of the project directory
+            log::warn!(
+                "Removed image \"{}\" that couldn't be embedded",
+                self.url.url
+            );
+            return writer.write_escaped(alt);
+        }
+        let url = if let Some(content) = content {
             let mime_type = self.get_mime_type();
-            format!(
-                "data:{};base64,{}",
-                mime_type.to_string(),
-                base64::encode(content)
-            )
+            if let Some(assets) = writer.get_asset_store() {
+                let url = assets.add(content, mime_type.clone());
+                for variant in self.get_variants() {
+                    let variant_url = assets.add(variant.data, mime_type.clone());
+                    srcset.push(format!("{} {}w", variant_url, variant.width));
+                }
+                if let (false, Some((width, _))) = (srcset.is_empty(), dimensions) {
+                    srcset.push(format!("{} {}w", url, width));
+                }
+                url
+            } else {
+                format!(
+                    "data:{};base64,{}",
+                    mime_type.to_string(),
+                    base64::encode(content)
+                )
+            }
         } else {
-            encode_attribute(self.url.url.as_str())
+            writer.capture(|w| w.write_url(self.url.url.clone()))?
         };
         if let Some(meta) = &self.metadata {
-            if let Some(width) = meta.get_string("width") {
-                style = format!("{}width: {};", style, width)
-            }
-            if let Some(height) = meta.get_string("height") {
-                style = format!("{}height: {};", style, height)
+            for key in &["width", "height"] {
+                if let Some(value) = meta.get_string(key) {
+                    if writer.is_safe_mode() && !is_safe_length(&value) {
+                        log::warn!("Removed unsafe image {} \"{}\"", key, value);
+                        continue;
+                    }
+                    style = format!("{}{}: {};", style, key, value)
+                }
             }
         }
+
+        let mut img = format!("<img src=\"{}\"", url);
+        if let (false, Some((width, _))) = (srcset.is_empty(), dimensions) {
+            img += &format!(
+                " srcset=\"{}\" sizes=\"(max-width: {}px) 100vw, {}px\"",
+                srcset.join(", "),
+                width,
+                width
+            );
+        }
+        if let Some((width, height)) = dimensions {
+            img += &format!(" width=\"{}\" height=\"{}\"", width, height);
+        }
+        // embedded images are already loaded with the document
+        if writer.get_asset_store().is_some() && !writer.is_xhtml() {
+            img += " loading=\"lazy\"";
+        }
+        img += &format!(
+            " alt=\"{}\" style=\"{}\"/>",
+            encode_attribute(&alt),
+            encode_attribute(&style)
+        );
+
         if let Some(description) = self.url.description.clone() {
-            writer.write("<div class=\"figure\"><a href=\"".to_string())?;
-            writer.write_attribute(url.clone())?;
-            writer.write("\"><img src=\"".to_string())?;
+            writer.write("<figure class=\"figure\"><a href=\"".to_string())?;
             writer.write(url)?;
-            writer.write("\" style=\"".to_string())?;
-            writer.write(style)?;
-            writer.write("\"/></a><br><label class=\"imageDescripton\">".to_string())?;
+            writer.write("\">".to_string())?;
+            writer.write(img)?;
+            writer.write("</a><figcaption class=\"imageDescription\">".to_string())?;
             for item in description {
                 item.to_html(writer)?;
                 writer.write("&#32;".to_string())?;
             }
-            writer.write("</label></div>".to_string())?;
+            writer.write("</figcaption></figure>".to_string())?;
         } else {
             writer.write("<a href=\"".to_string())?;
-            writer.write(url.clone())?;
-            writer.write("\"><img src=\"".to_string())?;
             writer.write(url)?;
-            writer.write("\" style=\"".to_string())?;
-            writer.write(style)?;
-            writer.write("\"/></a>".to_string())?;
+            writer.write("\">".to_string())?;
+            writer.write(img)?;
+            writer.write("</a>".to_string())?;
         }
 
         Ok(())
@@ -524,7 +879,11 @@ impl ToHtml for MonospaceText {
 impl ToHtml for Url {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
         writer.write("<a href=\"".to_string())?;
-        writer.write(self.url.clone())?;
+        if let Some(anchor) = self.url.strip_prefix('#') {
+            writer.write_attribute(writer.get_anchor_url(anchor))?;
+        } else {
+            writer.write_url(self.url.clone())?;
+        }
         writer.write("\">".to_string())?;
         if let Some(description) = self.description.clone() {
             for desc in description {
@@ -560,8 +919,8 @@ impl ToHtml for Placeholder {
 
 impl ToHtml for RefLink {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
-        writer.write("<a href=\"#".to_string())?;
-        writer.write_escaped(self.reference.clone())?;
+        writer.write("<a href=\"".to_string())?;
+        writer.write_escaped(writer.get_anchor_url(&self.reference))?;
         writer.write("\">".to_string())?;
         self.description.to_html(writer)?;
 
@@ -577,7 +936,11 @@ impl ToHtml for InlineMetadata {
                 .iter()
                 .for_each(|(k, v)| template.add_replacement(k, &v.to_string()));
 
-            writer.write(template.render())?;
+            if writer.is_safe_mode() {
+                writer.write_escaped(template.render())?;
+            } else {
+                writer.write(template.render())?;
+            }
         } else {
             for (k, v) in &self.data {
                 writer.write_escaped(format!("{}={},", k, v.to_string()))?;
@@ -598,9 +961,11 @@ impl ToHtml for Centered {
 
 impl ToHtml for Checkbox {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
-        writer.write("<input type=\"checkbox\" disabled ".to_string())?;
+        writer.write("<input type=\"checkbox\" disabled=\"disabled\"".to_string())?;
         if self.value {
-            writer.write("checked".to_string())?;
+            writer.write(" checked=\"checked\" aria-label=\"Completed task\"".to_string())?;
+        } else {
+            writer.write(" aria-label=\"Open task\"".to_string())?;
         }
 
         writer.write("/>".to_string())
@@ -620,9 +985,14 @@ impl ToHtml for Emoji {
 
 impl ToHtml for Colored {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
-        writer.write("<span class=\"colored\" style=\"color:".to_string())?;
-        writer.write(self.color.clone())?;
-        writer.write(";\">".to_string())?;
+        if writer.is_safe_mode() && !is_safe_color(&self.color) {
+            log::warn!("Removed unsafe color \"{}\"", self.color);
+            writer.write("<span class=\"colored\">".to_string())?;
+        } else {
+            writer.write("<span class=\"colored\" style=\"color:".to_string())?;
+            writer.write_attribute(self.color.clone())?;
+            writer.write(";\">".to_string())?;
+        }
         self.value.to_html(writer)?;
 
         writer.write("</span>".to_string())
@@ -631,10 +1001,14 @@ impl ToHtml for Colored {
 
 impl ToHtml for BibReference {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
-        writer.write("<sup><a href=\"#".to_string())?;
-        writer.write_attribute(self.key.clone())?;
+        writer.write("<sup><a href=\"".to_string())?;
+        writer.write_attribute(writer.get_anchor_url(&self.key))?;
         writer.write("\">".to_string())?;
-        writer.write(self.get_formatted())?;
+        if writer.is_safe_mode() {
+            writer.write_escaped(self.get_formatted())?;
+        } else {
+            writer.write(self.get_formatted())?;
+        }
 
         writer.write("</a></sup>".to_string())
     }
@@ -664,6 +1038,12 @@ impl ToHtml for TemplateVariable {
 
 impl ToHtml for CharacterCode {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
+        if writer.is_xhtml() {
+            // named entities aren't defined in xhtml
+            let code = format!("&{};", self.code);
+            let decoded = htmlescape::decode_html(&code).unwrap_or(code);
+            return writer.write_escaped(decoded);
+        }
         writer.write("&".to_string())?;
         writer.write_escaped(self.code.clone())?;
 
@@ -686,8 +1066,10 @@ impl ToHtml for GlossaryReference {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
         if let Some(entry) = &self.entry {
             let entry = entry.lock();
-            writer.write("<a class=\"glossaryReference\" href=\"#".to_string())?;
-            writer.write_attribute(self.short.clone())?;
+            writer.write("<a class=\"glossaryReference\" href=\"".to_string())?;
+            writer.write_attribute(writer.get_anchor_url(&self.short))?;
+            writer.write("\" aria-label=\"".to_string())?;
+            writer.write_attribute(format!("{} ({})", entry.long, entry.short))?;
             writer.write("\">".to_string())?;
             match self.display {
                 GlossaryDisplay::Short => writer.write_escaped(entry.short.clone())?,
@@ -706,14 +1088,96 @@ impl ToHtml for Arrow {
     fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
         writer.write("<span class=\"arrow\">".to_string())?;
         match self {
-            Arrow::RightArrow => writer.write("&xrarr;".to_string()),
-            Arrow::LeftArrow => writer.write("&xlarr;".to_string()),
-            Arrow::LeftRightArrow => writer.write("&xharr;".to_string()),
-            Arrow::BigRightArrow => writer.write("&xrArr;".to_string()),
-            Arrow::BigLeftArrow => writer.write("&xlArr;".to_string()),
-            Arrow::BigLeftRightArrow => writer.write("&xhArr;".to_string()),
+            Arrow::RightArrow => writer.write("&#x27F6;".to_string()),
+            Arrow::LeftArrow => writer.write("&#x27F5;".to_string()),
+            Arrow::LeftRightArrow => writer.write("&#x27F7;".to_string()),
+            Arrow::BigRightArrow => writer.write("&#x27F9;".to_string()),
+            Arrow::BigLeftArrow => writer.write("&#x27F8;".to_string()),
+            Arrow::BigLeftRightArrow => writer.write("&#x27FA;".to_string()),
         }?;
 
         writer.write("</span>".to_string())
     }
 }
+
+/// Returns if the line only contains content that is allowed inside of a p element
+fn is_phrasing_line(line: &Line) -> bool {
+    match line {
+        Line::Text(text) => text.subtext.iter().all(is_phrasing_inline),
+        Line::RefLink(_) | Line::BibEntry(_) => true,
+        _ => false,
+    }
+}
+
+fn is_phrasing_inline(inline: &Inline) -> bool {
+    match inline {
+        Inline::Image(image) => image.url.description.is_none(),
+        Inline::Anchor(_) => false,
+        Inline::Bold(BoldText { value })
+        | Inline::Italic(ItalicText { value })
+        | Inline::Underlined(UnderlinedText { value })
+        | Inline::Striked(StrikedText { value })
+        | Inline::Superscript(SuperscriptText { value }) => value.iter().all(is_phrasing_inline),
+        Inline::Url(url) => url
+            .description
+            .as_ref()
+            .map(|d| d.iter().all(is_phrasing_inline))
+            .unwrap_or(true),
+        Inline::Colored(colored) => is_phrasing_inline(&colored.value),
+        Inline::Placeholder(placeholder) => placeholder
+            .read()
+            .unwrap()
+            .value
+            .as_ref()
+            .map(is_phrasing_element)
+            .unwrap_or(true),
+        Inline::TemplateVar(var) => var
+            .read()
+            .unwrap()
+            .value
+            .as_ref()
+            .map(is_phrasing_element)
+            .unwrap_or(true),
+        _ => true,
+    }
+}
+
+fn is_phrasing_element(element: &Element) -> bool {
+    match element {
+        Element::Inline(inline) => is_phrasing_inline(inline),
+        Element::Line(line) => is_phrasing_line(line),
+        Element::Block(_) => false,
+    }
+}
+
+/// Splits highlighted html into lines. Spans that continue on the next line
+/// are closed at the end of the line and opened again at the start of the next one.
+fn split_highlighted_lines(html: &str) -> Vec<String> {
+    let mut open_spans: Vec<&str> = Vec::new();
+    let mut lines = Vec::new();
+
+    for line in html.trim_end_matches('\n').split('\n') {
+        let mut result = open_spans.concat();
+        let mut rest = line;
+
+        // the text is escaped so every angle bracket belongs to a tag
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..]
+                .find('>')
+                .map(|e| start + e + 1)
+                .unwrap_or_else(|| rest.len());
+            let tag = &rest[start..end];
+            if tag.starts_with("</") {
+                open_spans.pop();
+            } else {
+                open_spans.push(tag);
+            }
+            rest = &rest[end..];
+        }
+        result.push_str(line);
+        result.push_str(&"</span>".repeat(open_spans.len()));
+        lines.push(result);
+    }
+
+    lines
+}
@@ -0,0 +1,216 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::Document;
+use crate::format::json::result::{JsonError, JsonResult};
+use crate::format::{RenderResult, Renderer};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub mod result;
+
+/// The version of the json representation of documents.
+/// It is increased whenever the structure of the element tree or the settings changes
+/// in a way that older documents can't be read anymore. Settings that are missing
+/// in older documents are read with their default values.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonDocumentRef<'a> {
+    schema_version: u32,
+    document: &'a Document,
+}
+
+#[derive(Deserialize)]
+struct JsonDocument {
+    document: Document,
+}
+
+/// Writes the element tree of the document as json
+pub fn render_to_json(document: Document, mut writer: Box<dyn Write>) -> JsonResult<()> {
+    serde_json::to_writer_pretty(
+        &mut writer,
+        &JsonDocumentRef {
+            schema_version: SCHEMA_VERSION,
+            document: &document,
+        },
+    )?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Renders documents to the json representation of the element tree
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn extension(&self) -> &str {
+        "json"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        render_to_json(document, writer)?;
+
+        Ok(())
+    }
+}
+
+/// Reads a document that has been written with [render_to_json]
+/// and prepares its images and stylesheets for rendering
+pub fn read_json_document<R: Read>(reader: R) -> JsonResult<Document> {
+    let document = deserialize_document(reader)?;
+    document.process_media();
+
+    Ok(document)
+}
+
+/// Reads an untrusted json document in safe mode.
+/// The given path replaces the documents own path and determines the project root.
+pub fn read_untrusted_json_document<R: Read>(reader: R, path: &Path) -> JsonResult<Document> {
+    let mut document = deserialize_document(reader)?;
+    document.path = Some(path.to_string_lossy().to_string());
+    document.config.lock().safe_mode = true;
+    document.process_media();
+
+    Ok(document)
+}
+
+fn deserialize_document<R: Read>(reader: R) -> JsonResult<Document> {
+    let value: serde_json::Value = serde_json::from_reader(reader)?;
+    let version = value
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as u32;
+
+    if version != SCHEMA_VERSION {
+        return Err(JsonError::UnsupportedVersion(version));
+    }
+    let json_document: JsonDocument = serde_json::from_value(value)?;
+
+    Ok(json_document.document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::html::shared_buffer::SharedBuffer;
+    use crate::parser::{Parser, ParserOptions};
+    use std::fs;
+    use std::path::PathBuf;
+
+    const DOCUMENT: &str = "# Introduction
+
+Some *formatted* **text** with a [link](https://example.com) and $$ a^2 $$.
+
+- first item
+- second item
+
+```rust[caption=\"The entry point\" id=main line_numbers]
+fn main() {}
+```
+
+| name | value |
+|------|-------|
+| a    | 1     |
+";
+
+    /// Parses the document from a file in the temporary directory
+    fn parse_document(name: &str) -> (Document, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("snekdown-json-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("document.md");
+        fs::write(&path, DOCUMENT).unwrap();
+        let document = Parser::with_defaults(ParserOptions::default().add_path(path)).parse();
+
+        (document, dir)
+    }
+
+    fn to_json(document: Document) -> String {
+        let buffer = SharedBuffer::default();
+        render_to_json(document, Box::new(buffer.clone())).unwrap();
+
+        buffer.take_string()
+    }
+
+    #[test]
+    fn it_reads_written_documents() {
+        let (document, dir) = parse_document("round-trip");
+        let json = to_json(document);
+        let document = read_json_document(json.as_bytes()).unwrap();
+
+        // metadata is stored in hash maps so the order of the keys can differ
+        let value = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+        assert_eq!(value(&to_json(document)), value(&json));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn it_reads_documents_with_missing_settings() {
+        let (document, dir) = parse_document("missing-settings");
+        let mut value: serde_json::Value = serde_json::from_str(&to_json(document)).unwrap();
+        let config = value["document"]["config"].as_object_mut().unwrap();
+        config.remove("html");
+        config["style"]
+            .as_object_mut()
+            .unwrap()
+            .remove("code_themes");
+        config["pdf"].as_object_mut().unwrap().remove("paper_size");
+        config["images"] = serde_json::json!({});
+
+        let document = read_json_document(value.to_string().as_bytes()).unwrap();
+        let config = document.config.lock();
+
+        assert!(config.style.code_themes.is_empty());
+        assert!(config.images.responsive_widths.is_empty());
+        assert!(config.html.layout.is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn it_rejects_unsupported_versions() {
+        let json = format!(
+            "{{\"schema_version\": {}, \"document\": {{}}}}",
+            SCHEMA_VERSION + 1
+        );
+
+        match read_json_document(json.as_bytes()) {
+            Err(JsonError::UnsupportedVersion(version)) => {
+                assert_eq!(version, SCHEMA_VERSION + 1)
+            }
+            _ => panic!("documents with an unsupported version must not be read"),
+        }
+        assert!(matches!(
+            read_json_document("{\"document\": {}}".as_bytes()),
+            Err(JsonError::UnsupportedVersion(0))
+        ));
+    }
+
+    #[test]
+    fn it_reads_untrusted_documents_in_safe_mode() {
+        let (document, dir) = parse_document("untrusted");
+        let mut value: serde_json::Value = serde_json::from_str(&to_json(document)).unwrap();
+        value["document"]["path"] = serde_json::json!("/etc/document.md");
+        value["document"]["config"]["safe_mode"] = serde_json::json!(false);
+        let path = dir.join("untrusted.json");
+
+        let document = read_untrusted_json_document(value.to_string().as_bytes(), &path).unwrap();
+
+        assert!(document.config.lock().safe_mode);
+        assert_eq!(document.path, Some(path.to_string_lossy().to_string()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
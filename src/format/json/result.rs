@@ -0,0 +1,44 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+
+pub type JsonResult<T> = Result<T, JsonError>;
+
+#[derive(Debug)]
+pub enum JsonError {
+    IoError(io::Error),
+    SerdeError(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::IoError(e) => write!(f, "IO Error: {}", e),
+            JsonError::SerdeError(e) => write!(f, "Serde Error: {}", e),
+            JsonError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported schema version: {}", v)
+            }
+        }
+    }
+}
+
+impl Error for JsonError {}
+
+impl From<io::Error> for JsonError {
+    fn from(other: io::Error) -> Self {
+        Self::IoError(other)
+    }
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(other: serde_json::Error) -> Self {
+        Self::SerdeError(other)
+    }
+}
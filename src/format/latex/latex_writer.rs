@@ -0,0 +1,128 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::format::html::asset_store::{Asset, AssetStore};
+use crate::references::glossary::GlossaryManager;
+use mime::Mime;
+use parking_lot::Mutex;
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+
+pub struct LatexWriter {
+    inner: Box<dyn Write>,
+    images: AssetStore,
+    glossary: Option<Arc<Mutex<GlossaryManager>>>,
+    pending_break: bool,
+    line_has_content: bool,
+}
+
+impl LatexWriter {
+    /// Creates a new writer that references images relative to the given directory
+    pub fn new(inner: Box<dyn Write>, image_directory: &str) -> Self {
+        Self {
+            inner,
+            images: AssetStore::new(image_directory),
+            glossary: None,
+            pending_break: false,
+            line_has_content: false,
+        }
+    }
+
+    /// Writes raw latex
+    pub fn write(&mut self, latex: String) -> io::Result<()> {
+        if self.pending_break {
+            self.pending_break = false;
+            // a line break without any content before it is an error in latex
+            if self.line_has_content {
+                self.inner.write_all(b"\\\\\n")?;
+                self.line_has_content = false;
+            }
+        }
+        if let Some(last_line) = latex.rsplit('\n').next() {
+            if latex.contains('\n') {
+                self.line_has_content = !last_line.trim().is_empty();
+            } else if !last_line.trim().is_empty() {
+                self.line_has_content = true;
+            }
+        }
+
+        self.inner.write_all(latex.as_bytes())
+    }
+
+    /// Writes text with all latex special characters escaped
+    pub fn write_escaped(&mut self, text: String) -> io::Result<()> {
+        self.write(escape_latex(&text))
+    }
+
+    /// Adds a line break that is only written if more text follows in the same block
+    pub fn line_break(&mut self) {
+        self.pending_break = true;
+    }
+
+    /// Discards a line break at the end of a block
+    pub fn discard_break(&mut self) {
+        self.pending_break = false;
+    }
+
+    /// Flushes the writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Adds an image that is written alongside the latex file and returns its path
+    pub fn add_image(&mut self, data: Vec<u8>, mime: Mime) -> String {
+        self.images.add(data, mime)
+    }
+
+    /// Returns all images that have been added
+    pub fn images(&self) -> &[Asset] {
+        self.images.assets()
+    }
+
+    /// Sets the glossary that is rendered for the glossary placeholder
+    pub fn set_glossary(&mut self, glossary: Arc<Mutex<GlossaryManager>>) {
+        self.glossary = Some(glossary);
+    }
+
+    /// Returns the glossary of the document
+    pub fn get_glossary(&self) -> Option<Arc<Mutex<GlossaryManager>>> {
+        self.glossary.clone()
+    }
+}
+
+/// Escapes all characters that have a special meaning in latex
+pub fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Returns a label that can safely be used in \label and \ref
+pub fn escape_label(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == ':' || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
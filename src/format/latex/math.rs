@@ -0,0 +1,440 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::format::latex::latex_writer::escape_latex;
+use crate::utils::entities::decode_entities;
+use asciimath_rs::elements::special::Expression;
+use asciimath_rs::format::mathml::ToMathML;
+use std::collections::HashMap;
+
+/// Functions that have their own latex command
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "sec", "csc", "cot", "sinh", "cosh", "tanh", "coth", "arcsin", "arccos",
+    "arctan", "exp", "log", "ln", "det", "dim", "max", "min", "gcd",
+];
+
+/// Operators that take their limits as sub- and superscripts
+const LARGE_OPERATORS: &[&str] = &["\\sum", "\\prod", "\\int", "\\oint"];
+
+/// A node of the mathml tree
+enum MathNode {
+    Element {
+        name: String,
+        attributes: HashMap<String, String>,
+        children: Vec<MathNode>,
+    },
+    Text(String),
+}
+
+/// Converts a math expression into latex math mode source.
+/// The expression is converted via its MathML representation because
+/// the inner structure of the expression can't be accessed.
+pub fn expression_to_latex(expression: &Expression) -> String {
+    let nodes = parse_mathml(&expression.to_mathml());
+
+    nodes_to_latex(&nodes).trim().to_string()
+}
+
+/// Parses the generated mathml into a tree. Unclosed or mismatched
+/// tags are closed implicitly.
+fn parse_mathml(mathml: &str) -> Vec<MathNode> {
+    let mut stack: Vec<(String, HashMap<String, String>, Vec<MathNode>)> =
+        vec![(String::new(), HashMap::new(), Vec::new())];
+    let mut rest = mathml;
+
+    while !rest.is_empty() {
+        if let Some(tag_start) = rest.strip_prefix('<') {
+            let end = tag_start.find('>').unwrap_or(tag_start.len());
+            let tag = &tag_start[..end];
+            rest = tag_start.get(end + 1..).unwrap_or("");
+
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = name.trim();
+                let depth = stack.iter().rposition(|(n, _, _)| n == name);
+                let depth = depth.unwrap_or_else(|| stack.len() - 1).max(1);
+                while stack.len() > depth {
+                    close_element(&mut stack);
+                }
+            } else {
+                let self_closing = tag.ends_with('/');
+                let tag = tag.trim_end_matches('/');
+                let mut parts = tag.splitn(2, char::is_whitespace);
+                let name = parts.next().unwrap_or("").to_string();
+                let attributes = parse_attributes(parts.next().unwrap_or(""));
+                stack.push((name, attributes, Vec::new()));
+                if self_closing {
+                    close_element(&mut stack);
+                }
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = decode_entities(&rest[..end]);
+            stack.last_mut().unwrap().2.push(MathNode::Text(text));
+            rest = &rest[end..];
+        }
+    }
+    while stack.len() > 1 {
+        close_element(&mut stack);
+    }
+
+    stack.pop().map(|(_, _, c)| c).unwrap_or_default()
+}
+
+fn close_element(stack: &mut Vec<(String, HashMap<String, String>, Vec<MathNode>)>) {
+    if let Some((name, attributes, children)) = stack.pop() {
+        if let Some(parent) = stack.last_mut() {
+            parent.2.push(MathNode::Element {
+                name,
+                attributes,
+                children,
+            })
+        }
+    }
+}
+
+fn parse_attributes(source: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+
+    for attribute in source.split_whitespace() {
+        if let Some(index) = attribute.find('=') {
+            let value = attribute[index + 1..].trim_matches(|c| c == '\'' || c == '"');
+            attributes.insert(attribute[..index].to_string(), value.to_string());
+        }
+    }
+
+    attributes
+}
+
+fn nodes_to_latex(nodes: &[MathNode]) -> String {
+    if let (Some(first), Some(last)) = (nodes.first(), nodes.last()) {
+        if nodes.len() > 1 {
+            if let (Some(left), Some(right)) = (get_delimiter(first), get_delimiter(last)) {
+                return format!(
+                    "\\left{} {} \\right{}",
+                    left,
+                    nodes[1..nodes.len() - 1]
+                        .iter()
+                        .map(node_to_latex)
+                        .collect::<String>(),
+                    right
+                );
+            }
+        }
+    }
+
+    nodes.iter().map(node_to_latex).collect()
+}
+
+/// Returns the delimiter command if the node is an operator that can be used as a delimiter
+fn get_delimiter(node: &MathNode) -> Option<&'static str> {
+    match node {
+        MathNode::Element { name, children, .. } if name == "mo" => {
+            let delimiter = match get_text(children).as_str() {
+                "(" => "(",
+                ")" => ")",
+                "[" => "[",
+                "]" => "]",
+                "{" => "\\{",
+                "}" => "\\}",
+                "\u{27E8}" => "\\langle",
+                "\u{27E9}" => "\\rangle",
+                "|" => "|",
+                "||" => "\\|",
+                "\u{230A}" => "\\lfloor",
+                "\u{230B}" => "\\rfloor",
+                "\u{2308}" => "\\lceil",
+                "\u{2309}" => "\\rceil",
+                _ => return None,
+            };
+            Some(delimiter)
+        }
+        _ => None,
+    }
+}
+
+fn node_to_latex(node: &MathNode) -> String {
+    let (name, attributes, children) = match node {
+        MathNode::Text(text) => return text_to_latex(text),
+        MathNode::Element {
+            name,
+            attributes,
+            children,
+        } => (name.as_str(), attributes, children),
+    };
+    let child = |index: usize| children.get(index).map(node_to_latex).unwrap_or_default();
+
+    match name {
+        "mi" | "mo" => symbol_to_latex(&get_text(children)),
+        "mn" => escape_math(&get_text(children)),
+        "mtext" => {
+            let text = format!("\\text{{{}}}", escape_latex(&get_text(children)));
+            match attributes.get("mathvariant").map(|v| v.as_str()) {
+                Some("bold") => format!("\\mathbf{{{}}}", text),
+                Some("double-struck") => format!("\\mathbb{{{}}}", get_text(children)),
+                Some("italic") => format!("\\mathit{{{}}}", text),
+                Some("script") => format!("\\mathtt{{{}}}", text),
+                Some("bold-fraktur") => format!("\\mathfrak{{{}}}", get_text(children)),
+                Some("sans-serif") => format!("\\mathsf{{{}}}", text),
+                _ => text,
+            }
+        }
+        "mfrac" => format!("\\frac{{{}}}{{{}}}", child(0), child(1)),
+        "msup" => format!("{{{}}}^{{{}}}", child(0), child(1)),
+        "msub" => format!("{{{}}}_{{{}}}", child(0), child(1)),
+        "msqrt" => format!("\\sqrt{{{}}}", nodes_to_latex(children)),
+        "mroot" => format!("\\sqrt[{}]{{{}}}", child(1), child(0)),
+        "munderover" => {
+            let base = child(0);
+            if LARGE_OPERATORS.contains(&base.trim()) {
+                format!("{}_{{{}}}^{{{}}}", base, child(1), child(2))
+            } else {
+                format!(
+                    "\\underset{{{}}}{{\\overset{{{}}}{{{}}}}}",
+                    child(1),
+                    child(2),
+                    base
+                )
+            }
+        }
+        "munder" => {
+            let base = child(0);
+            if attributes.contains_key("accentunder") {
+                match get_node_text(children.get(1)).as_str() {
+                    "\u{2013}" => format!("\\underline{{{}}}", base),
+                    "\u{23DF}" => format!("\\underbrace{{{}}}", base),
+                    _ => format!("\\underset{{{}}}{{{}}}", child(1), base),
+                }
+            } else if LARGE_OPERATORS.contains(&base.trim()) {
+                format!("{}_{{{}}}", base, child(1))
+            } else {
+                format!("\\underset{{{}}}{{{}}}", child(1), base)
+            }
+        }
+        "mover" => {
+            let base = child(0);
+            if attributes.contains_key("accentover") {
+                match get_node_text(children.get(1)).as_str() {
+                    "\u{2C6}" => format!("\\hat{{{}}}", base),
+                    "\u{AF}" => format!("\\overline{{{}}}", base),
+                    "\u{2192}" => format!("\\vec{{{}}}", base),
+                    "." => format!("\\dot{{{}}}", base),
+                    ".." => format!("\\ddot{{{}}}", base),
+                    "\u{23DE}" => format!("\\overbrace{{{}}}", base),
+                    _ => format!("\\overset{{{}}}{{{}}}", child(1), base),
+                }
+            } else if LARGE_OPERATORS.contains(&base.trim()) {
+                format!("{}^{{{}}}", base, child(1))
+            } else {
+                format!("\\overset{{{}}}{{{}}}", child(1), base)
+            }
+        }
+        "mtable" => format!(
+            "\\begin{{matrix}}{}\\end{{matrix}}",
+            children
+                .iter()
+                .map(node_to_latex)
+                .collect::<Vec<String>>()
+                .join(" \\\\ ")
+        ),
+        "mtr" => children
+            .iter()
+            .map(node_to_latex)
+            .collect::<Vec<String>>()
+            .join(" & "),
+        "mspace" => " \\\\ ".to_string(),
+        "mstyle" => match attributes.get("mathcolor") {
+            Some(color) => format!(
+                "\\textcolor{{{}}}{{{}}}",
+                color.replace(|c: char| !c.is_alphanumeric(), ""),
+                nodes_to_latex(children)
+            ),
+            None => nodes_to_latex(children),
+        },
+        "mrow" => format!("{{{}}}", nodes_to_latex(children)),
+        _ => nodes_to_latex(children),
+    }
+}
+
+fn get_text(nodes: &[MathNode]) -> String {
+    nodes
+        .iter()
+        .map(|n| match n {
+            MathNode::Text(t) => t.clone(),
+            MathNode::Element { children, .. } => get_text(children),
+        })
+        .collect()
+}
+
+fn get_node_text(node: Option<&MathNode>) -> String {
+    node.map(|n| get_text(std::slice::from_ref(n)))
+        .unwrap_or_default()
+}
+
+fn text_to_latex(text: &str) -> String {
+    if text.trim().is_empty() {
+        String::new()
+    } else {
+        symbol_to_latex(text)
+    }
+}
+
+/// Converts the content of an identifier or operator into latex
+fn symbol_to_latex(text: &str) -> String {
+    let text = text.trim();
+
+    if FUNCTIONS.contains(&text) {
+        return format!("\\{} ", text);
+    }
+    match text {
+        "" => return String::new(),
+        "exists;" => return "\\exists ".to_string(),
+        "and" | "or" | "if" => return format!("\\text{{ {} }}", text),
+        _ => {}
+    }
+    if text.chars().count() > 1 && text.chars().all(|c| c.is_ascii_alphabetic()) {
+        return format!("\\operatorname{{{}}}", text);
+    }
+
+    text.chars().map(char_to_latex).collect()
+}
+
+/// Escapes characters that have a special meaning in math mode
+fn escape_math(text: &str) -> String {
+    text.chars().map(char_to_latex).collect()
+}
+
+fn char_to_latex(c: char) -> String {
+    let command = match c {
+        '#' | '$' | '%' | '&' | '_' | '{' | '}' => return format!("\\{}", c),
+        '\\' => "\\backslash",
+        '~' => "\\sim",
+        '^' => "\\hat{}",
+        '\u{3B1}' => "\\alpha",
+        '\u{3B2}' => "\\beta",
+        '\u{3B3}' => "\\gamma",
+        '\u{393}' => "\\Gamma",
+        '\u{3B4}' => "\\delta",
+        '\u{394}' => "\\Delta",
+        '\u{3B5}' => "\\epsilon",
+        '\u{3B6}' => "\\zeta",
+        '\u{3B7}' => "\\eta",
+        '\u{3B8}' => "\\theta",
+        '\u{398}' => "\\Theta",
+        '\u{3B9}' => "\\iota",
+        '\u{3BA}' => "\\kappa",
+        '\u{3BB}' => "\\lambda",
+        '\u{39B}' => "\\Lambda",
+        '\u{3BC}' => "\\mu",
+        '\u{3BD}' => "\\nu",
+        '\u{3BE}' => "\\xi",
+        '\u{39E}' => "\\Xi",
+        '\u{3C0}' => "\\pi",
+        '\u{3A0}' => "\\Pi",
+        '\u{3C1}' => "\\rho",
+        '\u{3C3}' => "\\sigma",
+        '\u{3A3}' => "\\Sigma",
+        '\u{3C4}' => "\\tau",
+        '\u{3C5}' => "\\upsilon",
+        '\u{3C6}' => "\\phi",
+        '\u{3A6}' => "\\Phi",
+        '\u{3C7}' => "\\chi",
+        '\u{3C8}' => "\\psi",
+        '\u{3A8}' => "\\Psi",
+        '\u{3C9}' => "\\omega",
+        '\u{3A9}' => "\\Omega",
+        '\u{2211}' => "\\sum",
+        '\u{220F}' => "\\prod",
+        '\u{222B}' => "\\int",
+        '\u{222E}' => "\\oint",
+        '\u{2260}' => "\\neq",
+        '\u{2264}' => "\\leq",
+        '\u{2265}' => "\\geq",
+        '\u{227A}' => "\\prec",
+        '\u{227B}' => "\\succ",
+        '\u{227C}' => "\\preceq",
+        '\u{227D}' => "\\succeq",
+        '\u{2208}' => "\\in",
+        '\u{2209}' => "\\notin",
+        '\u{2282}' => "\\subset",
+        '\u{2283}' => "\\supset",
+        '\u{2286}' => "\\subseteq",
+        '\u{2287}' => "\\supseteq",
+        '\u{2261}' => "\\equiv",
+        '\u{2245}' => "\\cong",
+        '\u{2248}' => "\\approx",
+        '\u{221D}' => "\\propto",
+        '\u{AC}' => "\\neg",
+        '\u{21D2}' => "\\Rightarrow",
+        '\u{21D4}' => "\\Leftrightarrow",
+        '\u{2200}' => "\\forall",
+        '\u{22A5}' => "\\bot",
+        '\u{22A4}' => "\\top",
+        '\u{22A2}' => "\\vdash",
+        '\u{22A8}' => "\\models",
+        '\u{2191}' => "\\uparrow",
+        '\u{2193}' => "\\downarrow",
+        '\u{2192}' => "\\rightarrow",
+        '\u{21A3}' => "\\rightarrowtail",
+        '\u{21A0}' | '\u{2916}' => "\\twoheadrightarrow",
+        '\u{21A6}' => "\\mapsto",
+        '\u{2190}' => "\\leftarrow",
+        '\u{27F7}' | '\u{2B04}' => "\\Leftrightarrow",
+        '\u{21E8}' => "\\Rightarrow",
+        '\u{21E6}' => "\\Leftarrow",
+        '\u{2202}' => "\\partial",
+        '\u{2207}' => "\\nabla",
+        '\u{B1}' => "\\pm",
+        '\u{2205}' => "\\emptyset",
+        '\u{221E}' => "\\infty",
+        '\u{2135}' => "\\aleph",
+        '\u{2234}' => "\\therefore",
+        '\u{2235}' => "\\because",
+        '\u{2026}' => "\\ldots",
+        '\u{B7}' | '\u{22C5}' => "\\cdot",
+        '\u{FE19}' => "\\vdots",
+        '\u{22F1}' => "\\ddots",
+        '\u{2220}' => "\\angle",
+        '\u{2322}' => "\\frown",
+        '\u{25B3}' => "\\triangle",
+        '\u{22C4}' => "\\diamond",
+        '\u{25A1}' => "\\square",
+        '\u{230A}' => "\\lfloor",
+        '\u{230B}' => "\\rfloor",
+        '\u{2308}' => "\\lceil",
+        '\u{2309}' => "\\rceil",
+        '\u{2102}' => "\\mathbb{C}",
+        '\u{2115}' => "\\mathbb{N}",
+        '\u{211A}' => "\\mathbb{Q}",
+        '\u{211D}' => "\\mathbb{R}",
+        '\u{2124}' => "\\mathbb{Z}",
+        '\u{2212}' => "-",
+        '\u{2217}' => "\\ast",
+        '\u{22C6}' => "\\star",
+        '\u{2216}' => "\\setminus",
+        '\u{D7}' => "\\times",
+        '\u{F7}' => "\\div",
+        '\u{22C9}' => "\\ltimes",
+        '\u{22CA}' => "\\rtimes",
+        '\u{22C8}' => "\\bowtie",
+        '\u{2218}' => "\\circ",
+        '\u{2295}' => "\\oplus",
+        '\u{2297}' => "\\otimes",
+        '\u{2299}' => "\\odot",
+        '\u{2227}' => "\\wedge",
+        '\u{22C0}' => "\\bigwedge",
+        '\u{2228}' => "\\vee",
+        '\u{22C1}' => "\\bigvee",
+        '\u{2229}' => "\\cap",
+        '\u{22C2}' => "\\bigcap",
+        '\u{222A}' => "\\cup",
+        '\u{22C3}' => "\\bigcup",
+        '\u{27E8}' => "\\langle",
+        '\u{27E9}' => "\\rangle",
+        _ => return c.to_string(),
+    };
+
+    format!("{} ", command)
+}
@@ -0,0 +1,76 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::Document;
+use crate::format::latex::latex_writer::LatexWriter;
+use crate::format::latex::to_latex::ToLatex;
+use crate::format::{RenderResult, Renderer};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub mod latex_writer;
+pub mod math;
+pub mod to_latex;
+
+/// The directory next to the latex file the images are written to
+const IMAGE_DIR: &str = "images";
+
+/// Renders the document to latex. The images of the document are
+/// written to a directory inside the given output directory.
+pub fn render_to_latex(
+    document: Document,
+    inner: Box<dyn Write>,
+    output_dir: Option<&Path>,
+) -> io::Result<()> {
+    let mut writer = LatexWriter::new(inner, IMAGE_DIR);
+    document.to_latex(&mut writer)?;
+    writer.flush()?;
+
+    if writer.images().is_empty() {
+        return Ok(());
+    }
+    let output_dir = match output_dir {
+        Some(dir) => dir.join(IMAGE_DIR),
+        None => {
+            log::warn!("Images are not written when rendering latex to stdout");
+            return Ok(());
+        }
+    };
+    log::info!("Writing {} images...", writer.images().len());
+    fs::create_dir_all(&output_dir)?;
+
+    for image in writer.images() {
+        fs::write(output_dir.join(&image.name), &image.data)?;
+    }
+
+    Ok(())
+}
+
+/// Renders documents to latex sources
+pub struct LatexRenderer;
+
+impl Renderer for LatexRenderer {
+    fn name(&self) -> &str {
+        "latex"
+    }
+
+    fn extension(&self) -> &str {
+        "tex"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        writer: Box<dyn Write>,
+        output: Option<&Path>,
+    ) -> RenderResult<()> {
+        render_to_latex(document, writer, output.and_then(|o| o.parent()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,801 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::*;
+use crate::format::latex::latex_writer::{escape_label, escape_latex, LatexWriter};
+use crate::format::latex::math::expression_to_latex;
+use crate::format::PlaceholderTemplate;
+use crate::references::glossary::{GlossaryDisplay, GlossaryReference};
+use crate::references::templates::{Template, TemplateVariable};
+use crate::utils::entities::decode_entity;
+use std::io;
+
+/// The sequence that ends a verbatim environment
+const VERBATIM_END: &str = "\\end{verbatim}";
+
+const PREAMBLE: &str = "\\documentclass{article}
+\\usepackage[utf8]{inputenc}
+\\usepackage[T1]{fontenc}
+\\usepackage{amsmath}
+\\usepackage{amssymb}
+\\usepackage{graphicx}
+\\usepackage[svgnames]{xcolor}
+\\usepackage[normalem]{ulem}
+\\usepackage{hyperref}
+% scales images down to the line width but never up
+\\makeatletter
+\\def\\maxwidth{\\ifdim\\Gin@nat@width>\\linewidth\\linewidth\\else\\Gin@nat@width\\fi}
+\\makeatother
+";
+
+/// Units that can be used for image sizes in both css and latex
+const LENGTH_UNITS: &[&str] = &["cm", "mm", "in", "pt", "px", "em", "ex"];
+
+pub trait ToLatex {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()>;
+}
+
+impl ToLatex for Element {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        match self {
+            Element::Block(block) => block.to_latex(writer),
+            Element::Inline(inline) => inline.to_latex(writer),
+            Element::Line(line) => line.to_latex(writer),
+        }
+    }
+}
+
+impl ToLatex for Line {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        match self {
+            Line::Text(text) => text.to_latex(writer),
+            Line::Ruler(ruler) => ruler.to_latex(writer),
+            Line::RefLink(anchor) => anchor.to_latex(writer),
+            Line::Centered(centered) => centered.to_latex(writer),
+            Line::Anchor(a) => a.to_latex(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToLatex for Inline {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        match self {
+            Inline::Url(url) => url.to_latex(writer),
+            Inline::Monospace(mono) => mono.to_latex(writer),
+            Inline::Striked(striked) => striked.to_latex(writer),
+            Inline::Plain(plain) => plain.to_latex(writer),
+            Inline::Italic(italic) => italic.to_latex(writer),
+            Inline::Underlined(under) => under.to_latex(writer),
+            Inline::Bold(bold) => bold.to_latex(writer),
+            Inline::Image(img) => img.to_latex(writer),
+            Inline::Placeholder(placeholder) => placeholder.read().unwrap().to_latex(writer),
+            Inline::Superscript(superscript) => superscript.to_latex(writer),
+            Inline::Checkbox(checkbox) => checkbox.to_latex(writer),
+            Inline::Emoji(emoji) => emoji.to_latex(writer),
+            Inline::Colored(colored) => colored.to_latex(writer),
+            Inline::BibReference(bibref) => bibref.read().unwrap().to_latex(writer),
+            Inline::TemplateVar(var) => var.read().unwrap().to_latex(writer),
+            Inline::Math(m) => m.to_latex(writer),
+            Inline::LineBreak => {
+                writer.line_break();
+                Ok(())
+            }
+            Inline::CharacterCode(code) => code.to_latex(writer),
+            Inline::GlossaryReference(gloss) => gloss.lock().to_latex(writer),
+            Inline::Arrow(a) => a.to_latex(writer),
+            Inline::Anchor(a) => a.to_latex(writer),
+        }
+    }
+}
+
+impl ToLatex for Block {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        match self {
+            Block::Paragraph(para) => para.to_latex(writer),
+            Block::List(list) => list.to_latex(writer),
+            Block::Table(table) => table.to_latex(writer),
+            Block::CodeBlock(code) => code.to_latex(writer),
+            Block::Quote(quote) => quote.to_latex(writer),
+            Block::Section(section) => section.to_latex(writer),
+            Block::Import(import) => import.to_latex(writer),
+            Block::Placeholder(placeholder) => placeholder.read().unwrap().to_latex(writer),
+            Block::MathBlock(m) => m.to_latex(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToLatex for MetadataValue {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        match self {
+            MetadataValue::String(string) => writer.write_escaped(string.clone()),
+            MetadataValue::Integer(num) => writer.write(num.to_string()),
+            MetadataValue::Placeholder(ph) => ph.read().unwrap().to_latex(writer),
+            MetadataValue::Bool(b) => writer.write(b.to_string()),
+            MetadataValue::Float(f) => writer.write(f.to_string()),
+            MetadataValue::Template(t) => t.to_latex(writer),
+        }
+    }
+}
+
+impl ToLatex for Document {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if !self.is_root {
+            for element in &self.elements {
+                element.to_latex(writer)?;
+            }
+            return Ok(());
+        }
+        let metadata = self.config.lock().metadata.clone();
+        writer.set_glossary(self.glossary.clone());
+        writer.write(PREAMBLE.to_string())?;
+
+        if let Some(title) = &metadata.title {
+            writer.write(format!("\\title{{{}}}\n", escape_latex(title)))?;
+        }
+        if let Some(author) = &metadata.author {
+            writer.write(format!("\\author{{{}}}\n", escape_latex(author)))?;
+        }
+        writer.write("\\hypersetup{".to_string())?;
+        if let Some(title) = &metadata.title {
+            writer.write(format!("pdftitle={{{}}},", escape_latex(title)))?;
+        }
+        if let Some(author) = &metadata.author {
+            writer.write(format!("pdfauthor={{{}}},", escape_latex(author)))?;
+        }
+        if let Some(description) = &metadata.description {
+            writer.write(format!("pdfsubject={{{}}},", escape_latex(description)))?;
+        }
+        if !metadata.keywords.is_empty() {
+            writer.write(format!(
+                "pdfkeywords={{{}}},",
+                escape_latex(&metadata.keywords.join(", "))
+            ))?;
+        }
+        writer.write("colorlinks=true}\n\n\\begin{document}\n".to_string())?;
+
+        if metadata.title.is_some() {
+            writer.write("\\maketitle\n".to_string())?;
+        }
+        writer.write("\n".to_string())?;
+        for element in &self.elements {
+            element.to_latex(writer)?;
+        }
+
+        writer.write("\\end{document}\n".to_string())
+    }
+}
+
+impl ToLatex for Math {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write(format!("${}$", expression_to_latex(&self.expression)))
+    }
+}
+
+impl ToLatex for MathBlock {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write(format!(
+            "\\[ {} \\]\n\n",
+            expression_to_latex(&self.expression)
+        ))
+    }
+}
+
+impl ToLatex for Import {
+    fn to_latex(&self, _writer: &mut LatexWriter) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ToLatex for Section {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        let command = match self.header.size {
+            1 => "section",
+            2 => "subsection",
+            3 => "subsubsection",
+            4 => "paragraph",
+            _ => "subparagraph",
+        };
+        let star = if self.is_hidden_in_toc() { "*" } else { "" };
+        writer.write(format!("\\{}{}{{", command, star))?;
+        self.header.line.to_latex(writer)?;
+        writer.discard_break();
+        writer.write(format!(
+            "}}\\label{{{}}}\n\n",
+            escape_label(&self.header.anchor)
+        ))?;
+
+        for element in &self.elements {
+            element.to_latex(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToLatex for Paragraph {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if let Some(first) = self.elements.first() {
+            first.to_latex(writer)?;
+        }
+        if self.elements.len() > 1 {
+            for element in &self.elements[1..] {
+                writer.write(" ".to_string())?;
+                element.to_latex(writer)?;
+            }
+        }
+        writer.discard_break();
+
+        writer.write("\n\n".to_string())
+    }
+}
+
+impl ToLatex for List {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        write_list(writer, self.ordered, &self.items)?;
+
+        writer.write("\n".to_string())
+    }
+}
+
+/// Writes the items as an enumerate or itemize environment
+fn write_list(writer: &mut LatexWriter, ordered: bool, items: &[ListItem]) -> io::Result<()> {
+    let environment = if ordered { "enumerate" } else { "itemize" };
+    writer.write(format!("\\begin{{{}}}\n", environment))?;
+
+    for item in items {
+        item.to_latex(writer)?;
+    }
+
+    writer.write(format!("\\end{{{}}}\n", environment))
+}
+
+impl ToLatex for ListItem {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write("\\item ".to_string())?;
+        self.text.to_latex(writer)?;
+        writer.discard_break();
+        writer.write("\n".to_string())?;
+
+        if let Some(first) = self.children.first() {
+            write_list(writer, first.ordered, &self.children)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToLatex for Table {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        let columns = self
+            .rows
+            .iter()
+            .map(|r| r.cells.len())
+            .chain(std::iter::once(self.header.cells.len()))
+            .max()
+            .unwrap_or(0);
+        writer.write(format!(
+            "\\begin{{center}}\n\\begin{{tabular}}{{|{}}}\n\\hline\n",
+            "l|".repeat(columns)
+        ))?;
+
+        for (index, cell) in self.header.cells.iter().enumerate() {
+            if index > 0 {
+                writer.write(" & ".to_string())?;
+            }
+            writer.write("\\textbf{".to_string())?;
+            cell.text.to_latex(writer)?;
+            writer.discard_break();
+            writer.write("}".to_string())?;
+        }
+        writer.write(" \\\\\n\\hline\n".to_string())?;
+        for row in &self.rows {
+            row.to_latex(writer)?;
+        }
+
+        writer.write("\\end{tabular}\n\\end{center}\n\n".to_string())
+    }
+}
+
+impl ToLatex for Row {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        for (index, cell) in self.cells.iter().enumerate() {
+            if index > 0 {
+                writer.write(" & ".to_string())?;
+            }
+            cell.to_latex(writer)?;
+        }
+
+        writer.write(" \\\\\n\\hline\n".to_string())
+    }
+}
+
+impl ToLatex for Cell {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        self.text.to_latex(writer)?;
+        writer.discard_break();
+
+        Ok(())
+    }
+}
+
+impl ToLatex for CodeBlock {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if let Some(anchor) = self.get_anchor() {
+            writer.write(format!("\\label{{{}}}\n", escape_label(&anchor)))?;
+        }
+        if let Some(file_name) = self.get_file_name() {
+            writer.write(format!(
+                "\\noindent\\texttt{{{}}}\n",
+                escape_latex(&file_name)
+            ))?;
+        }
+        let code = self.get_numbered_lines().join("\n").trim_end().to_string();
+
+        if code.contains(VERBATIM_END) {
+            // the code would end the verbatim environment and is written as escaped text instead
+            writer.write("\\begin{flushleft}\\ttfamily\n".to_string())?;
+            let lines = code
+                .lines()
+                .map(|line| {
+                    format!(
+                        "\\mbox{{}}{}",
+                        escape_latex(line).replace('\t', "~~~~").replace(' ', "~")
+                    )
+                })
+                .collect::<Vec<String>>();
+            writer.write(lines.join("\\\\\n"))?;
+            writer.write("\n\\end{flushleft}\n\n".to_string())?;
+        } else {
+            writer.write("\\begin{verbatim}\n".to_string())?;
+            writer.write(code)?;
+            writer.write(format!("\n{}\n\n", VERBATIM_END))?;
+        }
+
+        if let Some(caption) = self.get_caption() {
+            writer.write(format!(
+                "\\begin{{center}}\n\\textit{{{}}}\n\\end{{center}}\n\n",
+                escape_latex(&caption)
+            ))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToLatex for Quote {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write("\\begin{quote}\n".to_string())?;
+        for line in &self.text {
+            line.to_latex(writer)?;
+            writer.line_break();
+        }
+        if let Some(meta) = &self.metadata {
+            writer.write("\\hfill\\textit{".to_string())?;
+            meta.to_latex(writer)?;
+            writer.write("}".to_string())?;
+        }
+        writer.discard_break();
+
+        writer.write("\n\\end{quote}\n\n".to_string())
+    }
+}
+
+impl ToLatex for Ruler {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.discard_break();
+        writer.write("\n\n\\noindent\\rule{\\linewidth}{0.4pt}\n\n".to_string())
+    }
+}
+
+impl ToLatex for TextLine {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        for text in &self.subtext {
+            text.to_latex(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToLatex for Image {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        let path = if let Some(content) = self.get_content() {
+            writer.add_image(content, self.get_mime_type())
+        } else {
+            log::warn!(
+                "The image {} is not available locally and is linked instead",
+                self.url.url
+            );
+            return self.url.to_latex(writer);
+        };
+        let mut options = Vec::new();
+
+        if let Some(meta) = &self.metadata {
+            if let Some(width) = meta.get_string("width").and_then(|w| get_length(&w)) {
+                options.push(format!("width={}", width));
+            }
+            if let Some(height) = meta.get_string("height").and_then(|h| get_length(&h)) {
+                options.push(format!("height={}", height));
+            }
+        }
+        if !options.iter().any(|o| o.starts_with("width=")) {
+            options.push("width=\\maxwidth".to_string());
+            options.push("keepaspectratio".to_string());
+        }
+        let graphic = format!("\\includegraphics[{}]{{{}}}", options.join(","), path);
+
+        if let Some(description) = &self.url.description {
+            writer.write("\\begin{figure}[h]\n\\centering\n".to_string())?;
+            writer.write(graphic)?;
+            writer.write("\n\\caption{".to_string())?;
+            for item in description {
+                item.to_latex(writer)?;
+            }
+            writer.discard_break();
+            writer.write("}\n\\end{figure}\n".to_string())
+        } else {
+            writer.write(graphic)
+        }
+    }
+}
+
+/// Converts a css length into a latex length
+fn get_length(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent = percent.trim().parse::<f64>().ok()?;
+        return Some(format!("{}\\linewidth", percent / 100.0));
+    }
+    LENGTH_UNITS
+        .iter()
+        .find(|u| {
+            value
+                .strip_suffix(*u)
+                .map(|n| n.trim().parse::<f64>().is_ok())
+                .unwrap_or(false)
+        })
+        .map(|_| value.to_string())
+}
+
+impl ToLatex for BoldText {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        write_command(writer, "textbf", &self.value)
+    }
+}
+
+impl ToLatex for UnderlinedText {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        write_command(writer, "uline", &self.value)
+    }
+}
+
+impl ToLatex for ItalicText {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        write_command(writer, "textit", &self.value)
+    }
+}
+
+impl ToLatex for StrikedText {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        write_command(writer, "sout", &self.value)
+    }
+}
+
+impl ToLatex for SuperscriptText {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        write_command(writer, "textsuperscript", &self.value)
+    }
+}
+
+/// Writes the inline elements as the argument of a latex command
+fn write_command(writer: &mut LatexWriter, command: &str, value: &[Inline]) -> io::Result<()> {
+    writer.write(format!("\\{}{{", command))?;
+    for element in value {
+        element.to_latex(writer)?;
+    }
+
+    writer.write("}".to_string())
+}
+
+impl ToLatex for MonospaceText {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write(format!("\\texttt{{{}}}", escape_latex(&self.value)))
+    }
+}
+
+impl ToLatex for Url {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        let url = escape_url(&self.url);
+
+        if let Some(anchor) = self.url.strip_prefix('#') {
+            writer.write(format!("\\hyperref[{}]{{", escape_label(anchor)))?;
+            match &self.description {
+                Some(description) => {
+                    for desc in description {
+                        desc.to_latex(writer)?;
+                    }
+                }
+                None => writer.write_escaped(anchor.to_string())?,
+            }
+            writer.write("}".to_string())
+        } else if let Some(description) = &self.description {
+            writer.write(format!("\\href{{{}}}{{", url))?;
+            for desc in description {
+                desc.to_latex(writer)?;
+            }
+            writer.write("}".to_string())
+        } else {
+            writer.write(format!("\\url{{{}}}", url))
+        }
+    }
+}
+
+/// Escapes an url for \url and \href. Characters that aren't allowed in urls are
+/// percent-encoded first, which doesn't change the url. Afterwards every character
+/// that is special to latex is escaped with a backslash the way hyperref expects it,
+/// including the `%` of existing percent-encodings.
+fn escape_url(url: &str) -> String {
+    let mut encoded = String::with_capacity(url.len());
+
+    for c in url.replace('\\', "/").chars() {
+        if c.is_whitespace() || "{}^|\"<>`".contains(c) {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+
+    encoded.replace('%', "\\%").replace('#', "\\#")
+}
+
+impl ToLatex for PlainText {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write_escaped(self.value.clone())
+    }
+}
+
+impl ToLatex for Placeholder {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        match (self.name.to_lowercase().as_str(), &self.value) {
+            ("bib", Some(Element::Block(block))) => {
+                if let Block::List(list) = &**block {
+                    return write_bibliography(writer, list);
+                }
+            }
+            ("gls", Some(_)) => return write_glossary(writer),
+            _ => {}
+        }
+        if let Some(value) = &self.value {
+            value.to_latex(writer)
+        } else {
+            log::debug!("Unknown placeholder [[{}]]", self.name.clone());
+            writer.write_escaped(format!("[[{}]]", self.name.clone()))
+        }
+    }
+}
+
+/// Writes the bibliography list as a thebibliography environment
+fn write_bibliography(writer: &mut LatexWriter, list: &List) -> io::Result<()> {
+    if list.items.is_empty() {
+        return Ok(());
+    }
+    writer.write(format!(
+        "\\begin{{thebibliography}}{{{}}}\n",
+        list.items.len()
+    ))?;
+
+    for item in &list.items {
+        match &item.text {
+            Line::Anchor(anchor) => {
+                writer.write(format!("\\bibitem{{{}}} ", escape_label(&anchor.key)))?;
+                anchor.inner.to_latex(writer)?;
+            }
+            line => {
+                writer.write("\\bibitem{} ".to_string())?;
+                line.to_latex(writer)?;
+            }
+        }
+        writer.discard_break();
+        writer.write("\n".to_string())?;
+    }
+
+    writer.write("\\end{thebibliography}\n\n".to_string())
+}
+
+/// Writes the glossary entries as a description list
+fn write_glossary(writer: &mut LatexWriter) -> io::Result<()> {
+    let entries = match writer.get_glossary() {
+        Some(glossary) => glossary.lock().get_assigned_entries(),
+        None => return Ok(()),
+    };
+    if entries.is_empty() {
+        return Ok(());
+    }
+    writer.write("\\begin{description}\n".to_string())?;
+
+    for entry in entries {
+        let entry = entry.lock();
+        writer.write(format!(
+            "\\item[{}] \\label{{{}}}\\textit{{{}}} -- {}\n",
+            escape_latex(&entry.short),
+            escape_label(&entry.short),
+            escape_latex(&entry.long),
+            escape_latex(&entry.description)
+        ))?;
+    }
+
+    writer.write("\\end{description}\n\n".to_string())
+}
+
+impl ToLatex for RefLink {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write(format!("\\hyperref[{}]{{", escape_label(&self.reference)))?;
+        self.description.to_latex(writer)?;
+
+        writer.write("}".to_string())
+    }
+}
+
+impl ToLatex for InlineMetadata {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if let Some(MetadataValue::String(format)) = self.data.get("display") {
+            let mut template = PlaceholderTemplate::new(format.clone());
+            self.data
+                .iter()
+                .for_each(|(k, v)| template.add_replacement(k, &v.to_string()));
+
+            writer.write_escaped(template.render())?;
+        } else {
+            for (k, v) in &self.data {
+                writer.write_escaped(format!("{}={},", k, v.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToLatex for Centered {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write("\\begin{center}\n".to_string())?;
+        self.line.to_latex(writer)?;
+        writer.discard_break();
+
+        writer.write("\n\\end{center}\n".to_string())
+    }
+}
+
+impl ToLatex for Checkbox {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if self.value {
+            writer.write("$\\boxtimes$".to_string())
+        } else {
+            writer.write("$\\square$".to_string())
+        }
+    }
+}
+
+impl ToLatex for Emoji {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        // emojis aren't supported by the default latex fonts
+        writer.write_escaped(format!(":{}:", self.name))
+    }
+}
+
+impl ToLatex for Colored {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        let color = self.color.trim();
+        let hex = color.trim_start_matches('#');
+
+        if color.starts_with('#') && hex.len() == 6 {
+            writer.write(format!("\\textcolor[HTML]{{{}}}{{", hex.to_uppercase()))?;
+        } else if color.starts_with('#') && hex.len() == 3 {
+            let hex = hex.chars().flat_map(|c| vec![c, c]).collect::<String>();
+            writer.write(format!("\\textcolor[HTML]{{{}}}{{", hex.to_uppercase()))?;
+        } else if color.chars().all(|c| c.is_ascii_alphabetic()) && !color.is_empty() {
+            writer.write(format!("\\textcolor{{{}}}{{", color))?;
+        } else {
+            return self.value.to_latex(writer);
+        }
+        self.value.to_latex(writer)?;
+
+        writer.write("}".to_string())
+    }
+}
+
+impl ToLatex for BibReference {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if self.entry_anchor.lock().entry.is_none() {
+            return writer.write_escaped(self.get_formatted());
+        }
+        if self.display.is_some() {
+            writer.write(format!(
+                "\\hyperlink{{cite.{}}}{{{}}}",
+                escape_label(&self.key),
+                escape_latex(&self.get_formatted())
+            ))
+        } else {
+            writer.write(format!("\\cite{{{}}}", escape_label(&self.key)))
+        }
+    }
+}
+
+impl ToLatex for Template {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        for element in &self.text {
+            element.to_latex(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToLatex for TemplateVariable {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            writer.write_escaped(self.prefix.clone())?;
+            value.to_latex(writer)?;
+            writer.write_escaped(self.suffix.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToLatex for CharacterCode {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        let decoded = decode_entity(&self.code).unwrap_or_else(|| format!("&{};", self.code));
+
+        writer.write_escaped(decoded)
+    }
+}
+
+impl ToLatex for Anchor {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        writer.write(format!("\\label{{{}}}", escape_label(&self.key)))?;
+
+        self.inner.to_latex(writer)
+    }
+}
+
+impl ToLatex for GlossaryReference {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        if let Some(entry) = &self.entry {
+            let entry = entry.lock();
+            let text = match self.display {
+                GlossaryDisplay::Short => &entry.short,
+                GlossaryDisplay::Long => &entry.long,
+            };
+            writer.write(format!(
+                "\\hyperref[{}]{{{}}}",
+                escape_label(&self.short),
+                escape_latex(text)
+            ))
+        } else {
+            writer.write_escaped(format!("~{}", self.short.clone()))
+        }
+    }
+}
+
+impl ToLatex for Arrow {
+    fn to_latex(&self, writer: &mut LatexWriter) -> io::Result<()> {
+        let arrow = match self {
+            Arrow::RightArrow => "$\\longrightarrow$",
+            Arrow::LeftArrow => "$\\longleftarrow$",
+            Arrow::LeftRightArrow => "$\\longleftrightarrow$",
+            Arrow::BigRightArrow => "$\\Longrightarrow$",
+            Arrow::BigLeftArrow => "$\\Longleftarrow$",
+            Arrow::BigLeftRightArrow => "$\\Longleftrightarrow$",
+        };
+
+        writer.write(arrow.to_string())
+    }
+}
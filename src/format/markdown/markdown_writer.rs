@@ -0,0 +1,181 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub struct MarkdownWriter {
+    inner: Box<dyn Write>,
+    prefixes: Vec<String>,
+    at_line_start: bool,
+    line_has_content: bool,
+    pending_break: bool,
+    base_dir: Option<PathBuf>,
+}
+
+impl MarkdownWriter {
+    /// Creates a new writer
+    pub fn new(inner: Box<dyn Write>) -> Self {
+        Self {
+            inner,
+            prefixes: Vec::new(),
+            at_line_start: true,
+            line_has_content: false,
+            pending_break: false,
+            base_dir: None,
+        }
+    }
+
+    /// Sets the directory that urls of local files are made relative to
+    pub fn set_base_dir(&mut self, base_dir: PathBuf) {
+        self.base_dir = Some(base_dir);
+    }
+
+    /// Returns the url of a local file relative to the base directory.
+    /// Other urls are returned unchanged.
+    pub fn get_local_url(&self, url: &str) -> String {
+        self.base_dir
+            .as_ref()
+            .and_then(|base| relative_path(Path::new(url), base))
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    /// Writes raw markdown. Every new line starts with the current prefix.
+    pub fn write(&mut self, markdown: String) -> io::Result<()> {
+        if self.pending_break {
+            self.pending_break = false;
+            if self.line_has_content {
+                self.write_lines("\\\n")?;
+            }
+        }
+
+        self.write_lines(&markdown)
+    }
+
+    /// Writes text with all markdown special characters escaped
+    pub fn write_escaped(&mut self, text: String) -> io::Result<()> {
+        let mut escaped = escape_markdown(&text);
+        let starts_line = self.at_line_start || (self.pending_break && self.line_has_content);
+
+        // list markers and setext underlines are only special at the start of a line
+        if starts_line && escaped.starts_with(['-', '+', '=']) {
+            escaped.insert(0, '\\');
+        }
+
+        self.write(escaped)
+    }
+
+    /// Writes a space between two lines of a block unless they are
+    /// already separated by a line break
+    pub fn write_separator(&mut self) -> io::Result<()> {
+        if self.pending_break || !self.line_has_content {
+            Ok(())
+        } else {
+            self.write_lines(" ")
+        }
+    }
+
+    /// Adds a hard line break that is only written if more text follows in the same block
+    pub fn line_break(&mut self) {
+        self.pending_break = true;
+    }
+
+    /// Discards a line break at the end of a block
+    pub fn discard_break(&mut self) {
+        self.pending_break = false;
+    }
+
+    /// Adds a prefix that is written at the start of every following line.
+    /// Used for list item indentation and quotes.
+    pub fn push_prefix(&mut self, prefix: &str) {
+        self.prefixes.push(prefix.to_string());
+    }
+
+    /// Removes the last added prefix
+    pub fn pop_prefix(&mut self) {
+        self.prefixes.pop();
+    }
+
+    /// Flushes the writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn write_lines(&mut self, text: &str) -> io::Result<()> {
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                if self.at_line_start {
+                    // empty lines only get the prefix without trailing whitespace
+                    let prefix = self.prefixes.concat();
+                    self.inner.write_all(prefix.trim_end().as_bytes())?;
+                }
+                self.inner.write_all(b"\n")?;
+                self.at_line_start = true;
+                self.line_has_content = false;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.at_line_start {
+                self.inner.write_all(self.prefixes.concat().as_bytes())?;
+                self.at_line_start = false;
+            }
+            if !line.trim().is_empty() {
+                self.line_has_content = true;
+            }
+            self.inner.write_all(line.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Escapes all characters that could be interpreted as markdown syntax
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~' | '$' | '&' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Returns a backtick fence that is longer than any backtick sequence in the text
+pub fn get_fence(text: &str, min_length: usize) -> String {
+    let longest = text.split(|c| c != '`').map(|s| s.len()).max().unwrap_or(0);
+
+    "`".repeat(min_length.max(longest + 1))
+}
+
+/// Returns the path of an existing file relative to the base directory
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path = path.canonicalize().ok()?;
+    let base = base.canonicalize().ok()?;
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    for component in path.components().skip(common) {
+        relative.push(component);
+    }
+
+    Some(relative)
+}
@@ -0,0 +1,60 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::Document;
+use crate::format::markdown::markdown_writer::MarkdownWriter;
+use crate::format::markdown::to_markdown::ToMarkdown;
+use crate::format::{RenderResult, Renderer};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub mod markdown_writer;
+pub mod to_markdown;
+
+/// Renders the resolved document to github flavoured markdown.
+/// Images are referenced by their original url.
+/// Renders the document to markdown. Urls of local files are written relative to the
+/// output directory or to the directory of the document if the output is no file.
+pub fn render_to_markdown(
+    document: Document,
+    inner: Box<dyn Write>,
+    output_dir: Option<&Path>,
+) -> io::Result<()> {
+    let mut writer = MarkdownWriter::new(inner);
+    writer.set_base_dir(
+        output_dir
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| document.get_root_dir()),
+    );
+    document.to_markdown(&mut writer)?;
+
+    writer.flush()
+}
+
+/// Renders documents to markdown
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn name(&self) -> &str {
+        "markdown"
+    }
+
+    fn extension(&self) -> &str {
+        "md"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        writer: Box<dyn Write>,
+        output: Option<&Path>,
+    ) -> RenderResult<()> {
+        render_to_markdown(document, writer, output.and_then(Path::parent))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,593 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::*;
+use crate::format::latex::math::expression_to_latex;
+use crate::format::markdown::markdown_writer::{escape_markdown, get_fence, MarkdownWriter};
+use crate::format::pdf_outline::inline_to_string;
+use crate::format::PlaceholderTemplate;
+use crate::references::glossary::{GlossaryDisplay, GlossaryReference};
+use crate::references::templates::{Template, TemplateVariable};
+use crate::utils::entities::decode_entity;
+use htmlescape::{encode_attribute, encode_minimal};
+use std::io;
+
+pub trait ToMarkdown {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()>;
+}
+
+impl ToMarkdown for Element {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        match self {
+            Element::Block(block) => block.to_markdown(writer),
+            Element::Inline(inline) => inline.to_markdown(writer),
+            Element::Line(line) => line.to_markdown(writer),
+        }
+    }
+}
+
+impl ToMarkdown for Line {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        match self {
+            Line::Text(text) => text.to_markdown(writer),
+            Line::Ruler(ruler) => ruler.to_markdown(writer),
+            Line::RefLink(anchor) => anchor.to_markdown(writer),
+            Line::Centered(centered) => centered.to_markdown(writer),
+            Line::Anchor(a) => a.to_markdown(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToMarkdown for Inline {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        match self {
+            Inline::Url(url) => url.to_markdown(writer),
+            Inline::Monospace(mono) => mono.to_markdown(writer),
+            Inline::Striked(striked) => striked.to_markdown(writer),
+            Inline::Plain(plain) => plain.to_markdown(writer),
+            Inline::Italic(italic) => italic.to_markdown(writer),
+            Inline::Underlined(under) => under.to_markdown(writer),
+            Inline::Bold(bold) => bold.to_markdown(writer),
+            Inline::Image(img) => img.to_markdown(writer),
+            Inline::Placeholder(placeholder) => placeholder.read().unwrap().to_markdown(writer),
+            Inline::Superscript(superscript) => superscript.to_markdown(writer),
+            Inline::Checkbox(checkbox) => checkbox.to_markdown(writer),
+            Inline::Emoji(emoji) => emoji.to_markdown(writer),
+            Inline::Colored(colored) => colored.to_markdown(writer),
+            Inline::BibReference(bibref) => bibref.read().unwrap().to_markdown(writer),
+            Inline::TemplateVar(var) => var.read().unwrap().to_markdown(writer),
+            Inline::Math(m) => m.to_markdown(writer),
+            Inline::LineBreak => {
+                writer.line_break();
+                Ok(())
+            }
+            Inline::CharacterCode(code) => code.to_markdown(writer),
+            Inline::GlossaryReference(gloss) => gloss.lock().to_markdown(writer),
+            Inline::Arrow(a) => a.to_markdown(writer),
+            Inline::Anchor(a) => a.to_markdown(writer),
+        }
+    }
+}
+
+impl ToMarkdown for Block {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        match self {
+            Block::Paragraph(para) => para.to_markdown(writer),
+            Block::List(list) => list.to_markdown(writer),
+            Block::Table(table) => table.to_markdown(writer),
+            Block::CodeBlock(code) => code.to_markdown(writer),
+            Block::Quote(quote) => quote.to_markdown(writer),
+            Block::Section(section) => section.to_markdown(writer),
+            Block::Import(import) => import.to_markdown(writer),
+            Block::Placeholder(placeholder) => placeholder.read().unwrap().to_markdown(writer),
+            Block::MathBlock(m) => m.to_markdown(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToMarkdown for MetadataValue {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        match self {
+            MetadataValue::String(string) => writer.write_escaped(string.clone()),
+            MetadataValue::Integer(num) => writer.write(num.to_string()),
+            MetadataValue::Placeholder(ph) => ph.read().unwrap().to_markdown(writer),
+            MetadataValue::Bool(b) => writer.write(b.to_string()),
+            MetadataValue::Float(f) => writer.write(f.to_string()),
+            MetadataValue::Template(t) => t.to_markdown(writer),
+        }
+    }
+}
+
+impl ToMarkdown for Document {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        for element in &self.elements {
+            element.to_markdown(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for Math {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write(format!("${}$", expression_to_latex(&self.expression)))
+    }
+}
+
+impl ToMarkdown for MathBlock {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write(format!(
+            "$$\n{}\n$$\n\n",
+            expression_to_latex(&self.expression)
+        ))
+    }
+}
+
+impl ToMarkdown for Import {
+    fn to_markdown(&self, _writer: &mut MarkdownWriter) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ToMarkdown for Section {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write(format!(
+            "<a id=\"{}\"></a>\n\n",
+            encode_minimal(&self.header.anchor)
+        ))?;
+        writer.write(format!(
+            "{} ",
+            "#".repeat(self.header.size.clamp(1, 6) as usize)
+        ))?;
+        self.header.line.to_markdown(writer)?;
+        writer.discard_break();
+        writer.write("\n\n".to_string())?;
+
+        for element in &self.elements {
+            element.to_markdown(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for Paragraph {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        if let Some(first) = self.elements.first() {
+            first.to_markdown(writer)?;
+        }
+        if self.elements.len() > 1 {
+            for element in &self.elements[1..] {
+                writer.write_separator()?;
+                element.to_markdown(writer)?;
+            }
+        }
+        writer.discard_break();
+
+        writer.write("\n\n".to_string())
+    }
+}
+
+impl ToMarkdown for List {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        write_list(writer, self.ordered, &self.items)?;
+
+        writer.write("\n".to_string())
+    }
+}
+
+/// Writes the items of a list with the children indented below each item
+fn write_list(writer: &mut MarkdownWriter, ordered: bool, items: &[ListItem]) -> io::Result<()> {
+    for (index, item) in items.iter().enumerate() {
+        let marker = if ordered {
+            format!("{}. ", index + 1)
+        } else {
+            "- ".to_string()
+        };
+        writer.write(marker.clone())?;
+        writer.push_prefix(&" ".repeat(marker.len()));
+        item.to_markdown(writer)?;
+        writer.pop_prefix();
+    }
+
+    Ok(())
+}
+
+impl ToMarkdown for ListItem {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        self.text.to_markdown(writer)?;
+        writer.discard_break();
+        writer.write("\n".to_string())?;
+
+        if let Some(first) = self.children.first() {
+            write_list(writer, first.ordered, &self.children)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for Table {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        self.header.to_markdown(writer)?;
+        writer.write(format!(
+            "|{}\n",
+            " --- |".repeat(self.header.cells.len().max(1))
+        ))?;
+
+        for row in &self.rows {
+            row.to_markdown(writer)?;
+        }
+
+        writer.write("\n".to_string())
+    }
+}
+
+impl ToMarkdown for Row {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write("|".to_string())?;
+        for cell in &self.cells {
+            writer.write(" ".to_string())?;
+            cell.to_markdown(writer)?;
+            writer.write(" |".to_string())?;
+        }
+
+        writer.write("\n".to_string())
+    }
+}
+
+impl ToMarkdown for Cell {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        self.text.to_markdown(writer)?;
+        writer.discard_break();
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for CodeBlock {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        if let Some(anchor) = self.get_anchor() {
+            writer.write(format!("<a id=\"{}\"></a>\n\n", encode_minimal(&anchor)))?;
+        }
+        if let Some(file_name) = self.get_file_name() {
+            writer.write(format!("**{}**\n\n", escape_markdown(&file_name)))?;
+        }
+        // markdown has no syntax for line numbers and highlighted lines
+        let fence = get_fence(&self.code, 3);
+        writer.write(format!("{}{}\n", fence, self.language))?;
+        writer.write(self.code.trim_end_matches('\n').to_string())?;
+        writer.write(format!("\n{}\n\n", fence))?;
+
+        if let Some(caption) = self.get_caption() {
+            writer.write(format!("*{}*\n\n", escape_markdown(&caption)))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for Quote {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.push_prefix("> ");
+        for line in &self.text {
+            line.to_markdown(writer)?;
+            writer.line_break();
+        }
+        if let Some(meta) = &self.metadata {
+            writer.write("— *".to_string())?;
+            meta.to_markdown(writer)?;
+            writer.write("*".to_string())?;
+        }
+        writer.discard_break();
+        writer.write("\n".to_string())?;
+        writer.pop_prefix();
+
+        writer.write("\n".to_string())
+    }
+}
+
+impl ToMarkdown for Ruler {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.discard_break();
+        writer.write("\n\n---\n\n".to_string())
+    }
+}
+
+impl ToMarkdown for TextLine {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        for text in &self.subtext {
+            text.to_markdown(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for Image {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        let description = self
+            .url
+            .description
+            .as_ref()
+            .map(|d| d.iter().map(inline_to_string).collect::<String>())
+            .unwrap_or_default();
+        let url = writer.get_local_url(&self.url.url);
+        let mut size = String::new();
+
+        if let Some(meta) = &self.metadata {
+            if let Some(width) = meta.get_string("width") {
+                size.push_str(&format!(" width=\"{}\"", encode_attribute(&width)));
+            }
+            if let Some(height) = meta.get_string("height") {
+                size.push_str(&format!(" height=\"{}\"", encode_attribute(&height)));
+            }
+        }
+        if size.is_empty() {
+            writer.write(format!(
+                "![{}]({})",
+                escape_markdown(&description),
+                escape_url(&url)
+            ))
+        } else {
+            // markdown has no syntax for image sizes
+            writer.write(format!(
+                "<img src=\"{}\" alt=\"{}\"{}/>",
+                encode_attribute(&url),
+                encode_attribute(&description),
+                size
+            ))
+        }
+    }
+}
+
+/// Escapes the characters of an url that would end the link destination
+fn escape_url(url: &str) -> String {
+    url.replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+}
+
+impl ToMarkdown for BoldText {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        write_wrapped(writer, "**", "**", &self.value)
+    }
+}
+
+impl ToMarkdown for UnderlinedText {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        write_wrapped(writer, "<ins>", "</ins>", &self.value)
+    }
+}
+
+impl ToMarkdown for ItalicText {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        write_wrapped(writer, "*", "*", &self.value)
+    }
+}
+
+impl ToMarkdown for StrikedText {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        write_wrapped(writer, "~~", "~~", &self.value)
+    }
+}
+
+impl ToMarkdown for SuperscriptText {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        write_wrapped(writer, "<sup>", "</sup>", &self.value)
+    }
+}
+
+/// Writes the inline elements between the given start and end markers
+fn write_wrapped(
+    writer: &mut MarkdownWriter,
+    start: &str,
+    end: &str,
+    value: &[Inline],
+) -> io::Result<()> {
+    writer.write(start.to_string())?;
+    for element in value {
+        element.to_markdown(writer)?;
+    }
+
+    writer.write(end.to_string())
+}
+
+impl ToMarkdown for MonospaceText {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        let fence = get_fence(&self.value, 1);
+        if self.value.starts_with('`') || self.value.ends_with('`') {
+            writer.write(format!("{} {} {}", fence, self.value, fence))
+        } else {
+            writer.write(format!("{}{}{}", fence, self.value, fence))
+        }
+    }
+}
+
+impl ToMarkdown for Url {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        if let Some(description) = &self.description {
+            writer.write("[".to_string())?;
+            for desc in description {
+                desc.to_markdown(writer)?;
+            }
+            let url = writer.get_local_url(&self.url);
+            writer.write(format!("]({})", escape_url(&url)))
+        } else {
+            let url = writer.get_local_url(&self.url);
+            writer.write(format!("<{}>", url.replace('>', "%3E")))
+        }
+    }
+}
+
+impl ToMarkdown for PlainText {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write_escaped(self.value.clone())
+    }
+}
+
+impl ToMarkdown for Placeholder {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            value.to_markdown(writer)
+        } else {
+            log::debug!("Unknown placeholder [[{}]]", self.name.clone());
+            writer.write_escaped(format!("[[{}]]", self.name.clone()))
+        }
+    }
+}
+
+impl ToMarkdown for RefLink {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write("[".to_string())?;
+        self.description.to_markdown(writer)?;
+
+        writer.write(format!("](#{})", escape_url(&self.reference)))
+    }
+}
+
+impl ToMarkdown for InlineMetadata {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        if let Some(MetadataValue::String(format)) = self.data.get("display") {
+            let mut template = PlaceholderTemplate::new(format.clone());
+            self.data
+                .iter()
+                .for_each(|(k, v)| template.add_replacement(k, &v.to_string()));
+
+            writer.write_escaped(template.render())?;
+        } else {
+            for (k, v) in &self.data {
+                writer.write_escaped(format!("{}={},", k, v.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToMarkdown for Centered {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        // the blank lines are needed for the markdown inside the html block to be rendered
+        writer.write("<div align=\"center\">\n\n".to_string())?;
+        self.line.to_markdown(writer)?;
+        writer.discard_break();
+
+        writer.write("\n\n</div>\n\n".to_string())
+    }
+}
+
+impl ToMarkdown for Checkbox {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        // at the start of a list item this is rendered as a task list item
+        if self.value {
+            writer.write("[x]".to_string())
+        } else {
+            writer.write("[ ]".to_string())
+        }
+    }
+}
+
+impl ToMarkdown for Emoji {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write(format!(":{}:", self.name))
+    }
+}
+
+impl ToMarkdown for Colored {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write(format!(
+            "<span style=\"color: {}\">",
+            encode_attribute(&self.color)
+        ))?;
+        self.value.to_markdown(writer)?;
+
+        writer.write("</span>".to_string())
+    }
+}
+
+impl ToMarkdown for BibReference {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write(format!(
+            "<sup>[{}](#{})</sup>",
+            escape_markdown(&self.get_formatted()),
+            escape_url(&self.key)
+        ))
+    }
+}
+
+impl ToMarkdown for Template {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        for element in &self.text {
+            element.to_markdown(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for TemplateVariable {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            writer.write_escaped(self.prefix.clone())?;
+            value.to_markdown(writer)?;
+            writer.write_escaped(self.suffix.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToMarkdown for CharacterCode {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        match decode_entity(&self.code) {
+            Some(decoded) => writer.write_escaped(decoded),
+            None => writer.write(format!("&{};", self.code)),
+        }
+    }
+}
+
+impl ToMarkdown for Anchor {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        writer.write(format!("<a id=\"{}\"></a>", encode_minimal(&self.key)))?;
+
+        self.inner.to_markdown(writer)
+    }
+}
+
+impl ToMarkdown for GlossaryReference {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        if let Some(entry) = &self.entry {
+            let entry = entry.lock();
+            let text = match self.display {
+                GlossaryDisplay::Short => &entry.short,
+                GlossaryDisplay::Long => &entry.long,
+            };
+            writer.write(format!(
+                "[{}](#{})",
+                escape_markdown(text),
+                escape_url(&self.short)
+            ))
+        } else {
+            writer.write_escaped(format!("~{}", self.short.clone()))
+        }
+    }
+}
+
+impl ToMarkdown for Arrow {
+    fn to_markdown(&self, writer: &mut MarkdownWriter) -> io::Result<()> {
+        let arrow = match self {
+            Arrow::RightArrow => "\u{27F6}",
+            Arrow::LeftArrow => "\u{27F5}",
+            Arrow::LeftRightArrow => "\u{27F7}",
+            Arrow::BigRightArrow => "\u{27F9}",
+            Arrow::BigLeftArrow => "\u{27F8}",
+            Arrow::BigLeftRightArrow => "\u{27FA}",
+        };
+
+        writer.write(arrow.to_string())
+    }
+}
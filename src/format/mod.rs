@@ -4,13 +4,128 @@
  * See LICENSE for more information.
  */
 
-use regex::Regex;
+use crate::elements::Document;
+use regex::{Captures, Regex};
 use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
 
 #[cfg(feature = "pdf")]
 pub mod chromium_pdf;
+pub mod docx;
+pub mod epub;
 pub mod html;
+pub mod json;
+pub mod latex;
+pub mod markdown;
+pub mod native_pdf;
+pub mod odt;
+pub mod page_setup;
+pub mod pdf_outline;
+pub mod site;
+pub mod slides;
 pub mod style;
+pub mod text;
+
+pub type RenderResult<T> = Result<T, Box<dyn Error>>;
+
+/// An output format documents can be rendered to
+pub trait Renderer {
+    /// The name the format is selected with
+    fn name(&self) -> &str;
+
+    /// The file extension of the output without the leading dot.
+    /// Formats that write a directory return an empty string.
+    fn extension(&self) -> &str;
+
+    /// Renders the document to the writer. The output is the path of the file
+    /// the writer writes to or the output directory if the format writes a directory.
+    fn render(
+        &mut self,
+        document: Document,
+        writer: Box<dyn Write>,
+        output: Option<&Path>,
+    ) -> RenderResult<()>;
+
+    /// Returns if the format writes a directory instead of a single file.
+    /// The writer passed to those formats discards everything written to it.
+    fn writes_directory(&self) -> bool {
+        false
+    }
+}
+
+/// A registry of the formats documents can be rendered to
+#[derive(Default)]
+pub struct RendererRegistry {
+    renderers: Vec<Box<dyn Renderer>>,
+}
+
+impl RendererRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with all built-in formats
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(html::HtmlRenderer);
+        #[cfg(feature = "pdf")]
+        registry.register(chromium_pdf::PdfRenderer::new());
+        registry.register(native_pdf::NativePdfRenderer);
+        registry.register(epub::EpubRenderer);
+        registry.register(docx::DocxRenderer);
+        registry.register(odt::OdtRenderer);
+        registry.register(latex::LatexRenderer);
+        registry.register(markdown::MarkdownRenderer);
+        registry.register(text::TextRenderer);
+        registry.register(json::JsonRenderer);
+        registry.register(site::SiteRenderer);
+        registry.register(slides::SlidesRenderer);
+        #[cfg(feature = "pdf")]
+        registry.register(chromium_pdf::SlidesPdfRenderer::new());
+
+        registry
+    }
+
+    /// Registers a renderer. A renderer with the same name is replaced.
+    pub fn register<R: Renderer + 'static>(&mut self, renderer: R) {
+        let renderer: Box<dyn Renderer> = Box::new(renderer);
+
+        if let Some(existing) = self
+            .renderers
+            .iter_mut()
+            .find(|r| r.name() == renderer.name())
+        {
+            *existing = renderer;
+        } else {
+            self.renderers.push(renderer);
+        }
+    }
+
+    /// Returns the renderer with the given name
+    pub fn get(&mut self, name: &str) -> Option<&mut dyn Renderer> {
+        let renderer = self.renderers.iter_mut().find(|r| r.name() == name)?;
+
+        Some(renderer.as_mut())
+    }
+
+    /// Returns the name of the first format registered for the extension of the path
+    pub fn get_format_for_path(&self, path: &Path) -> Option<String> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+        self.renderers
+            .iter()
+            .find(|r| !r.extension().is_empty() && r.extension() == extension)
+            .map(|r| r.name().to_string())
+    }
+
+    /// Returns the names of all registered formats
+    pub fn names(&self) -> Vec<&str> {
+        self.renderers.iter().map(|r| r.name()).collect()
+    }
+}
 
 pub struct PlaceholderTemplate {
     value: String,
@@ -41,19 +156,46 @@ impl PlaceholderTemplate {
         self.replacements = replacements;
     }
 
+    /// Replaces all placeholders of the template in a single pass
+    /// so that the inserted values are never scanned for placeholders again
     pub fn render(&self) -> String {
         lazy_static::lazy_static! { static ref RE_REP: Regex = Regex::new(r"\{\{([^}]*)}}").unwrap(); }
-        let mut ret_string = self.value.clone();
-        RE_REP.find_iter(&self.value).for_each(|m| {
-            let full_match = m.as_str();
-            let name = &full_match[2..full_match.len() - 2];
-            if let Some(val) = self.replacements.get(name) {
-                ret_string = ret_string.replace(full_match, val)
-            } else {
-                ret_string = ret_string.replace(full_match, "")
-            }
-        });
-
-        ret_string
+
+        RE_REP
+            .replace_all(&self.value, |caps: &Captures| {
+                self.replacements.get(&caps[1]).cloned().unwrap_or_default()
+            })
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_replaces_placeholders() {
+        let mut template = PlaceholderTemplate::new(
+            "<title>{{title}}</title><main>{{body}}</main>{{unknown}}".to_string(),
+        );
+        template.add_replacement("title", "Document");
+        template.add_replacement("body", "<p>Text</p>");
+
+        assert_eq!(
+            template.render(),
+            "<title>Document</title><main><p>Text</p></main>"
+        );
+    }
+
+    #[test]
+    fn it_doesnt_replace_placeholders_in_inserted_values() {
+        let mut template = PlaceholderTemplate::new("{{body}}<nav>{{toc}}</nav>".to_string());
+        template.add_replacement("body", "<code>{{toc}}</code>");
+        template.add_replacement("toc", "<ul></ul>");
+
+        assert_eq!(
+            template.render(),
+            "<code>{{toc}}</code><nav><ul></ul></nav>"
+        );
     }
 }
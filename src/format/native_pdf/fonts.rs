@@ -0,0 +1,179 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use lopdf::{Dictionary, Object};
+
+/// Widths of the printable ascii characters (32-126) of Helvetica in 1/1000 em
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Widths of the printable ascii characters (32-126) of Helvetica-Bold in 1/1000 em
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// The width of every character of the courier font family
+const COURIER_WIDTH: u16 = 600;
+
+/// The width used for characters outside of the ascii range
+const FALLBACK_WIDTH: u16 = 556;
+
+/// One of the standard pdf fonts used by the native renderer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StandardFont {
+    Helvetica,
+    HelveticaBold,
+    HelveticaOblique,
+    HelveticaBoldOblique,
+    Courier,
+    CourierBold,
+    CourierOblique,
+    CourierBoldOblique,
+}
+
+impl StandardFont {
+    /// All fonts that are registered in the resources of every page
+    pub const ALL: [StandardFont; 8] = [
+        StandardFont::Helvetica,
+        StandardFont::HelveticaBold,
+        StandardFont::HelveticaOblique,
+        StandardFont::HelveticaBoldOblique,
+        StandardFont::Courier,
+        StandardFont::CourierBold,
+        StandardFont::CourierOblique,
+        StandardFont::CourierBoldOblique,
+    ];
+
+    /// Returns the font for the given style flags
+    pub fn from_style(bold: bool, italic: bool, monospace: bool) -> Self {
+        match (monospace, bold, italic) {
+            (false, false, false) => StandardFont::Helvetica,
+            (false, true, false) => StandardFont::HelveticaBold,
+            (false, false, true) => StandardFont::HelveticaOblique,
+            (false, true, true) => StandardFont::HelveticaBoldOblique,
+            (true, false, false) => StandardFont::Courier,
+            (true, true, false) => StandardFont::CourierBold,
+            (true, false, true) => StandardFont::CourierOblique,
+            (true, true, true) => StandardFont::CourierBoldOblique,
+        }
+    }
+
+    /// The postscript name of the font
+    pub fn base_name(&self) -> &'static str {
+        match self {
+            StandardFont::Helvetica => "Helvetica",
+            StandardFont::HelveticaBold => "Helvetica-Bold",
+            StandardFont::HelveticaOblique => "Helvetica-Oblique",
+            StandardFont::HelveticaBoldOblique => "Helvetica-BoldOblique",
+            StandardFont::Courier => "Courier",
+            StandardFont::CourierBold => "Courier-Bold",
+            StandardFont::CourierOblique => "Courier-Oblique",
+            StandardFont::CourierBoldOblique => "Courier-BoldOblique",
+        }
+    }
+
+    /// The name the font is registered with in the page resources
+    pub fn resource_name(&self) -> &'static str {
+        match self {
+            StandardFont::Helvetica => "F1",
+            StandardFont::HelveticaBold => "F2",
+            StandardFont::HelveticaOblique => "F3",
+            StandardFont::HelveticaBoldOblique => "F4",
+            StandardFont::Courier => "F5",
+            StandardFont::CourierBold => "F6",
+            StandardFont::CourierOblique => "F7",
+            StandardFont::CourierBoldOblique => "F8",
+        }
+    }
+
+    /// Returns the font dictionary that is embedded into the pdf
+    pub fn to_dictionary(&self) -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.set("Type", Object::Name(b"Font".to_vec()));
+        dict.set("Subtype", Object::Name(b"Type1".to_vec()));
+        dict.set(
+            "BaseFont",
+            Object::Name(self.base_name().as_bytes().to_vec()),
+        );
+        dict.set("Encoding", Object::Name(b"WinAnsiEncoding".to_vec()));
+
+        dict
+    }
+
+    /// Returns the width of a single character in 1/1000 em
+    pub fn char_width(&self, c: char) -> u16 {
+        let widths = match self {
+            StandardFont::Helvetica | StandardFont::HelveticaOblique => &HELVETICA_WIDTHS,
+            StandardFont::HelveticaBold | StandardFont::HelveticaBoldOblique => {
+                &HELVETICA_BOLD_WIDTHS
+            }
+            _ => return COURIER_WIDTH,
+        };
+        let code = c as u32;
+
+        if (32..127).contains(&code) {
+            widths[(code - 32) as usize]
+        } else {
+            FALLBACK_WIDTH
+        }
+    }
+
+    /// Returns the width of a string in points for the given font size
+    pub fn text_width(&self, text: &str, size: f32) -> f32 {
+        let units: u32 = text.chars().map(|c| self.char_width(c) as u32).sum();
+
+        units as f32 * size / 1000.0
+    }
+}
+
+/// Encodes a string with the WinAnsiEncoding used by the standard fonts.
+/// Characters that can't be represented are replaced with a question mark.
+pub fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| win_ansi_byte(c).unwrap_or(b'?'))
+        .collect()
+}
+
+/// Returns if the character can be represented with the WinAnsiEncoding
+pub fn is_win_ansi(c: char) -> bool {
+    win_ansi_byte(c).is_some()
+}
+
+/// Returns the WinAnsiEncoding byte of a character
+fn win_ansi_byte(c: char) -> Option<u8> {
+    let byte = match c {
+        ' '..='~' => c as u8,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        '„' => 0x84,
+        '…' => 0x85,
+        '†' => 0x86,
+        '‡' => 0x87,
+        '‰' => 0x89,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '™' => 0x99,
+        '\t' => b' ',
+        _ => return None,
+    };
+
+    Some(byte)
+}
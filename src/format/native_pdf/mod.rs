@@ -0,0 +1,56 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::Document;
+use crate::format::native_pdf::pdf_writer::PDFWriter;
+use crate::format::native_pdf::result::NativePdfResult;
+use crate::format::native_pdf::to_pdf::ToPdf;
+use crate::format::pdf_outline::create_outline;
+use crate::format::{RenderResult, Renderer};
+use std::io::Write;
+use std::path::Path;
+
+pub mod fonts;
+pub mod pdf_writer;
+pub mod result;
+pub mod to_pdf;
+
+/// Renders the document to pdf without using a browser and returns the resulting bytes
+pub fn render_to_native_pdf(document: Document) -> NativePdfResult<Vec<u8>> {
+    let settings = document.config.lock().clone();
+    let mut writer = PDFWriter::new(&settings);
+    log::info!("Laying out pdf pages...");
+    document.to_pdf(&mut writer)?;
+    let outline = create_outline(&document);
+    log::info!("Writing pdf document...");
+
+    writer.finish(&settings, &outline)
+}
+
+/// Renders documents to pdf without a browser
+pub struct NativePdfRenderer;
+
+impl Renderer for NativePdfRenderer {
+    fn name(&self) -> &str {
+        "pdf-native"
+    }
+
+    fn extension(&self) -> &str {
+        "pdf"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        mut writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        writer.write_all(&render_to_native_pdf(document)?)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
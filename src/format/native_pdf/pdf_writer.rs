@@ -0,0 +1,1178 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::format::native_pdf::fonts::{encode_win_ansi, is_win_ansi, StandardFont};
+use crate::format::native_pdf::result::NativePdfResult;
+use crate::format::pdf_outline::{apply_document_info, write_outline, OutlineEntry};
+use crate::settings::Settings;
+use image::GenericImageView;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+use std::collections::{BTreeSet, HashMap};
+
+/// Points per inch. Page dimensions in the settings are given in inches.
+const POINTS_PER_INCH: f32 = 72.0;
+/// Points per css pixel used to determine the natural size of images
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+const DEFAULT_PAGE_WIDTH: f32 = 8.5;
+const DEFAULT_PAGE_HEIGHT: f32 = 11.0;
+const DEFAULT_MARGIN: f32 = 0.4;
+
+const BASE_FONT_SIZE: f32 = 11.0;
+const CODE_FONT_SIZE: f32 = 9.0;
+const LINE_SPACING: f32 = 1.35;
+const INDENT_WIDTH: f32 = 18.0;
+const CELL_PADDING: f32 = 4.0;
+
+/// The style of a piece of text
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub monospace: bool,
+    pub underline: bool,
+    pub strike: bool,
+    pub superscript: bool,
+    pub size: f32,
+    pub color: (f32, f32, f32),
+}
+
+/// The target of a link
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    Url(String),
+    Anchor(String),
+}
+
+/// The horizontal alignment of laid out lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+}
+
+/// An inline item that is buffered until the surrounding block is laid out
+#[derive(Clone, Debug)]
+pub enum InlineItem {
+    Text(Span),
+    LineBreak,
+}
+
+/// Text with a single style
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub text: String,
+    pub style: TextStyle,
+    pub link: Option<LinkTarget>,
+}
+
+/// A position in the document that can be jumped to
+#[derive(Clone, Copy, Debug)]
+pub struct Destination {
+    pub page: usize,
+    pub y: f32,
+}
+
+#[derive(Clone, Debug)]
+struct Fragment {
+    text: String,
+    style: TextStyle,
+    link: Option<LinkTarget>,
+    width: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+struct LayoutLine {
+    /// Words of the line. Fragments of a word are rendered without spaces in between.
+    words: Vec<Vec<Fragment>>,
+    /// The width of the space preceding each word
+    spaces: Vec<f32>,
+    width: f32,
+    font_size: f32,
+}
+
+#[derive(Clone, Debug)]
+struct LinkAnnotation {
+    rect: [f32; 4],
+    target: LinkTarget,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Page {
+    operations: Vec<Operation>,
+    links: Vec<LinkAnnotation>,
+}
+
+#[derive(Clone, Debug)]
+struct PdfImage {
+    name: String,
+    stream: Stream,
+    mask: Option<Stream>,
+}
+
+/// A writer that lays out text and graphics onto pdf pages
+pub struct PDFWriter {
+    page_width: f32,
+    page_height: f32,
+    margin_top: f32,
+    margin_bottom: f32,
+    margin_left: f32,
+    margin_right: f32,
+    display_footer: bool,
+    pages: Vec<Page>,
+    cursor_y: f32,
+    indent: f32,
+    indent_stack: Vec<f32>,
+    styles: Vec<TextStyle>,
+    links: Vec<LinkTarget>,
+    items: Vec<InlineItem>,
+    capture_depth: usize,
+    anchors: HashMap<String, Destination>,
+    images: Vec<PdfImage>,
+    unsupported_chars: BTreeSet<char>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            italic: false,
+            monospace: false,
+            underline: false,
+            strike: false,
+            superscript: false,
+            size: BASE_FONT_SIZE,
+            color: (0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl TextStyle {
+    /// Returns the font used to render the style
+    pub fn font(&self) -> StandardFont {
+        StandardFont::from_style(self.bold, self.italic, self.monospace)
+    }
+
+    /// Returns the font size the text is rendered with
+    pub fn effective_size(&self) -> f32 {
+        if self.superscript {
+            self.size * 0.7
+        } else {
+            self.size
+        }
+    }
+
+    /// Returns the width of the text in this style
+    pub fn text_width(&self, text: &str) -> f32 {
+        self.font().text_width(text, self.effective_size())
+    }
+}
+
+impl PDFWriter {
+    /// Creates a new writer with the page settings of the given config
+    pub fn new(settings: &Settings) -> Self {
+        let pdf = &settings.pdf;
+        let (width, height) = pdf.page_dimensions();
+        let mut width = width.unwrap_or(DEFAULT_PAGE_WIDTH);
+        let mut height = height.unwrap_or(DEFAULT_PAGE_HEIGHT);
+
+        if pdf.landscape {
+            std::mem::swap(&mut width, &mut height);
+        }
+        let mut writer = Self {
+            page_width: width * POINTS_PER_INCH,
+            page_height: height * POINTS_PER_INCH,
+            margin_top: pdf.margin.top.unwrap_or(DEFAULT_MARGIN) * POINTS_PER_INCH,
+            margin_bottom: pdf.margin.bottom.unwrap_or(DEFAULT_MARGIN) * POINTS_PER_INCH,
+            margin_left: pdf.margin.left.unwrap_or(DEFAULT_MARGIN) * POINTS_PER_INCH,
+            margin_right: pdf.margin.right.unwrap_or(DEFAULT_MARGIN) * POINTS_PER_INCH,
+            display_footer: pdf.display_header_footer,
+            pages: Vec::new(),
+            cursor_y: 0.0,
+            indent: 0.0,
+            indent_stack: Vec::new(),
+            styles: vec![TextStyle::default()],
+            links: Vec::new(),
+            items: Vec::new(),
+            capture_depth: 0,
+            anchors: HashMap::new(),
+            images: Vec::new(),
+            unsupported_chars: BTreeSet::new(),
+        };
+        writer.new_page();
+
+        writer
+    }
+
+    /// Returns the current text style
+    pub fn style(&self) -> TextStyle {
+        self.styles.last().cloned().unwrap_or_default()
+    }
+
+    /// Pushes a modified copy of the current style onto the style stack
+    pub fn push_style<F: FnOnce(&mut TextStyle)>(&mut self, modify: F) {
+        let mut style = self.style();
+        modify(&mut style);
+        self.styles.push(style);
+    }
+
+    /// Removes the last pushed style
+    pub fn pop_style(&mut self) {
+        if self.styles.len() > 1 {
+            self.styles.pop();
+        }
+    }
+
+    /// Starts a link. All text written until [end_link] is called links to the target.
+    pub fn begin_link(&mut self, target: LinkTarget) {
+        self.links.push(target);
+    }
+
+    /// Ends the current link
+    pub fn end_link(&mut self) {
+        self.links.pop();
+    }
+
+    /// Writes text with the current style into the inline buffer
+    pub fn write_text<S: ToString>(&mut self, text: S) {
+        self.items.push(InlineItem::Text(Span {
+            text: text.to_string(),
+            style: self.style(),
+            link: self.links.last().cloned(),
+        }))
+    }
+
+    /// Writes a forced line break into the inline buffer
+    pub fn write_line_break(&mut self) {
+        self.items.push(InlineItem::LineBreak);
+    }
+
+    /// Returns if the inline buffer contains items
+    pub fn has_inline_content(&self) -> bool {
+        !self.items.is_empty()
+    }
+
+    /// Lays out the buffered inline content as a paragraph if there is any
+    pub fn flush_inline(&mut self) {
+        if self.has_inline_content() && !self.is_capturing() {
+            self.end_paragraph();
+        }
+    }
+
+    /// Starts capturing inline content for a nested layout like a table cell.
+    /// Block content can't be laid out while capturing.
+    pub fn begin_capture(&mut self) -> Vec<InlineItem> {
+        self.capture_depth += 1;
+        self.take_inline()
+    }
+
+    /// Ends capturing and returns the captured items restoring the previous buffer
+    pub fn end_capture(&mut self, previous: Vec<InlineItem>) -> Vec<InlineItem> {
+        self.capture_depth = self.capture_depth.saturating_sub(1);
+        std::mem::replace(&mut self.items, previous)
+    }
+
+    /// Returns if inline content is currently being captured
+    pub fn is_capturing(&self) -> bool {
+        self.capture_depth > 0
+    }
+
+    /// Takes the buffered inline items
+    pub fn take_inline(&mut self) -> Vec<InlineItem> {
+        std::mem::take(&mut self.items)
+    }
+
+    /// Increases the indentation of the following blocks
+    pub fn indent(&mut self, width: f32) {
+        self.indent_stack.push(width);
+        self.indent += width;
+    }
+
+    /// Reverts the last indentation
+    pub fn dedent(&mut self) {
+        if let Some(width) = self.indent_stack.pop() {
+            self.indent -= width;
+        }
+    }
+
+    /// Indents by the default list indentation
+    pub fn begin_list(&mut self) {
+        self.indent(INDENT_WIDTH);
+    }
+
+    /// Reverts the list indentation
+    pub fn end_list(&mut self) {
+        self.dedent();
+    }
+
+    /// Registers an anchor at the current position
+    pub fn add_anchor(&mut self, key: &str) {
+        let destination = self.current_destination();
+        self.anchors.insert(key.to_string(), destination);
+    }
+
+    /// Adds vertical space
+    pub fn add_space(&mut self, height: f32) {
+        if self.cursor_y - height > self.margin_bottom {
+            self.cursor_y -= height;
+        }
+    }
+
+    /// Lays out the buffered inline content as a paragraph
+    pub fn end_paragraph(&mut self) {
+        let items = self.take_inline();
+        self.layout_items(&items, Align::Left, None);
+        self.add_space(BASE_FONT_SIZE * 0.5);
+    }
+
+    /// Lays out the buffered inline content centered
+    pub fn end_centered(&mut self) {
+        let items = self.take_inline();
+        self.layout_items(&items, Align::Center, None);
+        self.add_space(BASE_FONT_SIZE * 0.5);
+    }
+
+    /// Lays out the buffered inline content as a list item with the given marker
+    pub fn end_list_item(&mut self, marker: &str) {
+        let items = self.take_inline();
+        self.layout_items(&items, Align::Left, Some(marker));
+        self.add_space(BASE_FONT_SIZE * 0.2);
+    }
+
+    /// Starts a heading of the given level.
+    /// The text of the heading has to be written before calling [end_heading].
+    pub fn begin_heading(&mut self, level: u8) {
+        let size = match level {
+            1 => 22.0,
+            2 => 18.0,
+            3 => 15.0,
+            4 => 13.0,
+            5 => 12.0,
+            _ => BASE_FONT_SIZE,
+        };
+        self.push_style(|s| {
+            s.bold = true;
+            s.size = size;
+        });
+    }
+
+    /// Lays out the buffered heading and registers its anchor
+    pub fn end_heading(&mut self, anchor: &str) {
+        let size = self.style().size;
+        self.pop_style();
+        let items = self.take_inline();
+        self.add_space(size * 0.4);
+        // keep the heading on the same page as the first lines of the following content
+        self.ensure_space(size * LINE_SPACING + BASE_FONT_SIZE * LINE_SPACING * 3.0);
+        self.add_anchor(anchor);
+        self.layout_items(&items, Align::Left, None);
+        self.add_space(size * 0.3);
+    }
+
+    /// Draws a horizontal ruler
+    pub fn write_ruler(&mut self) {
+        self.ensure_space(BASE_FONT_SIZE);
+        self.cursor_y -= BASE_FONT_SIZE * 0.5;
+        let x = self.content_left();
+        let y = self.cursor_y;
+        let width = self.content_width();
+        self.draw_line(x, y, x + width, y, 0.5, (0.6, 0.6, 0.6));
+        self.cursor_y -= BASE_FONT_SIZE * 0.5;
+    }
+
+    /// Writes a block of preformatted code
+    pub fn write_code_block(&mut self, code: &str) {
+        let style = TextStyle {
+            monospace: true,
+            size: CODE_FONT_SIZE,
+            ..Default::default()
+        };
+        let padding = CELL_PADDING;
+        let line_height = CODE_FONT_SIZE * LINE_SPACING;
+        let char_width = style.text_width(" ");
+        let max_chars = ((self.content_width() - padding * 2.0) / char_width).max(1.0) as usize;
+        let mut lines = Vec::new();
+
+        for line in code.trim_end_matches('\n').lines() {
+            let chars = line.replace('\t', "    ").chars().collect::<Vec<char>>();
+            if chars.is_empty() {
+                lines.push(String::new());
+            }
+            for chunk in chars.chunks(max_chars) {
+                lines.push(chunk.iter().collect::<String>());
+            }
+        }
+        self.ensure_space(line_height + padding * 2.0);
+        self.fill_rect_band(padding);
+
+        for line in lines {
+            if self.ensure_space(line_height + padding) {
+                self.fill_rect_band(padding);
+            }
+            self.fill_rect_band(line_height);
+            let baseline = self.cursor_y + line_height - CODE_FONT_SIZE;
+            let x = self.content_left() + padding;
+            self.draw_text(x, baseline, &line, &style);
+        }
+        self.fill_rect_band(padding);
+        self.add_space(BASE_FONT_SIZE * 0.5);
+    }
+
+    /// Embeds an image. The data is decoded to determine the dimensions of the image.
+    pub fn write_image(&mut self, data: &[u8]) -> bool {
+        let image = match image::load_from_memory(data) {
+            Ok(image) => image,
+            Err(e) => {
+                log::warn!("Failed to embed image into pdf: {}", e);
+                return false;
+            }
+        };
+        let (pixel_width, pixel_height) = image.dimensions();
+        let mut width = pixel_width as f32 * POINTS_PER_PIXEL;
+        let mut height = pixel_height as f32 * POINTS_PER_PIXEL;
+        let max_width = self.content_width();
+        let max_height = self.page_height - self.margin_top - self.margin_bottom;
+
+        if width > max_width {
+            height *= max_width / width;
+            width = max_width;
+        }
+        if height > max_height {
+            width *= max_height / height;
+            height = max_height;
+        }
+        let name = format!("Im{}", self.images.len() + 1);
+        let rgb = image.to_rgb8();
+        let mut stream = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => pixel_width,
+                "Height" => pixel_height,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+            },
+            rgb.into_raw(),
+        );
+        let _ = stream.compress();
+        let mask = if image.color().has_alpha() {
+            let alpha = image.to_rgba8().pixels().map(|p| p[3]).collect::<Vec<u8>>();
+            let mut mask = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => pixel_width,
+                    "Height" => pixel_height,
+                    "ColorSpace" => "DeviceGray",
+                    "BitsPerComponent" => 8,
+                },
+                alpha,
+            );
+            let _ = mask.compress();
+            Some(mask)
+        } else {
+            None
+        };
+        self.images.push(PdfImage {
+            name: name.clone(),
+            stream,
+            mask,
+        });
+
+        self.ensure_space(height);
+        self.cursor_y -= height;
+        let x = self.content_left() + (self.content_width() - width) / 2.0;
+        let y = self.cursor_y;
+        let page = self.current_page();
+        page.operations.push(Operation::new("q", vec![]));
+        page.operations.push(Operation::new(
+            "cm",
+            vec![
+                width.into(),
+                0.into(),
+                0.into(),
+                height.into(),
+                x.into(),
+                y.into(),
+            ],
+        ));
+        page.operations
+            .push(Operation::new("Do", vec![Object::Name(name.into_bytes())]));
+        page.operations.push(Operation::new("Q", vec![]));
+        self.add_space(BASE_FONT_SIZE * 0.3);
+
+        true
+    }
+
+    /// Lays out a table with the given header and rows. Each cell contains the inline items of its content.
+    pub fn write_table(&mut self, header: Vec<Vec<InlineItem>>, rows: Vec<Vec<Vec<InlineItem>>>) {
+        let column_count = rows
+            .iter()
+            .map(|r| r.len())
+            .chain(std::iter::once(header.len()))
+            .max()
+            .unwrap_or(0);
+        if column_count == 0 {
+            return;
+        }
+        let header = header
+            .into_iter()
+            .map(|cell| Self::with_style(cell, |s| s.bold = true))
+            .collect::<Vec<Vec<InlineItem>>>();
+        let widths = self.column_widths(column_count, &header, &rows);
+
+        self.add_space(BASE_FONT_SIZE * 0.3);
+        self.write_table_row(&header, &widths, true);
+        for row in &rows {
+            let row_height = self.row_height(row, &widths);
+            if self.ensure_space(row_height) {
+                self.write_table_row(&header, &widths, true);
+            }
+            self.write_table_row(row, &widths, false);
+        }
+        self.add_space(BASE_FONT_SIZE * 0.5);
+    }
+
+    /// Starts a quote which is rendered indented with a bar on the left side
+    pub fn begin_quote(&mut self) -> Destination {
+        self.indent(INDENT_WIDTH);
+        self.push_style(|s| s.italic = true);
+
+        self.current_destination()
+    }
+
+    /// Ends a quote that started at the given position
+    pub fn end_quote(&mut self, start: Destination) {
+        self.pop_style();
+        self.dedent();
+        let x = self.content_left() + INDENT_WIDTH / 3.0;
+        let end_page = self.pages.len() - 1;
+        let top = self.page_height - self.margin_top;
+
+        for page_index in start.page..=end_page {
+            let from = if page_index == start.page {
+                start.y
+            } else {
+                top
+            };
+            let to = if page_index == end_page {
+                self.cursor_y
+            } else {
+                self.margin_bottom
+            };
+            let page = &mut self.pages[page_index];
+            Self::push_line(page, x, from, x, to, 2.0, (0.7, 0.7, 0.7));
+        }
+        self.add_space(BASE_FONT_SIZE * 0.5);
+    }
+
+    /// Creates the final pdf document
+    pub fn finish(self, settings: &Settings, outline: &[OutlineEntry]) -> NativePdfResult<Vec<u8>> {
+        if !self.unsupported_chars.is_empty() {
+            log::warn!(
+                "The characters {} aren't supported by the standard pdf fonts and are replaced with \"?\"",
+                self.unsupported_chars.iter().collect::<String>()
+            );
+        }
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let mut fonts = Dictionary::new();
+
+        for font in StandardFont::ALL.iter() {
+            let font_id = doc.add_object(font.to_dictionary());
+            fonts.set(font.resource_name(), font_id);
+        }
+        let mut xobjects = Dictionary::new();
+
+        for image in self.images {
+            let mut stream = image.stream;
+            if let Some(mask) = image.mask {
+                let mask_id = doc.add_object(mask);
+                stream.dict.set("SMask", mask_id);
+            }
+            let image_id = doc.add_object(stream);
+            xobjects.set(image.name, image_id);
+        }
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => fonts,
+            "XObject" => xobjects,
+        });
+        let page_ids = self
+            .pages
+            .iter()
+            .map(|_| doc.new_object_id())
+            .collect::<Vec<ObjectId>>();
+        let destinations = self
+            .anchors
+            .iter()
+            .map(|(key, dest)| {
+                (
+                    key.clone(),
+                    Self::destination_object(page_ids[dest.page], dest.y),
+                )
+            })
+            .collect::<HashMap<String, Object>>();
+        let page_count = self.pages.len();
+
+        for (index, mut page) in self.pages.into_iter().enumerate() {
+            if self.display_footer {
+                let style = TextStyle {
+                    size: 9.0,
+                    ..Default::default()
+                };
+                let text = format!("{}/{}", index + 1, page_count);
+                let x = (self.page_width - style.text_width(&text)) / 2.0;
+                Self::push_text(&mut page, x, self.margin_bottom / 2.0, &text, &style);
+            }
+            let content = Content {
+                operations: page.operations,
+            };
+            let mut content_stream = Stream::new(Dictionary::new(), content.encode()?);
+            let _ = content_stream.compress();
+            let content_id = doc.add_object(content_stream);
+            let annotations = page
+                .links
+                .iter()
+                .filter_map(|link| {
+                    let rect = link
+                        .rect
+                        .iter()
+                        .map(|v| (*v).into())
+                        .collect::<Vec<Object>>();
+                    let mut annotation = dictionary! {
+                        "Type" => "Annot",
+                        "Subtype" => "Link",
+                        "Rect" => rect,
+                        "Border" => vec![0.into(), 0.into(), 0.into()],
+                    };
+                    match &link.target {
+                        LinkTarget::Url(url) => annotation.set(
+                            "A",
+                            dictionary! {
+                                "S" => "URI",
+                                "URI" => Object::string_literal(url.as_bytes().to_vec()),
+                            },
+                        ),
+                        LinkTarget::Anchor(key) => {
+                            annotation.set("Dest", destinations.get(key)?.clone())
+                        }
+                    }
+                    Some(Object::Dictionary(annotation))
+                })
+                .collect::<Vec<Object>>();
+            let page_dict = dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), self.page_width.into(), self.page_height.into()],
+                "Contents" => content_id,
+                "Resources" => resources_id,
+                "Annots" => annotations,
+            };
+            doc.objects
+                .insert(page_ids[index], Object::Dictionary(page_dict));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => page_ids.iter().map(|id| (*id).into()).collect::<Vec<Object>>(),
+                "Count" => page_count as u32,
+            }),
+        );
+        let fallback = Self::destination_object(page_ids[0], self.page_height);
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Lang" => Object::string_literal(settings.metadata.language.as_bytes().to_vec()),
+        };
+        if let Some(outline_id) = write_outline(&mut doc, outline, &destinations, fallback) {
+            catalog.set("Outlines", outline_id);
+            catalog.set("PageMode", "UseOutlines");
+        }
+        let catalog_id = doc.add_object(catalog);
+        doc.trailer.set("Root", catalog_id);
+        apply_document_info(&mut doc, &settings.metadata);
+
+        let mut data = Vec::new();
+        doc.save_to(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// Returns the destination array pointing to the given position on a page
+    fn destination_object(page_id: ObjectId, y: f32) -> Object {
+        Object::Array(vec![
+            page_id.into(),
+            "XYZ".into(),
+            Object::Null,
+            y.into(),
+            Object::Null,
+        ])
+    }
+
+    /// Returns the destination of the current position
+    fn current_destination(&self) -> Destination {
+        Destination {
+            page: self.pages.len() - 1,
+            y: self.cursor_y,
+        }
+    }
+
+    fn current_page(&mut self) -> &mut Page {
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        self.pages.last_mut().unwrap()
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page::default());
+        self.cursor_y = self.page_height - self.margin_top;
+    }
+
+    /// Starts a new page if the remaining space is smaller than the given height.
+    /// Returns true if a new page was started.
+    fn ensure_space(&mut self, height: f32) -> bool {
+        let at_page_start = self.cursor_y >= self.page_height - self.margin_top;
+
+        if self.cursor_y - height < self.margin_bottom && !at_page_start {
+            self.new_page();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn content_left(&self) -> f32 {
+        self.margin_left + self.indent
+    }
+
+    fn content_width(&self) -> f32 {
+        (self.page_width - self.margin_left - self.margin_right - self.indent).max(INDENT_WIDTH)
+    }
+
+    /// Returns a copy of the items with a modified style
+    fn with_style<F: Fn(&mut TextStyle)>(items: Vec<InlineItem>, modify: F) -> Vec<InlineItem> {
+        items
+            .into_iter()
+            .map(|item| match item {
+                InlineItem::Text(mut span) => {
+                    modify(&mut span.style);
+                    InlineItem::Text(span)
+                }
+                other => other,
+            })
+            .collect()
+    }
+
+    /// Fills a gray band of the given height over the content width and moves the cursor below it
+    fn fill_rect_band(&mut self, height: f32) {
+        let x = self.content_left();
+        let width = self.content_width();
+        self.cursor_y -= height;
+        let y = self.cursor_y;
+        let page = self.current_page();
+        page.operations.push(Operation::new(
+            "rg",
+            vec![0.95.into(), 0.95.into(), 0.95.into()],
+        ));
+        page.operations.push(Operation::new(
+            "re",
+            vec![x.into(), y.into(), width.into(), height.into()],
+        ));
+        page.operations.push(Operation::new("f", vec![]));
+    }
+
+    /// Lays out inline items within the current content width
+    fn layout_items(&mut self, items: &[InlineItem], align: Align, marker: Option<&str>) {
+        let lines = Self::break_lines(items, self.content_width());
+        let left = self.content_left();
+        let width = self.content_width();
+
+        for (index, line) in lines.iter().enumerate() {
+            let line_height = line.font_size * LINE_SPACING;
+            self.ensure_space(line_height);
+            let baseline = self.cursor_y - line.font_size;
+            let mut x = match align {
+                Align::Left => left,
+                Align::Center => left + (width - line.width).max(0.0) / 2.0,
+            };
+            if index == 0 {
+                if let Some(marker) = marker {
+                    let style = TextStyle::default();
+                    let marker_x = left - style.text_width(marker) - 4.0;
+                    self.draw_text(marker_x, baseline, marker, &style);
+                }
+            }
+            for (word, space) in line.words.iter().zip(line.spaces.iter()) {
+                x += space;
+                for fragment in word {
+                    self.draw_fragment(x, baseline, fragment);
+                    x += fragment.width;
+                }
+            }
+            self.cursor_y -= line_height;
+        }
+    }
+
+    /// Breaks the inline items into lines that fit into the given width
+    fn break_lines(items: &[InlineItem], max_width: f32) -> Vec<LayoutLine> {
+        let mut lines = Vec::new();
+        let mut line = LayoutLine::default();
+        let mut word: Vec<Fragment> = Vec::new();
+        let mut pending_space = 0.0;
+
+        for item in items {
+            match item {
+                InlineItem::LineBreak => {
+                    Self::push_word(&mut lines, &mut line, &mut word, pending_space, max_width);
+                    pending_space = 0.0;
+                    if line.font_size == 0.0 {
+                        line.font_size = BASE_FONT_SIZE;
+                    }
+                    lines.push(std::mem::take(&mut line));
+                }
+                InlineItem::Text(span) => {
+                    let mut current = String::new();
+                    for c in span.text.chars() {
+                        if c.is_whitespace() {
+                            if !current.is_empty() {
+                                word.push(Self::create_fragment(&current, span));
+                                current.clear();
+                            }
+                            if !word.is_empty() {
+                                Self::push_word(
+                                    &mut lines,
+                                    &mut line,
+                                    &mut word,
+                                    pending_space,
+                                    max_width,
+                                );
+                            }
+                            pending_space = span.style.text_width(" ");
+                        } else {
+                            current.push(c);
+                        }
+                    }
+                    if !current.is_empty() {
+                        word.push(Self::create_fragment(&current, span));
+                    }
+                }
+            }
+        }
+        Self::push_word(&mut lines, &mut line, &mut word, pending_space, max_width);
+        if !line.words.is_empty() {
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    fn create_fragment(text: &str, span: &Span) -> Fragment {
+        Fragment {
+            text: text.to_string(),
+            width: span.style.text_width(text),
+            style: span.style.clone(),
+            link: span.link.clone(),
+        }
+    }
+
+    /// Adds a word to the line starting a new line if the word doesn't fit
+    fn push_word(
+        lines: &mut Vec<LayoutLine>,
+        line: &mut LayoutLine,
+        word: &mut Vec<Fragment>,
+        space: f32,
+        max_width: f32,
+    ) {
+        if word.is_empty() {
+            return;
+        }
+        let word_width: f32 = word.iter().map(|f| f.width).sum();
+        let space = if line.words.is_empty() { 0.0 } else { space };
+
+        if line.width + space + word_width > max_width && !line.words.is_empty() {
+            lines.push(std::mem::take(line));
+            return Self::push_word(lines, line, word, 0.0, max_width);
+        }
+        if word_width > max_width {
+            // the word is longer than a whole line and needs to be split
+            let fragments = std::mem::take(word);
+            for fragment in fragments {
+                let mut current = String::new();
+                for c in fragment.text.chars() {
+                    let mut next = current.clone();
+                    next.push(c);
+                    if line.width + fragment.style.text_width(&next) > max_width
+                        && !current.is_empty()
+                    {
+                        let piece = Fragment {
+                            width: fragment.style.text_width(&current),
+                            text: std::mem::take(&mut current),
+                            ..fragment.clone()
+                        };
+                        Self::append_word(line, vec![piece], 0.0);
+                        lines.push(std::mem::take(line));
+                    }
+                    current.push(c);
+                }
+                if !current.is_empty() {
+                    let piece = Fragment {
+                        width: fragment.style.text_width(&current),
+                        text: current,
+                        ..fragment.clone()
+                    };
+                    Self::append_word(line, vec![piece], 0.0);
+                }
+            }
+            return;
+        }
+        Self::append_word(line, std::mem::take(word), space);
+    }
+
+    fn append_word(line: &mut LayoutLine, word: Vec<Fragment>, space: f32) {
+        for fragment in &word {
+            line.width += fragment.width;
+            line.font_size = line.font_size.max(fragment.style.size);
+        }
+        line.width += space;
+        line.spaces.push(space);
+        line.words.push(word);
+    }
+
+    /// Calculates the widths of the table columns
+    fn column_widths(
+        &self,
+        column_count: usize,
+        header: &[Vec<InlineItem>],
+        rows: &[Vec<Vec<InlineItem>>],
+    ) -> Vec<f32> {
+        let mut natural = vec![0f32; column_count];
+        let mut minimum = vec![0f32; column_count];
+
+        for row in std::iter::once(header).chain(rows.iter().map(|r| r.as_slice())) {
+            for (index, cell) in row.iter().enumerate() {
+                let lines = Self::break_lines(cell, f32::MAX);
+                let width = lines.iter().map(|l| l.width).fold(0.0, f32::max);
+                let longest_word = lines
+                    .iter()
+                    .flat_map(|l| l.words.iter())
+                    .map(|w| w.iter().map(|f| f.width).sum::<f32>())
+                    .fold(0.0, f32::max);
+                natural[index] = natural[index].max(width + CELL_PADDING * 2.0);
+                minimum[index] = minimum[index].max(longest_word + CELL_PADDING * 2.0);
+            }
+        }
+        let available = self.content_width();
+        let natural_sum: f32 = natural.iter().sum();
+
+        if natural_sum <= available {
+            return natural;
+        }
+        let minimum_sum: f32 = minimum.iter().sum();
+
+        if minimum_sum >= available {
+            return minimum
+                .iter()
+                .map(|w| w * available / minimum_sum)
+                .collect();
+        }
+        let remaining = available - minimum_sum;
+        let flexible: f32 = natural_sum - minimum_sum;
+
+        minimum
+            .iter()
+            .zip(natural.iter())
+            .map(|(min, nat)| min + (nat - min) * remaining / flexible)
+            .collect()
+    }
+
+    /// Returns the height of a table row
+    fn row_height(&self, row: &[Vec<InlineItem>], widths: &[f32]) -> f32 {
+        row.iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| {
+                Self::break_lines(cell, width - CELL_PADDING * 2.0)
+                    .iter()
+                    .map(|l| l.font_size * LINE_SPACING)
+                    .sum::<f32>()
+            })
+            .fold(BASE_FONT_SIZE * LINE_SPACING, f32::max)
+            + CELL_PADDING * 2.0
+    }
+
+    fn write_table_row(&mut self, row: &[Vec<InlineItem>], widths: &[f32], is_header: bool) {
+        let height = self.row_height(row, widths);
+        self.ensure_space(height);
+        let top = self.cursor_y;
+        let mut x = self.content_left();
+        let total_width: f32 = widths.iter().sum();
+
+        if is_header {
+            let left = x;
+            let page = self.current_page();
+            page.operations.push(Operation::new(
+                "rg",
+                vec![0.93.into(), 0.93.into(), 0.93.into()],
+            ));
+            page.operations.push(Operation::new(
+                "re",
+                vec![
+                    left.into(),
+                    (top - height).into(),
+                    total_width.into(),
+                    height.into(),
+                ],
+            ));
+            page.operations.push(Operation::new("f", vec![]));
+        }
+        for (index, width) in widths.iter().enumerate() {
+            if let Some(cell) = row.get(index) {
+                let lines = Self::break_lines(cell, width - CELL_PADDING * 2.0);
+                let mut y = top - CELL_PADDING;
+                for line in lines {
+                    let baseline = y - line.font_size;
+                    let mut line_x = x + CELL_PADDING;
+                    for (word, space) in line.words.iter().zip(line.spaces.iter()) {
+                        line_x += space;
+                        for fragment in word {
+                            self.draw_fragment(line_x, baseline, fragment);
+                            line_x += fragment.width;
+                        }
+                    }
+                    y -= line.font_size * LINE_SPACING;
+                }
+            }
+            let page = self.current_page();
+            page.operations.push(Operation::new(
+                "RG",
+                vec![0.7.into(), 0.7.into(), 0.7.into()],
+            ));
+            page.operations.push(Operation::new("w", vec![0.5.into()]));
+            page.operations.push(Operation::new(
+                "re",
+                vec![
+                    x.into(),
+                    (top - height).into(),
+                    (*width).into(),
+                    height.into(),
+                ],
+            ));
+            page.operations.push(Operation::new("S", vec![]));
+            x += width;
+        }
+        self.cursor_y = top - height;
+    }
+
+    /// Draws a fragment of text including its decorations and links
+    fn draw_fragment(&mut self, x: f32, baseline: f32, fragment: &Fragment) {
+        let style = &fragment.style;
+        let baseline = if style.superscript {
+            baseline + style.size * 0.35
+        } else {
+            baseline
+        };
+        self.draw_text(x, baseline, &fragment.text, style);
+        let size = style.effective_size();
+
+        if style.underline {
+            self.draw_line(
+                x,
+                baseline - size * 0.15,
+                x + fragment.width,
+                baseline - size * 0.15,
+                size * 0.05,
+                style.color,
+            );
+        }
+        if style.strike {
+            self.draw_line(
+                x,
+                baseline + size * 0.3,
+                x + fragment.width,
+                baseline + size * 0.3,
+                size * 0.05,
+                style.color,
+            );
+        }
+        if let Some(target) = &fragment.link {
+            let rect = [
+                x,
+                baseline - size * 0.25,
+                x + fragment.width,
+                baseline + size * 0.9,
+            ];
+            self.current_page().links.push(LinkAnnotation {
+                rect,
+                target: target.clone(),
+            })
+        }
+    }
+
+    fn draw_text(&mut self, x: f32, baseline: f32, text: &str, style: &TextStyle) {
+        self.unsupported_chars
+            .extend(text.chars().filter(|c| !is_win_ansi(*c)));
+        let page = self.current_page();
+        Self::push_text(page, x, baseline, text, style);
+    }
+
+    fn push_text(page: &mut Page, x: f32, baseline: f32, text: &str, style: &TextStyle) {
+        let (r, g, b) = style.color;
+        page.operations.push(Operation::new("BT", vec![]));
+        page.operations.push(Operation::new(
+            "Tf",
+            vec![
+                style.font().resource_name().into(),
+                style.effective_size().into(),
+            ],
+        ));
+        page.operations
+            .push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
+        page.operations
+            .push(Operation::new("Td", vec![x.into(), baseline.into()]));
+        page.operations.push(Operation::new(
+            "Tj",
+            vec![Object::string_literal(encode_win_ansi(text))],
+        ));
+        page.operations.push(Operation::new("ET", vec![]));
+    }
+
+    fn draw_line(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        width: f32,
+        color: (f32, f32, f32),
+    ) {
+        let page = self.current_page();
+        Self::push_line(page, x1, y1, x2, y2, width, color);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push_line(
+        page: &mut Page,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        width: f32,
+        color: (f32, f32, f32),
+    ) {
+        let (r, g, b) = color;
+        page.operations
+            .push(Operation::new("RG", vec![r.into(), g.into(), b.into()]));
+        page.operations
+            .push(Operation::new("w", vec![width.into()]));
+        page.operations
+            .push(Operation::new("m", vec![x1.into(), y1.into()]));
+        page.operations
+            .push(Operation::new("l", vec![x2.into(), y2.into()]));
+        page.operations.push(Operation::new("S", vec![]));
+    }
+}
@@ -0,0 +1,40 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+
+pub type NativePdfResult<T> = Result<T, NativePdfError>;
+
+#[derive(Debug)]
+pub enum NativePdfError {
+    IoError(io::Error),
+    PdfError(lopdf::Error),
+}
+
+impl Display for NativePdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NativePdfError::IoError(e) => write!(f, "IO Error: {}", e),
+            NativePdfError::PdfError(e) => write!(f, "PDF Error: {}", e),
+        }
+    }
+}
+
+impl Error for NativePdfError {}
+
+impl From<io::Error> for NativePdfError {
+    fn from(other: io::Error) -> Self {
+        Self::IoError(other)
+    }
+}
+
+impl From<lopdf::Error> for NativePdfError {
+    fn from(other: lopdf::Error) -> Self {
+        Self::PdfError(other)
+    }
+}
@@ -0,0 +1,694 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::*;
+use crate::format::native_pdf::pdf_writer::{LinkTarget, PDFWriter};
+use crate::format::native_pdf::result::NativePdfResult;
+use crate::format::PlaceholderTemplate;
+use crate::references::glossary::{GlossaryDisplay, GlossaryReference};
+use crate::references::templates::{Template, TemplateVariable};
+
+const LINK_COLOR: (f32, f32, f32) = (0.0, 0.3, 0.7);
+
+pub trait ToPdf {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()>;
+}
+
+impl ToPdf for Element {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        match self {
+            Element::Block(block) => {
+                writer.flush_inline();
+                block.to_pdf(writer)
+            }
+            Element::Inline(inline) => inline.to_pdf(writer),
+            Element::Line(line) => line.to_pdf(writer),
+        }
+    }
+}
+
+impl ToPdf for Line {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        match self {
+            Line::Text(text) => text.to_pdf(writer),
+            Line::Ruler(ruler) => ruler.to_pdf(writer),
+            Line::RefLink(anchor) => anchor.to_pdf(writer),
+            Line::Centered(centered) => centered.to_pdf(writer),
+            Line::Anchor(a) => a.to_pdf(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToPdf for Inline {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        match self {
+            Inline::Url(url) => url.to_pdf(writer),
+            Inline::Monospace(mono) => mono.to_pdf(writer),
+            Inline::Striked(striked) => striked.to_pdf(writer),
+            Inline::Plain(plain) => plain.to_pdf(writer),
+            Inline::Italic(italic) => italic.to_pdf(writer),
+            Inline::Underlined(under) => under.to_pdf(writer),
+            Inline::Bold(bold) => bold.to_pdf(writer),
+            Inline::Image(img) => img.to_pdf(writer),
+            Inline::Placeholder(placeholder) => placeholder.read().unwrap().to_pdf(writer),
+            Inline::Superscript(superscript) => superscript.to_pdf(writer),
+            Inline::Checkbox(checkbox) => checkbox.to_pdf(writer),
+            Inline::Emoji(emoji) => emoji.to_pdf(writer),
+            Inline::Colored(colored) => colored.to_pdf(writer),
+            Inline::BibReference(bibref) => bibref.read().unwrap().to_pdf(writer),
+            Inline::TemplateVar(var) => var.read().unwrap().to_pdf(writer),
+            Inline::Math(m) => m.to_pdf(writer),
+            Inline::LineBreak => {
+                writer.write_line_break();
+                Ok(())
+            }
+            Inline::CharacterCode(code) => code.to_pdf(writer),
+            Inline::GlossaryReference(gloss) => gloss.lock().to_pdf(writer),
+            Inline::Arrow(a) => a.to_pdf(writer),
+            Inline::Anchor(a) => a.to_pdf(writer),
+        }
+    }
+}
+
+impl ToPdf for Block {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        match self {
+            Block::Paragraph(para) => para.to_pdf(writer),
+            Block::List(list) => list.to_pdf(writer),
+            Block::Table(table) => table.to_pdf(writer),
+            Block::CodeBlock(code) => code.to_pdf(writer),
+            Block::Quote(quote) => quote.to_pdf(writer),
+            Block::Section(section) => section.to_pdf(writer),
+            Block::Import(import) => import.to_pdf(writer),
+            Block::Placeholder(placeholder) => placeholder.read().unwrap().to_pdf(writer),
+            Block::MathBlock(m) => m.to_pdf(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToPdf for MetadataValue {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        match self {
+            MetadataValue::Placeholder(ph) => ph.read().unwrap().to_pdf(writer),
+            MetadataValue::Template(t) => t.to_pdf(writer),
+            other => {
+                writer.write_text(other.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ToPdf for Document {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        for element in &self.elements {
+            element.to_pdf(writer)?;
+        }
+        writer.flush_inline();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Math {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.monospace = true);
+        writer.write_text(self.source.trim());
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for MathBlock {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.monospace = true);
+        for line in self.source.trim().lines() {
+            writer.write_text(line);
+            writer.write_line_break();
+        }
+        writer.pop_style();
+        writer.end_centered();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Import {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if let Some(document) = &self.anchor.read().unwrap().document {
+            document.to_pdf(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Section {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        self.header.to_pdf(writer)?;
+        for element in &self.elements {
+            element.to_pdf(writer)?;
+        }
+        writer.flush_inline();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Header {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.flush_inline();
+        writer.begin_heading(self.size);
+        self.line.to_pdf(writer)?;
+        writer.end_heading(&self.anchor);
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Paragraph {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if let Some(first) = self.elements.first() {
+            first.to_pdf(writer)?;
+        }
+        if self.elements.len() > 1 {
+            for element in &self.elements[1..] {
+                writer.write_text(" ");
+                element.to_pdf(writer)?;
+            }
+        }
+        writer.flush_inline();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for List {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.flush_inline();
+        write_list_items(&self.items, self.ordered, 0, writer)?;
+        writer.add_space(4.0);
+
+        Ok(())
+    }
+}
+
+/// Writes the items of a list with their markers and nested lists
+fn write_list_items(
+    items: &[ListItem],
+    ordered: bool,
+    depth: usize,
+    writer: &mut PDFWriter,
+) -> NativePdfResult<()> {
+    writer.begin_list();
+    for (index, item) in items.iter().enumerate() {
+        let marker = if ordered {
+            format!("{}.", index + 1)
+        } else if depth % 2 == 0 {
+            "•".to_string()
+        } else {
+            "–".to_string()
+        };
+        item.text.to_pdf(writer)?;
+        writer.end_list_item(&marker);
+
+        if let Some(first) = item.children.first() {
+            write_list_items(&item.children, first.ordered, depth + 1, writer)?;
+        }
+    }
+    writer.end_list();
+
+    Ok(())
+}
+
+impl ToPdf for Table {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.flush_inline();
+        let header = self
+            .header
+            .cells
+            .iter()
+            .map(|cell| capture_line(&cell.text, writer))
+            .collect::<NativePdfResult<Vec<_>>>()?;
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.cells
+                    .iter()
+                    .map(|cell| capture_line(&cell.text, writer))
+                    .collect::<NativePdfResult<Vec<_>>>()
+            })
+            .collect::<NativePdfResult<Vec<_>>>()?;
+        writer.write_table(header, rows);
+
+        Ok(())
+    }
+}
+
+/// Returns the inline items of a line without laying them out
+fn capture_line(
+    line: &Line,
+    writer: &mut PDFWriter,
+) -> NativePdfResult<Vec<crate::format::native_pdf::pdf_writer::InlineItem>> {
+    let previous = writer.begin_capture();
+    let result = line.to_pdf(writer);
+    let items = writer.end_capture(previous);
+    result?;
+
+    Ok(items)
+}
+
+impl ToPdf for CodeBlock {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.flush_inline();
+        if let Some(anchor) = self.get_anchor() {
+            writer.add_anchor(&anchor);
+        }
+        if let Some(file_name) = self.get_file_name() {
+            writer.push_style(|s| {
+                s.monospace = true;
+                s.bold = true;
+            });
+            writer.write_text(file_name);
+            writer.pop_style();
+            writer.flush_inline();
+        }
+        writer.write_code_block(&self.get_numbered_lines().join("\n"));
+
+        if let Some(caption) = self.get_caption() {
+            writer.push_style(|s| s.italic = true);
+            writer.write_text(caption);
+            writer.pop_style();
+            writer.end_centered();
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Quote {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.flush_inline();
+        let start = writer.begin_quote();
+        for line in &self.text {
+            line.to_pdf(writer)?;
+            writer.write_line_break();
+        }
+        if let Some(meta) = &self.metadata {
+            meta.to_pdf(writer)?;
+        }
+        writer.end_paragraph();
+        writer.end_quote(start);
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Ruler {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if !writer.is_capturing() {
+            writer.flush_inline();
+            writer.write_ruler();
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for TextLine {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        for text in &self.subtext {
+            text.to_pdf(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Image {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if !writer.is_capturing() {
+            if let Some(content) = self.get_content() {
+                writer.flush_inline();
+                if writer.write_image(&content) {
+                    if let Some(description) = &self.url.description {
+                        writer.push_style(|s| s.italic = true);
+                        for item in description {
+                            item.to_pdf(writer)?;
+                            writer.write_text(" ");
+                        }
+                        writer.pop_style();
+                        writer.end_centered();
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        writer.begin_link(LinkTarget::Url(self.url.url.clone()));
+        writer.push_style(|s| s.color = LINK_COLOR);
+        if let Some(description) = &self.url.description {
+            for item in description {
+                item.to_pdf(writer)?;
+            }
+        } else {
+            writer.write_text(&self.url.url);
+        }
+        writer.pop_style();
+        writer.end_link();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for BoldText {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.bold = true);
+        for element in &self.value {
+            element.to_pdf(writer)?;
+        }
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for UnderlinedText {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.underline = true);
+        for element in &self.value {
+            element.to_pdf(writer)?;
+        }
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for ItalicText {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.italic = true);
+        for element in &self.value {
+            element.to_pdf(writer)?;
+        }
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for StrikedText {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.strike = true);
+        for element in &self.value {
+            element.to_pdf(writer)?;
+        }
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for SuperscriptText {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.superscript = true);
+        for element in &self.value {
+            element.to_pdf(writer)?;
+        }
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for MonospaceText {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.monospace = true);
+        writer.write_text(&self.value);
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Url {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.begin_link(LinkTarget::Url(self.url.clone()));
+        writer.push_style(|s| s.color = LINK_COLOR);
+        if let Some(description) = &self.description {
+            for desc in description {
+                desc.to_pdf(writer)?;
+            }
+        } else {
+            writer.write_text(&self.url);
+        }
+        writer.pop_style();
+        writer.end_link();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for PlainText {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.write_text(&self.value);
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Placeholder {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if let Some(value) = &self.value {
+            value.to_pdf(writer)
+        } else {
+            log::debug!("Unknown placeholder [[{}]]", self.name.clone());
+            writer.write_text(format!("[[{}]]", self.name));
+
+            Ok(())
+        }
+    }
+}
+
+impl ToPdf for RefLink {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.begin_link(LinkTarget::Anchor(self.reference.clone()));
+        self.description.to_pdf(writer)?;
+        writer.end_link();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for InlineMetadata {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if let Some(MetadataValue::String(format)) = self.data.get("display") {
+            let mut template = PlaceholderTemplate::new(format.clone());
+            self.data
+                .iter()
+                .for_each(|(k, v)| template.add_replacement(k, &v.to_string()));
+
+            writer.write_text(template.render());
+        } else {
+            for (k, v) in &self.data {
+                writer.write_text(format!("{}={},", k, v.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Centered {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if writer.is_capturing() {
+            return self.line.to_pdf(writer);
+        }
+        writer.flush_inline();
+        self.line.to_pdf(writer)?;
+        writer.end_centered();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Checkbox {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.push_style(|s| s.monospace = true);
+        if self.value {
+            writer.write_text("[x]");
+        } else {
+            writer.write_text("[ ]");
+        }
+        writer.pop_style();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Emoji {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        // the standard fonts don't contain emoji glyphs
+        writer.write_text(format!(":{}:", self.name));
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Colored {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if let Some(color) = parse_color(&self.color) {
+            writer.push_style(|s| s.color = color);
+            self.value.to_pdf(writer)?;
+            writer.pop_style();
+
+            Ok(())
+        } else {
+            self.value.to_pdf(writer)
+        }
+    }
+}
+
+/// Parses a css hex color or one of the basic named colors
+fn parse_color(color: &str) -> Option<(f32, f32, f32)> {
+    let color = color.trim().to_lowercase();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        let hex = if hex.len() == 3 {
+            hex.chars().flat_map(|c| vec![c, c]).collect::<String>()
+        } else {
+            hex.to_string()
+        };
+        if hex.len() != 6 {
+            return None;
+        }
+        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+
+        return Some((r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0));
+    }
+    let rgb: (u8, u8, u8) = match color.as_str() {
+        "black" => (0, 0, 0),
+        "white" => (255, 255, 255),
+        "red" => (255, 0, 0),
+        "green" => (0, 128, 0),
+        "lime" => (0, 255, 0),
+        "blue" => (0, 0, 255),
+        "yellow" => (255, 255, 0),
+        "orange" => (255, 165, 0),
+        "purple" => (128, 0, 128),
+        "magenta" | "fuchsia" => (255, 0, 255),
+        "cyan" | "aqua" => (0, 255, 255),
+        "gray" | "grey" => (128, 128, 128),
+        "brown" => (165, 42, 42),
+        "pink" => (255, 192, 203),
+        "navy" => (0, 0, 128),
+        "teal" => (0, 128, 128),
+        "maroon" => (128, 0, 0),
+        "olive" => (128, 128, 0),
+        _ => return None,
+    };
+
+    Some((
+        rgb.0 as f32 / 255.0,
+        rgb.1 as f32 / 255.0,
+        rgb.2 as f32 / 255.0,
+    ))
+}
+
+impl ToPdf for BibReference {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.begin_link(LinkTarget::Anchor(self.key.clone()));
+        writer.push_style(|s| {
+            s.superscript = true;
+            s.color = LINK_COLOR;
+        });
+        writer.write_text(self.get_formatted());
+        writer.pop_style();
+        writer.end_link();
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Template {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        for element in &self.text {
+            element.to_pdf(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for TemplateVariable {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if let Some(value) = &self.value {
+            writer.write_text(&self.prefix);
+            value.to_pdf(writer)?;
+            writer.write_text(&self.suffix);
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for CharacterCode {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        let encoded = format!("&{};", self.code);
+        let decoded = htmlescape::decode_html(&encoded).unwrap_or(encoded);
+        writer.write_text(decoded);
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Anchor {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        writer.add_anchor(&self.key);
+
+        self.inner.to_pdf(writer)
+    }
+}
+
+impl ToPdf for GlossaryReference {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        if let Some(entry) = &self.entry {
+            let entry = entry.lock();
+            writer.begin_link(LinkTarget::Anchor(self.short.clone()));
+            match self.display {
+                GlossaryDisplay::Short => writer.write_text(&entry.short),
+                GlossaryDisplay::Long => writer.write_text(&entry.long),
+            }
+            writer.end_link();
+        } else {
+            writer.write_text(format!("~{}", self.short));
+        }
+
+        Ok(())
+    }
+}
+
+impl ToPdf for Arrow {
+    fn to_pdf(&self, writer: &mut PDFWriter) -> NativePdfResult<()> {
+        // arrows aren't part of the WinAnsiEncoding of the standard fonts
+        let arrow = match self {
+            Arrow::RightArrow => "->",
+            Arrow::LeftArrow => "<-",
+            Arrow::LeftRightArrow => "<->",
+            Arrow::BigRightArrow => "=>",
+            Arrow::BigLeftArrow => "<=",
+            Arrow::BigLeftRightArrow => "<=>",
+        };
+        writer.write_text(arrow);
+
+        Ok(())
+    }
+}
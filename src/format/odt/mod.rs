@@ -0,0 +1,380 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::Document;
+use crate::format::odt::odt_writer::OdtWriter;
+use crate::format::odt::result::OdtResult;
+use crate::format::odt::to_odt::ToOdt;
+use crate::format::page_setup::PageSetup;
+use crate::format::style::{get_colors_for_theme, CodeStyle, ThemeColors};
+use crate::format::{RenderResult, Renderer};
+use crate::settings::metadata_settings::MetadataSettings;
+use htmlescape::{encode_attribute, encode_minimal};
+use std::io::{Cursor, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+pub mod odt_writer;
+pub mod result;
+pub mod to_odt;
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.text";
+
+const OFFICE_NAMESPACES: &str =
+    "xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" \
+    xmlns:style=\"urn:oasis:names:tc:opendocument:xmlns:style:1.0\" \
+    xmlns:text=\"urn:oasis:names:tc:opendocument:xmlns:text:1.0\" \
+    xmlns:table=\"urn:oasis:names:tc:opendocument:xmlns:table:1.0\" \
+    xmlns:draw=\"urn:oasis:names:tc:opendocument:xmlns:drawing:1.0\" \
+    xmlns:fo=\"urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0\" \
+    xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+    xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+    xmlns:meta=\"urn:oasis:names:tc:opendocument:xmlns:meta:1.0\" \
+    xmlns:svg=\"urn:oasis:names:tc:opendocument:xmlns:svg-compatible:1.0\" \
+    office:version=\"1.2\"";
+
+const FONT_FACES: &str = "<office:font-face-decls>\
+    <style:font-face style:name=\"Liberation Serif\" svg:font-family=\"'Liberation Serif'\" \
+    style:font-family-generic=\"roman\" style:font-pitch=\"variable\"/>\
+    <style:font-face style:name=\"Liberation Sans\" svg:font-family=\"'Liberation Sans'\" \
+    style:font-family-generic=\"swiss\" style:font-pitch=\"variable\"/>\
+    <style:font-face style:name=\"Liberation Mono\" svg:font-family=\"'Liberation Mono'\" \
+    style:font-family-generic=\"modern\" style:font-pitch=\"fixed\"/>\
+    </office:font-face-decls>";
+
+/// The font sizes of the heading levels in points
+const HEADING_SIZES: [&str; 6] = ["20pt", "16pt", "14pt", "12pt", "11pt", "11pt"];
+
+/// Renders the document to an OpenDocument text and returns the bytes of the archive
+pub fn render_to_odt(document: Document) -> OdtResult<Vec<u8>> {
+    let settings = document.config.lock().clone();
+    let page = PageSetup::new(&settings.pdf);
+    let colors = get_colors_for_theme(settings.style.theme.clone());
+
+    let code_style = CodeStyle::load(&settings, &document.get_root_dir());
+    let mut writer = OdtWriter::new(page.clone(), code_style);
+
+    log::info!("Rendering odt document...");
+    document.to_odt(&mut writer)?;
+    let body = writer.take_body();
+
+    log::info!("Packaging odt...");
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    // the mimetype has to be the first uncompressed file of the archive
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+    zip.start_file("META-INF/manifest.xml", deflated)?;
+    zip.write_all(create_manifest(&writer).as_bytes())?;
+    zip.start_file("meta.xml", deflated)?;
+    zip.write_all(create_meta(&settings.metadata).as_bytes())?;
+    zip.start_file("styles.xml", deflated)?;
+    zip.write_all(create_styles(&colors, &page).as_bytes())?;
+    zip.start_file("content.xml", deflated)?;
+    zip.write_all(create_content(&writer, &body, &colors, &page).as_bytes())?;
+
+    for image in writer.images() {
+        zip.start_file(format!("Pictures/{}", image.name), stored)?;
+        zip.write_all(&image.data)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Renders documents to OpenDocument texts
+pub struct OdtRenderer;
+
+impl Renderer for OdtRenderer {
+    fn name(&self) -> &str {
+        "odt"
+    }
+
+    fn extension(&self) -> &str {
+        "odt"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        mut writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        writer.write_all(&render_to_odt(document)?)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Creates the manifest listing all files of the package
+fn create_manifest(writer: &OdtWriter) -> String {
+    let mut manifest = String::new();
+    manifest.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    manifest.push_str(
+        "<manifest:manifest xmlns:manifest=\"urn:oasis:names:tc:opendocument:xmlns:manifest:1.0\" \
+        manifest:version=\"1.2\">",
+    );
+    manifest.push_str(&format!(
+        "<manifest:file-entry manifest:full-path=\"/\" manifest:version=\"1.2\" \
+        manifest:media-type=\"{}\"/>",
+        MIMETYPE
+    ));
+    for part in &["content.xml", "styles.xml", "meta.xml"] {
+        manifest.push_str(&format!(
+            "<manifest:file-entry manifest:full-path=\"{}\" manifest:media-type=\"text/xml\"/>",
+            part
+        ));
+    }
+    for image in writer.images() {
+        manifest.push_str(&format!(
+            "<manifest:file-entry manifest:full-path=\"Pictures/{}\" manifest:media-type=\"{}\"/>",
+            encode_attribute(&image.name),
+            image.mime.essence_str()
+        ));
+    }
+    manifest.push_str("</manifest:manifest>");
+
+    manifest
+}
+
+/// Creates the content part with the automatic styles of tables and text spans
+fn create_content(
+    writer: &OdtWriter,
+    body: &str,
+    colors: &ThemeColors,
+    page: &PageSetup,
+) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <office:document-content {namespaces}>{fonts}<office:automatic-styles>\
+        <style:style style:name=\"Table\" style:family=\"table\">\
+        <style:table-properties style:width=\"{width:.3}in\" table:align=\"margins\" \
+        fo:margin-top=\"0.05in\" fo:margin-bottom=\"0.1in\"/></style:style>\
+        <style:style style:name=\"TableCell\" style:family=\"table-cell\">\
+        <style:table-cell-properties fo:padding=\"0.04in\" fo:border=\"0.5pt solid #{border}\"/>\
+        </style:style>\
+        <style:style style:name=\"TableHeaderCell\" style:family=\"table-cell\">\
+        <style:table-cell-properties fo:padding=\"0.04in\" fo:border=\"0.5pt solid #{border}\" \
+        fo:background-color=\"#{header}\"/></style:style>\
+        {text_styles}</office:automatic-styles>\
+        <office:body><office:text>{body}</office:text></office:body></office:document-content>",
+        namespaces = OFFICE_NAMESPACES,
+        fonts = FONT_FACES,
+        width = page.content_width(),
+        border = colors.secondary,
+        header = colors.table_background,
+        text_styles = writer.text_styles(),
+        body = body
+    )
+}
+
+/// Creates the styles part with the paragraph, list and page styles derived from the theme colors
+fn create_styles(colors: &ThemeColors, page: &PageSetup) -> String {
+    let mut styles = String::new();
+    styles.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    styles.push_str(&format!(
+        "<office:document-styles {}>{}<office:styles>",
+        OFFICE_NAMESPACES, FONT_FACES
+    ));
+    styles.push_str(&format!(
+        "<style:default-style style:family=\"paragraph\">\
+        <style:paragraph-properties fo:orphans=\"2\" fo:widows=\"2\"/>\
+        <style:text-properties style:font-name=\"Liberation Sans\" fo:font-size=\"11pt\" \
+        fo:color=\"#{text}\"/></style:default-style>\
+        <style:style style:name=\"Standard\" style:family=\"paragraph\" style:class=\"text\"/>\
+        <style:style style:name=\"Text_20_body\" style:display-name=\"Text body\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Standard\" style:class=\"text\">\
+        <style:paragraph-properties fo:margin-top=\"0in\" fo:margin-bottom=\"0.1in\" \
+        fo:line-height=\"115%\"/></style:style>\
+        <style:style style:name=\"Heading\" style:family=\"paragraph\" \
+        style:parent-style-name=\"Standard\" style:next-style-name=\"Text_20_body\" \
+        style:class=\"text\"><style:paragraph-properties fo:margin-top=\"0.17in\" \
+        fo:margin-bottom=\"0.08in\" fo:keep-with-next=\"always\"/>\
+        <style:text-properties fo:font-weight=\"bold\" fo:color=\"#{text}\"/></style:style>",
+        text = colors.text
+    ));
+
+    for (index, size) in HEADING_SIZES.iter().enumerate() {
+        styles.push_str(&format!(
+            "<style:style style:name=\"Heading_20_{level}\" style:display-name=\"Heading {level}\" \
+            style:family=\"paragraph\" style:parent-style-name=\"Heading\" \
+            style:next-style-name=\"Text_20_body\" style:default-outline-level=\"{level}\" \
+            style:class=\"text\"><style:text-properties fo:font-size=\"{size}\"/></style:style>",
+            level = index + 1,
+            size = size
+        ));
+    }
+
+    styles.push_str(&format!(
+        "<style:style style:name=\"Preformatted_20_Text\" style:display-name=\"Preformatted Text\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Standard\" style:class=\"html\">\
+        <style:paragraph-properties fo:margin-top=\"0in\" fo:margin-bottom=\"0.1in\" \
+        fo:padding=\"0.08in\" fo:background-color=\"#{code}\"/>\
+        <style:text-properties style:font-name=\"Liberation Mono\" fo:font-size=\"10pt\"/>\
+        </style:style>\
+        <style:style style:name=\"Code_20_File_20_Name\" style:display-name=\"Code File Name\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Standard\" style:class=\"html\">\
+        <style:paragraph-properties fo:margin-top=\"0in\" fo:margin-bottom=\"0.04in\" \
+        fo:keep-with-next=\"always\"/>\
+        <style:text-properties style:font-name=\"Liberation Mono\" fo:font-size=\"10pt\" \
+        fo:font-weight=\"bold\"/></style:style>\
+        <style:style style:name=\"Caption\" style:family=\"paragraph\" \
+        style:parent-style-name=\"Text_20_body\" style:class=\"extra\">\
+        <style:paragraph-properties fo:text-align=\"center\"/>\
+        <style:text-properties fo:font-style=\"italic\" fo:color=\"#{secondary}\"/></style:style>\
+        <style:style style:name=\"Quotations\" style:family=\"paragraph\" \
+        style:parent-style-name=\"Standard\" style:class=\"html\">\
+        <style:paragraph-properties fo:margin-left=\"0.4in\" fo:margin-right=\"0.4in\" \
+        fo:margin-top=\"0in\" fo:margin-bottom=\"0.1in\" fo:padding=\"0.08in\" \
+        fo:background-color=\"#{quote}\" fo:border-left=\"2.5pt solid #{secondary}\" \
+        fo:border-right=\"none\" fo:border-top=\"none\" fo:border-bottom=\"none\"/>\
+        <style:text-properties fo:font-style=\"italic\"/></style:style>\
+        <style:style style:name=\"Quotation_20_Source\" style:display-name=\"Quotation Source\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Text_20_body\" style:class=\"html\">\
+        <style:paragraph-properties fo:margin-left=\"0.4in\" fo:margin-right=\"0.4in\" \
+        fo:text-align=\"end\"/><style:text-properties fo:color=\"#{secondary}\"/></style:style>\
+        <style:style style:name=\"List_20_Contents\" style:display-name=\"List Contents\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Standard\" style:class=\"list\">\
+        <style:paragraph-properties fo:margin-top=\"0in\" fo:margin-bottom=\"0.04in\"/></style:style>\
+        <style:style style:name=\"Table_20_Contents\" style:display-name=\"Table Contents\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Standard\" style:class=\"extra\"/>\
+        <style:style style:name=\"Table_20_Heading\" style:display-name=\"Table Heading\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Table_20_Contents\" style:class=\"extra\">\
+        <style:paragraph-properties fo:text-align=\"center\"/>\
+        <style:text-properties fo:font-weight=\"bold\"/></style:style>\
+        <style:style style:name=\"Centered\" style:family=\"paragraph\" \
+        style:parent-style-name=\"Text_20_body\"><style:paragraph-properties fo:text-align=\"center\"/>\
+        </style:style>\
+        <style:style style:name=\"Horizontal_20_Line\" style:display-name=\"Horizontal Line\" \
+        style:family=\"paragraph\" style:parent-style-name=\"Standard\" style:class=\"html\">\
+        <style:paragraph-properties fo:margin-top=\"0in\" fo:margin-bottom=\"0.2in\" \
+        fo:padding=\"0in\" fo:border-left=\"none\" fo:border-right=\"none\" fo:border-top=\"none\" \
+        fo:border-bottom=\"0.5pt solid #{secondary}\"/>\
+        <style:text-properties fo:font-size=\"6pt\"/></style:style>\
+        <style:style style:name=\"Internet_20_link\" style:display-name=\"Internet link\" \
+        style:family=\"text\"><style:text-properties fo:color=\"#{secondary}\" \
+        style:text-underline-style=\"solid\" style:text-underline-width=\"auto\" \
+        style:text-underline-color=\"font-color\"/></style:style>",
+        code = colors.code_background,
+        quote = colors.quote_background,
+        secondary = colors.secondary
+    ));
+    styles.push_str(&create_list_style("Bullet_20_List", "Bullet List", false));
+    styles.push_str(&create_list_style(
+        "Numbered_20_List",
+        "Numbered List",
+        true,
+    ));
+    styles.push_str("</office:styles>");
+
+    styles.push_str(&format!(
+        "<office:automatic-styles><style:page-layout style:name=\"PageLayout\">\
+        <style:page-layout-properties fo:page-width=\"{:.3}in\" fo:page-height=\"{:.3}in\" \
+        style:print-orientation=\"{}\" fo:margin-top=\"{:.3}in\" fo:margin-bottom=\"{:.3}in\" \
+        fo:margin-left=\"{:.3}in\" fo:margin-right=\"{:.3}in\" fo:background-color=\"#{}\"/>\
+        </style:page-layout></office:automatic-styles>\
+        <office:master-styles><style:master-page style:name=\"Standard\" \
+        style:page-layout-name=\"PageLayout\"/></office:master-styles></office:document-styles>",
+        page.width,
+        page.height,
+        if page.landscape {
+            "landscape"
+        } else {
+            "portrait"
+        },
+        page.margin_top,
+        page.margin_bottom,
+        page.margin_left,
+        page.margin_right,
+        colors.background
+    ));
+
+    styles
+}
+
+/// Creates a list style with ten levels of bullets or numbers
+fn create_list_style(name: &str, display_name: &str, ordered: bool) -> String {
+    let mut style = format!(
+        "<text:list-style style:name=\"{}\" style:display-name=\"{}\">",
+        name, display_name
+    );
+
+    for level in 1..=10 {
+        let properties = format!(
+            "<style:list-level-properties text:list-level-position-and-space-mode=\"label-alignment\">\
+            <style:list-level-label-alignment text:label-followed-by=\"listtab\" \
+            text:list-tab-stop-position=\"{indent:.2}in\" fo:text-indent=\"-0.25in\" \
+            fo:margin-left=\"{indent:.2}in\"/></style:list-level-properties>",
+            indent = 0.25 * level as f32 + 0.1
+        );
+        if ordered {
+            style.push_str(&format!(
+                "<text:list-level-style-number text:level=\"{}\" style:num-suffix=\".\" \
+                style:num-format=\"1\">{}</text:list-level-style-number>",
+                level, properties
+            ));
+        } else {
+            style.push_str(&format!(
+                "<text:list-level-style-bullet text:level=\"{}\" text:bullet-char=\"{}\">\
+                {}</text:list-level-style-bullet>",
+                level,
+                ["\u{2022}", "\u{25E6}", "\u{25AA}"][(level - 1) % 3],
+                properties
+            ));
+        }
+    }
+    style.push_str("</text:list-style>");
+
+    style
+}
+
+/// Creates the meta part from the metadata of the document
+fn create_meta(metadata: &MetadataSettings) -> String {
+    let mut meta = String::new();
+    meta.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    meta.push_str(&format!(
+        "<office:document-meta {}><office:meta>\
+        <meta:generator>Snekdown</meta:generator>",
+        OFFICE_NAMESPACES
+    ));
+
+    if let Some(title) = &metadata.title {
+        meta.push_str(&format!("<dc:title>{}</dc:title>", encode_minimal(title)));
+    }
+    if let Some(description) = &metadata.description {
+        meta.push_str(&format!(
+            "<dc:description>{}</dc:description>",
+            encode_minimal(description)
+        ));
+    }
+    for keyword in &metadata.keywords {
+        meta.push_str(&format!(
+            "<meta:keyword>{}</meta:keyword>",
+            encode_minimal(keyword)
+        ));
+    }
+    if let Some(author) = &metadata.author {
+        meta.push_str(&format!(
+            "<meta:initial-creator>{author}</meta:initial-creator><dc:creator>{author}</dc:creator>",
+            author = encode_minimal(author)
+        ));
+    }
+    meta.push_str(&format!(
+        "<dc:language>{}</dc:language>",
+        encode_minimal(&metadata.language)
+    ));
+    let now = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S");
+    meta.push_str(&format!(
+        "<meta:creation-date>{now}</meta:creation-date><dc:date>{now}</dc:date>",
+        now = now
+    ));
+    meta.push_str("</office:meta></office:document-meta>");
+
+    meta
+}
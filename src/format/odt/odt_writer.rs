@@ -0,0 +1,358 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::format::html::asset_store::{Asset, AssetStore};
+use crate::format::page_setup::PageSetup;
+use crate::format::style::CodeStyle;
+use htmlescape::{encode_attribute, encode_minimal};
+use mime::Mime;
+use std::sync::Arc;
+
+/// The directory images are stored in
+const PICTURE_DIR: &str = "Pictures";
+
+/// The character formatting of the text that is currently written
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpanStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underlined: bool,
+    pub striked: bool,
+    pub superscript: bool,
+    pub monospace: bool,
+    pub color: Option<String>,
+}
+
+impl SpanStyle {
+    /// Returns the text properties of the style
+    fn to_properties(&self) -> String {
+        let mut properties = String::new();
+
+        if self.bold {
+            properties.push_str(" fo:font-weight=\"bold\"");
+        }
+        if self.italic {
+            properties.push_str(" fo:font-style=\"italic\"");
+        }
+        if self.underlined {
+            properties.push_str(
+                " style:text-underline-style=\"solid\" style:text-underline-width=\"auto\" \
+                style:text-underline-color=\"font-color\"",
+            );
+        }
+        if self.striked {
+            properties.push_str(" style:text-line-through-style=\"solid\"");
+        }
+        if self.superscript {
+            properties.push_str(" style:text-position=\"super 58%\"");
+        }
+        if self.monospace {
+            properties.push_str(" style:font-name=\"Liberation Mono\"");
+        }
+        if let Some(color) = &self.color {
+            properties.push_str(&format!(" fo:color=\"#{}\"", color));
+        }
+
+        properties
+    }
+}
+
+pub struct OdtWriter {
+    body: String,
+    style: SpanStyle,
+    text_styles: Vec<String>,
+    paragraph_tag: Option<&'static str>,
+    line_has_content: bool,
+    last_was_space: bool,
+    pending_break: bool,
+    images: AssetStore,
+    page: PageSetup,
+    code_style: Arc<CodeStyle>,
+    frame_count: usize,
+    table_count: usize,
+}
+
+impl OdtWriter {
+    /// Creates a new writer for pages with the given dimensions
+    pub fn new(page: PageSetup, code_style: CodeStyle) -> Self {
+        Self {
+            body: String::new(),
+            style: SpanStyle::default(),
+            text_styles: Vec::new(),
+            paragraph_tag: None,
+            line_has_content: false,
+            last_was_space: false,
+            pending_break: false,
+            images: AssetStore::new(PICTURE_DIR),
+            page,
+            code_style: Arc::new(code_style),
+            frame_count: 0,
+            table_count: 0,
+        }
+    }
+
+    /// Writes raw opendocument markup
+    pub fn write_raw(&mut self, xml: &str) {
+        self.body.push_str(xml);
+    }
+
+    /// Starts a new paragraph with the given paragraph style
+    pub fn start_paragraph(&mut self, style: &str) {
+        self.end_paragraph();
+        self.body
+            .push_str(&format!("<text:p text:style-name=\"{}\">", style));
+        self.begin_block("text:p");
+    }
+
+    /// Starts a new heading with the given level
+    pub fn start_heading(&mut self, level: u8) {
+        self.end_paragraph();
+        self.body.push_str(&format!(
+            "<text:h text:style-name=\"Heading_20_{level}\" text:outline-level=\"{level}\">",
+            level = level
+        ));
+        self.begin_block("text:h");
+    }
+
+    fn begin_block(&mut self, tag: &'static str) {
+        self.paragraph_tag = Some(tag);
+        self.line_has_content = false;
+        self.last_was_space = false;
+        self.pending_break = false;
+    }
+
+    /// Ends the current paragraph discarding a trailing line break
+    pub fn end_paragraph(&mut self) {
+        if let Some(tag) = self.paragraph_tag.take() {
+            self.body.push_str(&format!("</{}>", tag));
+        }
+        self.line_has_content = false;
+        self.pending_break = false;
+    }
+
+    /// Writes text with the current style
+    pub fn write_text(&mut self, text: &str) {
+        if text.is_empty() || (text.trim().is_empty() && self.pending_break) {
+            return;
+        }
+        if self.paragraph_tag.is_none() {
+            self.start_paragraph("Text_20_body");
+        }
+        self.write_pending_break();
+        let content = self.encode_text(text);
+        self.line_has_content = true;
+
+        if self.style == SpanStyle::default() {
+            self.body.push_str(&content);
+        } else {
+            let name = self.get_text_style_name(&self.style.clone());
+            self.body.push_str(&format!(
+                "<text:span text:style-name=\"{}\">{}</text:span>",
+                name, content
+            ));
+        }
+    }
+
+    /// Writes an inline element like a frame that counts as content of the line
+    pub fn write_inline(&mut self, xml: &str) {
+        if self.paragraph_tag.is_none() {
+            self.start_paragraph("Text_20_body");
+        }
+        self.write_pending_break();
+        self.line_has_content = true;
+        self.last_was_space = false;
+
+        self.body.push_str(xml);
+    }
+
+    fn write_pending_break(&mut self) {
+        if self.pending_break {
+            self.pending_break = false;
+            if self.line_has_content {
+                self.body.push_str("<text:line-break/>");
+                self.line_has_content = false;
+                self.last_was_space = false;
+            }
+        }
+    }
+
+    /// Encodes text so that spaces, tabs and line breaks are preserved
+    fn encode_text(&mut self, text: &str) -> String {
+        let mut encoded = String::new();
+        let mut spaces = 0;
+        let mut first_in_line = !self.line_has_content;
+
+        for c in text.chars() {
+            if c == ' ' {
+                spaces += 1;
+                continue;
+            }
+            encode_spaces(&mut encoded, spaces, first_in_line || self.last_was_space);
+            spaces = 0;
+            self.last_was_space = false;
+
+            match c {
+                '\t' => {
+                    encoded.push_str("<text:tab/>");
+                    first_in_line = false;
+                }
+                '\n' => {
+                    encoded.push_str("<text:line-break/>");
+                    first_in_line = true;
+                }
+                _ => {
+                    encoded.push_str(&encode_minimal(&c.to_string()));
+                    first_in_line = false;
+                }
+            }
+        }
+        if spaces > 0 {
+            encode_spaces(&mut encoded, spaces, first_in_line || self.last_was_space);
+            self.last_was_space = true;
+        }
+
+        encoded
+    }
+
+    /// Writes a space between two lines of a paragraph
+    /// unless they are already separated by a line break
+    pub fn write_separator(&mut self) {
+        if !self.pending_break && self.line_has_content {
+            self.write_text(" ");
+        }
+    }
+
+    /// Adds a line break that is only written if more text follows in the same paragraph
+    pub fn line_break(&mut self) {
+        self.pending_break = true;
+    }
+
+    /// Returns the current character style
+    pub fn style(&self) -> SpanStyle {
+        self.style.clone()
+    }
+
+    /// Replaces the current character style
+    pub fn set_style(&mut self, style: SpanStyle) {
+        self.style = style;
+    }
+
+    /// Returns the name of the automatic style for the given character style
+    fn get_text_style_name(&mut self, style: &SpanStyle) -> String {
+        let properties = style.to_properties();
+        let index = match self.text_styles.iter().position(|p| *p == properties) {
+            Some(index) => index,
+            None => {
+                self.text_styles.push(properties);
+                self.text_styles.len() - 1
+            }
+        };
+
+        format!("T{}", index + 1)
+    }
+
+    /// Returns the automatic text styles that have been used
+    pub fn text_styles(&self) -> String {
+        self.text_styles
+            .iter()
+            .enumerate()
+            .map(|(i, properties)| {
+                format!(
+                    "<style:style style:name=\"T{}\" style:family=\"text\">\
+                    <style:text-properties{}/></style:style>",
+                    i + 1,
+                    properties
+                )
+            })
+            .collect()
+    }
+
+    /// Writes a bookmark that internal links can point to
+    pub fn write_bookmark(&mut self, key: &str) {
+        if self.paragraph_tag.is_none() {
+            self.start_paragraph("Text_20_body");
+        }
+        self.body.push_str(&format!(
+            "<text:bookmark text:name=\"{}\"/>",
+            encode_attribute(key)
+        ));
+    }
+
+    /// Starts a link to the given url
+    pub fn start_link(&mut self, url: &str) {
+        if self.paragraph_tag.is_none() {
+            self.start_paragraph("Text_20_body");
+        }
+        self.write_pending_break();
+        self.body.push_str(&format!(
+            "<text:a xlink:type=\"simple\" xlink:href=\"{}\" \
+            text:style-name=\"Internet_20_link\" text:visited-style-name=\"Internet_20_link\">",
+            encode_attribute(url)
+        ));
+    }
+
+    /// Ends the current link
+    pub fn end_link(&mut self) {
+        self.body.push_str("</text:a>");
+    }
+
+    /// Adds an image and returns the path it is referenced with
+    pub fn add_image(&mut self, data: Vec<u8>, mime: Mime) -> String {
+        self.images.add(data, mime)
+    }
+
+    /// Returns all images that have been added
+    pub fn images(&self) -> &[Asset] {
+        self.images.assets()
+    }
+
+    /// Returns a new number for a frame
+    pub fn next_frame_number(&mut self) -> usize {
+        self.frame_count += 1;
+
+        self.frame_count
+    }
+
+    /// Returns a new number for a table
+    pub fn next_table_number(&mut self) -> usize {
+        self.table_count += 1;
+
+        self.table_count
+    }
+
+    /// Returns the dimensions of the page
+    pub fn page(&self) -> &PageSetup {
+        &self.page
+    }
+
+    /// Returns the syntaxes and themes code blocks are highlighted with
+    pub fn code_style(&self) -> Arc<CodeStyle> {
+        Arc::clone(&self.code_style)
+    }
+
+    /// Returns the written body and ends an unclosed paragraph
+    pub fn take_body(&mut self) -> String {
+        self.end_paragraph();
+
+        std::mem::take(&mut self.body)
+    }
+}
+
+/// Encodes a sequence of spaces. Leading and consecutive spaces
+/// are collapsed by office applications unless they are written as text:s
+fn encode_spaces(encoded: &mut String, count: usize, leading: bool) {
+    if count == 0 {
+        return;
+    }
+    if leading {
+        encoded.push_str(&format!("<text:s text:c=\"{}\"/>", count));
+    } else {
+        encoded.push(' ');
+        if count > 1 {
+            encoded.push_str(&format!("<text:s text:c=\"{}\"/>", count - 1));
+        }
+    }
+}
@@ -0,0 +1,40 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+
+pub type OdtResult<T> = Result<T, OdtError>;
+
+#[derive(Debug)]
+pub enum OdtError {
+    IoError(io::Error),
+    ZipError(zip::result::ZipError),
+}
+
+impl Display for OdtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OdtError::IoError(e) => write!(f, "IO Error: {}", e),
+            OdtError::ZipError(e) => write!(f, "Zip Error: {}", e),
+        }
+    }
+}
+
+impl Error for OdtError {}
+
+impl From<io::Error> for OdtError {
+    fn from(other: io::Error) -> Self {
+        Self::IoError(other)
+    }
+}
+
+impl From<zip::result::ZipError> for OdtError {
+    fn from(other: zip::result::ZipError) -> Self {
+        Self::ZipError(other)
+    }
+}
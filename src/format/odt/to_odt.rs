@@ -0,0 +1,694 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::*;
+use crate::format::odt::odt_writer::{OdtWriter, SpanStyle};
+use crate::format::pdf_outline::inline_to_string;
+use crate::format::PlaceholderTemplate;
+use crate::references::glossary::{GlossaryDisplay, GlossaryReference};
+use crate::references::templates::{Template, TemplateVariable};
+use crate::utils::entities::decode_entity;
+use htmlescape::{encode_attribute, encode_minimal};
+use image::GenericImageView;
+use std::io;
+use syntect::easy::HighlightLines;
+
+pub trait ToOdt {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()>;
+}
+
+impl ToOdt for Element {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        match self {
+            Element::Block(block) => block.to_odt(writer),
+            Element::Inline(inline) => inline.to_odt(writer),
+            Element::Line(line) => line.to_odt(writer),
+        }
+    }
+}
+
+impl ToOdt for Line {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        match self {
+            Line::Text(text) => text.to_odt(writer),
+            Line::Ruler(ruler) => ruler.to_odt(writer),
+            Line::RefLink(anchor) => anchor.to_odt(writer),
+            Line::Centered(centered) => centered.to_odt(writer),
+            Line::Anchor(a) => a.to_odt(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToOdt for Inline {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        match self {
+            Inline::Url(url) => url.to_odt(writer),
+            Inline::Monospace(mono) => mono.to_odt(writer),
+            Inline::Striked(striked) => write_styled(writer, |s| s.striked = true, &striked.value),
+            Inline::Plain(plain) => plain.to_odt(writer),
+            Inline::Italic(italic) => write_styled(writer, |s| s.italic = true, &italic.value),
+            Inline::Underlined(under) => {
+                write_styled(writer, |s| s.underlined = true, &under.value)
+            }
+            Inline::Bold(bold) => write_styled(writer, |s| s.bold = true, &bold.value),
+            Inline::Image(img) => img.to_odt(writer),
+            Inline::Placeholder(placeholder) => placeholder.read().unwrap().to_odt(writer),
+            Inline::Superscript(superscript) => {
+                write_styled(writer, |s| s.superscript = true, &superscript.value)
+            }
+            Inline::Checkbox(checkbox) => checkbox.to_odt(writer),
+            Inline::Emoji(emoji) => emoji.to_odt(writer),
+            Inline::Colored(colored) => colored.to_odt(writer),
+            Inline::BibReference(bibref) => bibref.read().unwrap().to_odt(writer),
+            Inline::TemplateVar(var) => var.read().unwrap().to_odt(writer),
+            Inline::Math(m) => m.to_odt(writer),
+            Inline::LineBreak => {
+                writer.line_break();
+                Ok(())
+            }
+            Inline::CharacterCode(code) => code.to_odt(writer),
+            Inline::GlossaryReference(gloss) => gloss.lock().to_odt(writer),
+            Inline::Arrow(a) => a.to_odt(writer),
+            Inline::Anchor(a) => a.to_odt(writer),
+        }
+    }
+}
+
+impl ToOdt for Block {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        match self {
+            Block::Paragraph(para) => para.to_odt(writer),
+            Block::List(list) => list.to_odt(writer),
+            Block::Table(table) => table.to_odt(writer),
+            Block::CodeBlock(code) => code.to_odt(writer),
+            Block::Quote(quote) => quote.to_odt(writer),
+            Block::Section(section) => section.to_odt(writer),
+            Block::Placeholder(placeholder) => placeholder.read().unwrap().to_odt(writer),
+            Block::MathBlock(m) => m.to_odt(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToOdt for MetadataValue {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        match self {
+            MetadataValue::Placeholder(ph) => ph.read().unwrap().to_odt(writer),
+            MetadataValue::Template(t) => t.to_odt(writer),
+            _ => {
+                writer.write_text(&self.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ToOdt for Document {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        for element in &self.elements {
+            element.to_odt(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Math {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.set_style(SpanStyle {
+            italic: true,
+            ..previous.clone()
+        });
+        writer.write_text(self.source.trim());
+        writer.set_style(previous);
+
+        Ok(())
+    }
+}
+
+impl ToOdt for MathBlock {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.start_paragraph("Centered");
+        writer.set_style(SpanStyle {
+            italic: true,
+            ..previous.clone()
+        });
+        writer.write_text(self.source.trim());
+        writer.set_style(previous);
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Section {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.start_heading(self.header.size.clamp(1, 6));
+        writer.write_bookmark(&self.header.anchor);
+        self.header.line.to_odt(writer)?;
+        writer.end_paragraph();
+
+        for element in &self.elements {
+            element.to_odt(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Paragraph {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.end_paragraph();
+
+        for element in &self.elements {
+            match element {
+                // paragraphs can't be nested so these lines are written as separate paragraphs
+                Line::Centered(_) | Line::Ruler(_) => {
+                    writer.end_paragraph();
+                    element.to_odt(writer)?;
+                }
+                _ => {
+                    // the paragraph is opened by the first text so that empty lines are skipped
+                    writer.write_separator();
+                    element.to_odt(writer)?;
+                }
+            }
+        }
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToOdt for List {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        write_list(writer, self.ordered, &self.items)
+    }
+}
+
+/// Writes the items of a list with the children as nested lists
+fn write_list(writer: &mut OdtWriter, ordered: bool, items: &[ListItem]) -> io::Result<()> {
+    let style = if ordered {
+        "Numbered_20_List"
+    } else {
+        "Bullet_20_List"
+    };
+    writer.end_paragraph();
+    writer.write_raw(&format!("<text:list text:style-name=\"{}\">", style));
+
+    for item in items {
+        writer.write_raw("<text:list-item>");
+        writer.start_paragraph("List_20_Contents");
+        item.text.to_odt(writer)?;
+        writer.end_paragraph();
+
+        if let Some(first) = item.children.first() {
+            write_list(writer, first.ordered, &item.children)?;
+        }
+        writer.write_raw("</text:list-item>");
+    }
+    writer.write_raw("</text:list>");
+
+    Ok(())
+}
+
+impl ToOdt for Table {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let columns = std::iter::once(&self.header)
+            .chain(self.rows.iter())
+            .map(|r| r.cells.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let number = writer.next_table_number();
+
+        writer.end_paragraph();
+        writer.write_raw(&format!(
+            "<table:table table:name=\"Table{}\" table:style-name=\"Table\">\
+            <table:table-column table:number-columns-repeated=\"{}\"/>\
+            <table:table-header-rows>",
+            number, columns
+        ));
+        write_row(writer, &self.header, columns, true)?;
+        writer.write_raw("</table:table-header-rows>");
+
+        for row in &self.rows {
+            write_row(writer, row, columns, false)?;
+        }
+        writer.write_raw("</table:table>");
+
+        Ok(())
+    }
+}
+
+/// Writes a table row filling up missing cells so that every row has the same number of cells
+fn write_row(writer: &mut OdtWriter, row: &Row, columns: usize, header: bool) -> io::Result<()> {
+    let (cell_style, paragraph_style) = if header {
+        ("TableHeaderCell", "Table_20_Heading")
+    } else {
+        ("TableCell", "Table_20_Contents")
+    };
+    writer.write_raw("<table:table-row>");
+
+    for index in 0..columns {
+        writer.write_raw(&format!(
+            "<table:table-cell table:style-name=\"{}\" office:value-type=\"string\">",
+            cell_style
+        ));
+        writer.start_paragraph(paragraph_style);
+        if let Some(cell) = row.cells.get(index) {
+            cell.text.to_odt(writer)?;
+        }
+        writer.end_paragraph();
+        writer.write_raw("</table:table-cell>");
+    }
+    writer.write_raw("</table:table-row>");
+
+    Ok(())
+}
+
+impl ToOdt for CodeBlock {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let gutters = self.get_line_gutters();
+        let code = if gutters.is_empty() {
+            self.code.trim_matches('\n')
+        } else {
+            self.code.trim_end_matches('\n')
+        };
+        if let Some(file_name) = self.get_file_name() {
+            writer.start_paragraph("Code_20_File_20_Name");
+            writer.write_text(&file_name);
+        }
+        writer.start_paragraph("Preformatted_20_Text");
+        if let Some(anchor) = self.get_anchor() {
+            writer.write_bookmark(&anchor);
+        }
+
+        let code_style = writer.code_style();
+        let syntax_set = &code_style.syntax_set;
+        let syntax = if self.language.is_empty() {
+            None
+        } else {
+            syntax_set.find_syntax_by_token(&self.language)
+        };
+
+        if let Some(syntax) = syntax {
+            let mut highlighter = HighlightLines::new(syntax, &code_style.theme);
+            let previous = writer.style();
+
+            for (index, line) in code.lines().enumerate() {
+                if index > 0 {
+                    writer.write_text("\n");
+                }
+                if let Some(gutter) = gutters.get(index) {
+                    writer.set_style(previous.clone());
+                    writer.write_text(gutter);
+                }
+                for (style, text) in highlighter.highlight(line, syntax_set) {
+                    let color = style.foreground;
+                    writer.set_style(SpanStyle {
+                        color: Some(format!("{:02X}{:02X}{:02X}", color.r, color.g, color.b)),
+                        ..previous.clone()
+                    });
+                    writer.write_text(text);
+                }
+            }
+            writer.set_style(previous);
+        } else if gutters.is_empty() {
+            writer.write_text(code);
+        } else {
+            writer.write_text(&self.get_numbered_lines().join("\n"));
+        }
+        writer.end_paragraph();
+
+        if let Some(caption) = self.get_caption() {
+            writer.start_paragraph("Caption");
+            writer.write_text(&caption);
+            writer.end_paragraph();
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Quote {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.start_paragraph("Quotations");
+        for line in &self.text {
+            writer.write_separator();
+            line.to_odt(writer)?;
+        }
+        writer.end_paragraph();
+
+        if let Some(meta) = &self.metadata {
+            writer.start_paragraph("Quotation_20_Source");
+            writer.write_text("\u{2014} ");
+            meta.to_odt(writer)?;
+            writer.end_paragraph();
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Ruler {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.start_paragraph("Horizontal_20_Line");
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToOdt for TextLine {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        for text in &self.subtext {
+            text.to_odt(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Image {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let description = self
+            .url
+            .description
+            .as_ref()
+            .map(|d| d.iter().map(inline_to_string).collect::<String>())
+            .unwrap_or_default();
+        let content = self.get_content();
+        let dimensions = content
+            .as_ref()
+            .and_then(|c| image::load_from_memory(c).ok())
+            .map(|i| i.dimensions());
+
+        let (content, (pixel_width, pixel_height)) = match (content, dimensions) {
+            (Some(content), Some(dimensions)) => (content, dimensions),
+            _ => {
+                log::warn!(
+                    "The image {} can't be embedded in the odt document",
+                    self.url.url
+                );
+                return self.url.to_odt(writer);
+            }
+        };
+        let (width, height) =
+            writer
+                .page()
+                .image_size(self.metadata.as_ref(), pixel_width, pixel_height);
+        let path = writer.add_image(content, self.get_mime_type());
+        let number = writer.next_frame_number();
+
+        writer.write_inline(&format!(
+            "<draw:frame draw:name=\"Image{}\" text:anchor-type=\"as-char\" \
+            svg:width=\"{:.3}in\" svg:height=\"{:.3}in\">\
+            <draw:image xlink:href=\"{}\" xlink:type=\"simple\" xlink:show=\"embed\" \
+            xlink:actuate=\"onLoad\"/><svg:desc>{}</svg:desc></draw:frame>",
+            number,
+            width,
+            height,
+            encode_attribute(&path),
+            encode_minimal(&description)
+        ));
+
+        if let Some(description) = &self.url.description {
+            writer.line_break();
+            write_styled(writer, |s| s.italic = true, description)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the inline elements with a modified character style
+fn write_styled<F: FnOnce(&mut SpanStyle)>(
+    writer: &mut OdtWriter,
+    change: F,
+    value: &[Inline],
+) -> io::Result<()> {
+    let previous = writer.style();
+    let mut style = previous.clone();
+    change(&mut style);
+    writer.set_style(style);
+
+    for element in value {
+        element.to_odt(writer)?;
+    }
+    writer.set_style(previous);
+
+    Ok(())
+}
+
+impl ToOdt for MonospaceText {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.set_style(SpanStyle {
+            monospace: true,
+            ..previous.clone()
+        });
+        writer.write_text(&self.value);
+        writer.set_style(previous);
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Url {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.start_link(&self.url);
+        if let Some(description) = &self.description {
+            for desc in description {
+                desc.to_odt(writer)?;
+            }
+        } else {
+            writer.write_text(&self.url);
+        }
+        writer.end_link();
+
+        Ok(())
+    }
+}
+
+impl ToOdt for PlainText {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.write_text(&self.value);
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Placeholder {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            value.to_odt(writer)
+        } else {
+            log::debug!("Unknown placeholder [[{}]]", self.name.clone());
+            writer.write_text(&format!("[[{}]]", self.name));
+
+            Ok(())
+        }
+    }
+}
+
+impl ToOdt for RefLink {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.start_link(&format!("#{}", self.reference));
+        self.description.to_odt(writer)?;
+        writer.end_link();
+
+        Ok(())
+    }
+}
+
+impl ToOdt for InlineMetadata {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        if let Some(MetadataValue::String(format)) = self.data.get("display") {
+            let mut template = PlaceholderTemplate::new(format.clone());
+            self.data
+                .iter()
+                .for_each(|(k, v)| template.add_replacement(k, &v.to_string()));
+
+            writer.write_text(&template.render());
+        } else {
+            for (k, v) in &self.data {
+                writer.write_text(&format!("{}={},", k, v.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Centered {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.start_paragraph("Centered");
+        self.line.to_odt(writer)?;
+        writer.end_paragraph();
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Checkbox {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        if self.value {
+            writer.write_text("\u{2612}");
+        } else {
+            writer.write_text("\u{2610}");
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Emoji {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.write_text(&self.value.to_string());
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Colored {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.set_style(SpanStyle {
+            color: odt_color(&self.color).or_else(|| previous.color.clone()),
+            ..previous.clone()
+        });
+        self.value.to_odt(writer)?;
+        writer.set_style(previous);
+
+        Ok(())
+    }
+}
+
+/// Returns the hex value of a color without the leading #
+fn odt_color(color: &str) -> Option<String> {
+    let color = color.trim().to_lowercase();
+    let hex = match color.as_str() {
+        "black" => "000000",
+        "white" => "ffffff",
+        "red" => "ff0000",
+        "green" => "008000",
+        "blue" => "0000ff",
+        "yellow" => "ffff00",
+        "orange" => "ffa500",
+        "purple" => "800080",
+        "gray" | "grey" => "808080",
+        _ => color.trim_start_matches('#'),
+    };
+
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        6 => Some(hex.to_uppercase()),
+        3 => Some(
+            hex.chars()
+                .flat_map(|c| vec![c, c])
+                .collect::<String>()
+                .to_uppercase(),
+        ),
+        _ => None,
+    }
+}
+
+impl ToOdt for BibReference {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let previous = writer.style();
+        writer.set_style(SpanStyle {
+            superscript: true,
+            ..previous.clone()
+        });
+        writer.start_link(&format!("#{}", self.key));
+        writer.write_text(&self.get_formatted());
+        writer.end_link();
+        writer.set_style(previous);
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Template {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        for element in &self.text {
+            element.to_odt(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for TemplateVariable {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            writer.write_text(&self.prefix);
+            value.to_odt(writer)?;
+            writer.write_text(&self.suffix);
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for CharacterCode {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        match decode_entity(&self.code) {
+            Some(decoded) => writer.write_text(&decoded),
+            None => writer.write_text(&format!("&{};", self.code)),
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Anchor {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        writer.write_bookmark(&self.key);
+
+        self.inner.to_odt(writer)
+    }
+}
+
+impl ToOdt for GlossaryReference {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        if let Some(entry) = &self.entry {
+            let entry = entry.lock();
+            writer.start_link(&format!("#{}", self.short));
+            match self.display {
+                GlossaryDisplay::Short => writer.write_text(&entry.short),
+                GlossaryDisplay::Long => writer.write_text(&entry.long),
+            }
+            writer.end_link();
+        } else {
+            writer.write_text(&format!("~{}", self.short));
+        }
+
+        Ok(())
+    }
+}
+
+impl ToOdt for Arrow {
+    fn to_odt(&self, writer: &mut OdtWriter) -> io::Result<()> {
+        let arrow = match self {
+            Arrow::RightArrow => "\u{27F6}",
+            Arrow::LeftArrow => "\u{27F5}",
+            Arrow::LeftRightArrow => "\u{27F7}",
+            Arrow::BigRightArrow => "\u{27F9}",
+            Arrow::BigLeftArrow => "\u{27F8}",
+            Arrow::BigLeftRightArrow => "\u{27FA}",
+        };
+        writer.write_text(arrow);
+
+        Ok(())
+    }
+}
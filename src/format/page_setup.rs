@@ -0,0 +1,103 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::{InlineMetadata, Metadata};
+use crate::settings::pdf_settings::PDFSettings;
+
+const DEFAULT_PAGE_WIDTH: f32 = 8.27;
+const DEFAULT_PAGE_HEIGHT: f32 = 11.69;
+const DEFAULT_MARGIN: f32 = 1.0;
+const PIXELS_PER_INCH: f32 = 96.0;
+
+/// The dimensions of the page in inches for formats with a fixed page size
+#[derive(Clone, Debug)]
+pub struct PageSetup {
+    pub width: f32,
+    pub height: f32,
+    pub margin_top: f32,
+    pub margin_bottom: f32,
+    pub margin_left: f32,
+    pub margin_right: f32,
+    pub landscape: bool,
+}
+
+impl PageSetup {
+    /// Uses the page settings of the pdf rendering
+    pub fn new(pdf: &PDFSettings) -> Self {
+        let (width, height) = pdf.page_dimensions();
+        let mut width = width.unwrap_or(DEFAULT_PAGE_WIDTH);
+        let mut height = height.unwrap_or(DEFAULT_PAGE_HEIGHT);
+
+        if pdf.landscape {
+            std::mem::swap(&mut width, &mut height);
+        }
+
+        Self {
+            width,
+            height,
+            margin_top: pdf.margin.top.unwrap_or(DEFAULT_MARGIN),
+            margin_bottom: pdf.margin.bottom.unwrap_or(DEFAULT_MARGIN),
+            margin_left: pdf.margin.left.unwrap_or(DEFAULT_MARGIN),
+            margin_right: pdf.margin.right.unwrap_or(DEFAULT_MARGIN),
+            landscape: pdf.landscape,
+        }
+    }
+
+    /// Returns the width of the page without the margins
+    pub fn content_width(&self) -> f32 {
+        (self.width - self.margin_left - self.margin_right).max(1.0)
+    }
+
+    /// Returns the size of an image in inches. Images without a configured size are
+    /// displayed with their pixel size but never wider than the content of the page.
+    pub fn image_size(
+        &self,
+        metadata: Option<&InlineMetadata>,
+        pixel_width: u32,
+        pixel_height: u32,
+    ) -> (f32, f32) {
+        let content_width = self.content_width();
+        let natural_width = pixel_width as f32 / PIXELS_PER_INCH;
+        let natural_height = pixel_height as f32 / PIXELS_PER_INCH;
+        let parse_size = |value: String, reference: f32| -> Option<f32> {
+            let value = value.trim();
+            if let Some(percent) = value.strip_suffix('%') {
+                percent
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .map(|p| reference * p / 100.0)
+            } else {
+                value
+                    .trim_end_matches("px")
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .map(|p| p / PIXELS_PER_INCH)
+            }
+        };
+        let width = metadata
+            .and_then(|m| m.get_string("width"))
+            .and_then(|w| parse_size(w, content_width));
+        let height = metadata
+            .and_then(|m| m.get_string("height"))
+            .and_then(|h| parse_size(h, natural_height));
+        let ratio = natural_height / natural_width.max(f32::EPSILON);
+
+        let (width, height) = match (width, height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, width * ratio),
+            (None, Some(height)) => (height / ratio.max(f32::EPSILON), height),
+            (None, None) => (natural_width, natural_height),
+        };
+
+        if width > content_width {
+            (content_width, height * content_width / width)
+        } else {
+            (width, height)
+        }
+    }
+}
@@ -0,0 +1,215 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::{Block, Document, Inline, Line, Section};
+use crate::settings::metadata_settings::MetadataSettings;
+use lopdf::{dictionary, Dictionary, Object, ObjectId, StringFormat};
+use std::collections::HashMap;
+
+/// An entry of the pdf outline (bookmarks)
+#[derive(Clone, Debug)]
+pub struct OutlineEntry {
+    pub title: String,
+    pub anchor: String,
+    pub children: Vec<OutlineEntry>,
+}
+
+/// Creates the outline from the section hierarchy of the document.
+/// Sections that are hidden in the toc are skipped together with their children.
+pub fn create_outline(document: &Document) -> Vec<OutlineEntry> {
+    create_outline_entries(&document.elements)
+}
+
+fn create_outline_entries(blocks: &[Block]) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+
+    for block in blocks {
+        match block {
+            Block::Section(section) => {
+                if !section.is_hidden_in_toc() {
+                    entries.push(create_section_entry(section))
+                }
+            }
+            Block::Import(import) => {
+                if let Some(document) = &import.anchor.read().unwrap().document {
+                    entries.append(&mut create_outline(document))
+                }
+            }
+            _ => {}
+        }
+    }
+
+    entries
+}
+
+fn create_section_entry(section: &Section) -> OutlineEntry {
+    OutlineEntry {
+        title: line_to_string(&section.header.line),
+        anchor: section.header.anchor.clone(),
+        children: create_outline_entries(&section.elements),
+    }
+}
+
+/// Returns the text content of a line without any formatting
+pub fn line_to_string(line: &Line) -> String {
+    line.as_raw_text()
+        .subtext
+        .iter()
+        .map(inline_to_string)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Returns the text content of an inline element without any formatting
+pub fn inline_to_string(inline: &Inline) -> String {
+    match inline {
+        Inline::Plain(p) => p.value.clone(),
+        Inline::Bold(b) => b.value.iter().map(inline_to_string).collect(),
+        Inline::Italic(i) => i.value.iter().map(inline_to_string).collect(),
+        Inline::Underlined(u) => u.value.iter().map(inline_to_string).collect(),
+        Inline::Striked(s) => s.value.iter().map(inline_to_string).collect(),
+        Inline::Superscript(s) => s.value.iter().map(inline_to_string).collect(),
+        Inline::Monospace(m) => m.value.clone(),
+        Inline::Colored(c) => inline_to_string(&c.value),
+        Inline::Url(u) => {
+            if let Some(description) = &u.description {
+                description.iter().map(inline_to_string).collect()
+            } else {
+                u.url.clone()
+            }
+        }
+        Inline::Emoji(e) => e.value.to_string(),
+        Inline::Math(m) => m.source.clone(),
+        Inline::Placeholder(p) => {
+            if let Some(crate::elements::Element::Inline(inline)) = &p.read().unwrap().value {
+                inline_to_string(inline)
+            } else {
+                String::new()
+            }
+        }
+        _ => inline.as_plain_text().value,
+    }
+}
+
+/// Encodes a string as a pdf text string.
+/// Strings that contain non ascii characters are encoded as UTF-16BE with a byte order mark.
+pub fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::String(text.as_bytes().to_vec(), StringFormat::Literal)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
+}
+
+/// Writes the outline into the pdf document and returns the id of the outline dictionary.
+/// Entries without a destination point to the fallback destination.
+pub fn write_outline(
+    doc: &mut lopdf::Document,
+    entries: &[OutlineEntry],
+    destinations: &HashMap<String, Object>,
+    fallback: Object,
+) -> Option<ObjectId> {
+    if entries.is_empty() {
+        return None;
+    }
+    let outline_id = doc.new_object_id();
+    let (first, last, count) =
+        write_outline_level(doc, entries, outline_id, destinations, &fallback);
+    doc.objects.insert(
+        outline_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Outlines",
+            "First" => first,
+            "Last" => last,
+            "Count" => count as i64,
+        }),
+    );
+
+    Some(outline_id)
+}
+
+/// Writes one level of outline items and returns the first and last id and the number of
+/// visible descendants
+fn write_outline_level(
+    doc: &mut lopdf::Document,
+    entries: &[OutlineEntry],
+    parent: ObjectId,
+    destinations: &HashMap<String, Object>,
+    fallback: &Object,
+) -> (ObjectId, ObjectId, usize) {
+    let ids = entries
+        .iter()
+        .map(|_| doc.new_object_id())
+        .collect::<Vec<ObjectId>>();
+    let mut count = entries.len();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let destination = destinations
+            .get(&entry.anchor)
+            .cloned()
+            .unwrap_or_else(|| fallback.clone());
+        let mut item = dictionary! {
+            "Title" => text_string(&entry.title),
+            "Parent" => parent,
+            "Dest" => destination,
+        };
+        if index > 0 {
+            item.set("Prev", ids[index - 1]);
+        }
+        if index + 1 < ids.len() {
+            item.set("Next", ids[index + 1]);
+        }
+        if !entry.children.is_empty() {
+            let (first, last, child_count) =
+                write_outline_level(doc, &entry.children, ids[index], destinations, fallback);
+            item.set("First", first);
+            item.set("Last", last);
+            item.set("Count", child_count as i64);
+            count += child_count;
+        }
+        doc.objects.insert(ids[index], Object::Dictionary(item));
+    }
+
+    (ids[0], ids[ids.len() - 1], count)
+}
+
+/// Writes the metadata into the document information dictionary.
+/// An existing information dictionary is updated, otherwise a new one is created.
+pub fn apply_document_info(doc: &mut lopdf::Document, metadata: &MetadataSettings) {
+    let existing = doc
+        .trailer
+        .get(b"Info")
+        .and_then(Object::as_reference)
+        .ok()
+        .filter(|id| doc.get_dictionary(*id).is_ok());
+    let info_id = existing.unwrap_or_else(|| {
+        let id = doc.add_object(Dictionary::new());
+        doc.trailer.set("Info", id);
+        id
+    });
+    if let Ok(info) = doc.get_object_mut(info_id).and_then(Object::as_dict_mut) {
+        if !info.has(b"Creator") {
+            info.set("Creator", text_string("Snekdown"));
+        }
+        if let Some(title) = &metadata.title {
+            info.set("Title", text_string(title));
+        }
+        if let Some(author) = &metadata.author {
+            info.set("Author", text_string(author));
+        }
+        if let Some(description) = &metadata.description {
+            info.set("Subject", text_string(description));
+        }
+        if !metadata.keywords.is_empty() {
+            info.set("Keywords", text_string(&metadata.keywords.join(", ")));
+        }
+    }
+}
@@ -0,0 +1,359 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::{Block, Document, Section};
+use crate::format::html::asset_store::AssetStore;
+use crate::format::html::html_writer::HTMLWriter;
+use crate::format::html::search_index::{SearchIndex, SEARCH_BOX, SEARCH_SCRIPT};
+use crate::format::html::shared_buffer::SharedBuffer;
+use crate::format::html::to_html::{ToHtml, MATHJAX_URL};
+use crate::format::pdf_outline::line_to_string;
+use crate::format::style::{get_css_for_theme, get_site_css_for_theme, CodeStyle};
+use crate::format::{RenderResult, Renderer};
+use crate::settings::Settings;
+use htmlescape::{encode_attribute, encode_minimal};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+const ASSET_DIR: &str = "assets";
+const STYLE_FILE: &str = "style.css";
+const INDEX_FILE: &str = "index.html";
+const SEARCH_INDEX_FILE: &str = "search-index.js";
+const SEARCH_SCRIPT_FILE: &str = "search.js";
+
+/// A single html file of the site
+pub(crate) struct Page {
+    pub(crate) file_name: String,
+    pub(crate) title: String,
+    pub(crate) anchor: String,
+    pub(crate) blocks: Vec<Block>,
+}
+
+/// Renders the document to a static website with one page per section of the configured level.
+/// Images and styles are written to a shared asset directory inside the output directory.
+pub fn render_to_site(document: Document, output_dir: &Path) -> io::Result<()> {
+    let settings = document.config.lock().clone();
+    let pages = split_pages(&document.elements, settings.site.split_level.max(1));
+
+    let mut anchor_pages = HashMap::new();
+    for page in &pages {
+        for anchor in page.blocks.iter().flat_map(|b| b.get_anchors()) {
+            anchor_pages
+                .entry(anchor)
+                .or_insert_with(|| page.file_name.clone());
+        }
+    }
+
+    let buffer = SharedBuffer::default();
+    let mut writer = HTMLWriter::new(Box::new(buffer.clone()), settings.style.theme.clone());
+    writer.set_safe_mode(settings.safe_mode);
+    writer.set_code_style(CodeStyle::load(&settings, &document.get_root_dir()));
+    document.create_image_variants();
+    writer.set_asset_store(AssetStore::new(ASSET_DIR));
+    writer.set_anchor_pages(anchor_pages);
+    let toc = document.create_toc(true);
+    let mut rendered_pages = Vec::new();
+
+    log::info!("Rendering {} pages...", pages.len());
+    for page in &pages {
+        writer.set_current_page(&page.file_name);
+        for block in &page.blocks {
+            block.to_html(&mut writer)?;
+        }
+        writer.flush()?;
+        let content = buffer.take_string();
+        toc.to_html(&mut writer)?;
+        writer.flush()?;
+        rendered_pages.push((content, buffer.take_string()));
+    }
+    let assets = writer
+        .take_asset_store()
+        .map(|s| s.assets().to_vec())
+        .unwrap_or_default();
+
+    let mut style = get_css_for_theme(settings.style.theme.clone());
+    style.push_str(&get_site_css_for_theme(settings.style.theme.clone()));
+    style.push_str(&writer.get_code_style().get_css());
+    for stylesheet in &document.stylesheets {
+        if let Some(data) = &stylesheet.lock().data {
+            style.push('\n');
+            style.push_str(&String::from_utf8_lossy(data));
+        }
+    }
+
+    log::info!("Writing site to {:?}...", output_dir);
+    let asset_dir = output_dir.join(ASSET_DIR);
+    fs::create_dir_all(&asset_dir)?;
+    fs::write(asset_dir.join(STYLE_FILE), style)?;
+
+    for asset in &assets {
+        fs::write(asset_dir.join(&asset.name), &asset.data)?;
+    }
+    if settings.features.include_search {
+        let site_title = settings.metadata.title.clone().unwrap_or_default();
+        let mut index = SearchIndex::default();
+        for page in &pages {
+            index.add_blocks(
+                &page.file_name,
+                get_link_title(page, &site_title),
+                &page.blocks,
+            );
+        }
+        fs::write(asset_dir.join(SEARCH_INDEX_FILE), index.to_script())?;
+        fs::write(asset_dir.join(SEARCH_SCRIPT_FILE), SEARCH_SCRIPT)?;
+    }
+    for (index, (page, (content, navigation))) in pages.iter().zip(rendered_pages).enumerate() {
+        let previous = index.checked_sub(1).and_then(|i| pages.get(i));
+        let next = pages.get(index + 1);
+        let mut file = fs::File::create(output_dir.join(&page.file_name))?;
+        file.write_all(
+            create_page(&settings, page, &content, &navigation, previous, next).as_bytes(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Renders documents to static websites
+pub struct SiteRenderer;
+
+impl Renderer for SiteRenderer {
+    fn name(&self) -> &str {
+        "site"
+    }
+
+    fn extension(&self) -> &str {
+        ""
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        _: Box<dyn Write>,
+        output: Option<&Path>,
+    ) -> RenderResult<()> {
+        match output {
+            Some(output) => render_to_site(document, output)?,
+            None => return Err("No output directory specified".into()),
+        }
+
+        Ok(())
+    }
+
+    fn writes_directory(&self) -> bool {
+        true
+    }
+}
+
+/// Splits the blocks into pages. Blocks before the first section are put on the index page.
+fn split_pages(blocks: &[Block], split_level: u8) -> Vec<Page> {
+    let mut pages = Vec::new();
+    let mut front_matter = Vec::new();
+    collect_pages(blocks.iter(), 1, split_level, &mut pages, &mut front_matter);
+
+    if !front_matter.is_empty() || pages.is_empty() {
+        pages.insert(
+            0,
+            Page {
+                file_name: String::new(),
+                title: String::new(),
+                anchor: String::new(),
+                blocks: front_matter,
+            },
+        );
+    }
+    let mut used_names = HashSet::new();
+    used_names.insert(INDEX_FILE.to_string());
+
+    for (index, page) in pages.iter_mut().enumerate() {
+        page.file_name = if index == 0 {
+            INDEX_FILE.to_string()
+        } else {
+            get_file_name(&page.anchor, &mut used_names)
+        };
+    }
+
+    pages
+}
+
+/// Creates a page for every section up to the split level. The content of a section
+/// that belongs to none of its split child sections stays on the page of the section.
+pub(crate) fn collect_pages<'a, I: Iterator<Item = &'a Block>>(
+    blocks: I,
+    level: u8,
+    split_level: u8,
+    pages: &mut Vec<Page>,
+    front_matter: &mut Vec<Block>,
+) {
+    for block in blocks {
+        match block {
+            Block::Section(section) if level <= split_level => {
+                let (children, elements): (Vec<&Block>, Vec<&Block>) = section
+                    .elements
+                    .iter()
+                    .partition(|e| matches!(e, Block::Section(_)) && level < split_level);
+                pages.push(Page {
+                    file_name: String::new(),
+                    title: line_to_string(&section.header.line),
+                    anchor: section.header.anchor.clone(),
+                    blocks: vec![Block::Section(Section {
+                        header: section.header.clone(),
+                        elements: elements.into_iter().cloned().collect(),
+                        metadata: section.metadata.clone(),
+                    })],
+                });
+                collect_pages(
+                    children.into_iter(),
+                    level + 1,
+                    split_level,
+                    pages,
+                    front_matter,
+                );
+            }
+            Block::Null => {}
+            _ => match pages.last_mut() {
+                Some(page) => page.blocks.push(block.clone()),
+                None => front_matter.push(block.clone()),
+            },
+        }
+    }
+}
+
+/// Returns a file name for the page of a section that only contains url safe characters
+fn get_file_name(anchor: &str, used_names: &mut HashSet<String>) -> String {
+    let mut slug = String::new();
+    for c in anchor.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    let slug = if slug.is_empty() { "page" } else { slug };
+
+    let mut name = format!("{}.html", slug);
+    let mut count = 1;
+    while used_names.contains(&name) {
+        count += 1;
+        name = format!("{}-{}.html", slug, count);
+    }
+    used_names.insert(name.clone());
+
+    name
+}
+
+/// Creates the html file of a page with the navigation sidebar and links to the surrounding pages
+fn create_page(
+    settings: &Settings,
+    page: &Page,
+    content: &str,
+    navigation: &str,
+    previous: Option<&Page>,
+    next: Option<&Page>,
+) -> String {
+    let metadata = &settings.metadata;
+    let site_title = metadata.title.clone().unwrap_or_default();
+    let title = match (page.title.is_empty(), site_title.is_empty()) {
+        (false, false) => format!("{} - {}", page.title, site_title),
+        (false, true) => page.title.clone(),
+        _ => site_title.clone(),
+    };
+    let mut head = String::new();
+
+    if let Some(author) = &metadata.author {
+        head.push_str(&format!(
+            "<meta name=\"author\" content=\"{}\">",
+            encode_attribute(author)
+        ));
+    }
+    if let Some(description) = &metadata.description {
+        head.push_str(&format!(
+            "<meta name=\"description\" content=\"{}\">",
+            encode_attribute(description)
+        ));
+    }
+    if !metadata.keywords.is_empty() {
+        head.push_str(&format!(
+            "<meta name=\"keywords\" content=\"{}\">",
+            encode_attribute(&metadata.keywords.join(", "))
+        ));
+    }
+    if settings.features.include_mathjax {
+        head.push_str(&format!(
+            "<script id=\"MathJax-script\" type=\"text/javascript\" async src={}></script>",
+            MATHJAX_URL
+        ));
+    }
+
+    let (search_box, scripts) = if settings.features.include_search {
+        let scripts = format!(
+            "<script src=\"{assets}/{index}\"></script><script src=\"{assets}/{script}\"></script>",
+            assets = ASSET_DIR,
+            index = SEARCH_INDEX_FILE,
+            script = SEARCH_SCRIPT_FILE
+        );
+        (SEARCH_BOX, scripts)
+    } else {
+        ("", String::new())
+    };
+
+    let mut page_navigation = String::new();
+    if let Some(previous) = previous {
+        page_navigation.push_str(&format!(
+            "<a class=\"previous\" href=\"{}\">&larr; {}</a>",
+            encode_attribute(&previous.file_name),
+            encode_minimal(get_link_title(previous, &site_title))
+        ));
+    }
+    if let Some(next) = next {
+        page_navigation.push_str(&format!(
+            "<a class=\"next\" href=\"{}\">{} &rarr;</a>",
+            encode_attribute(&next.file_name),
+            encode_minimal(get_link_title(next, &site_title))
+        ));
+    }
+
+    format!(
+        "<!DOCTYPE html><html lang=\"{lang}\"><head><meta charset=\"UTF-8\">\
+        <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+        <title>{title}</title>{head}<link rel=\"stylesheet\" href=\"{assets}/{style}\"></head>\
+        <body class=\"site\"><nav class=\"siteNavigation\">\
+        <a class=\"siteTitle\" href=\"{index}\">{site_title}</a>{search_box}{navigation}</nav>\
+        <div class=\"sitePage\"><div class=\"content\">{content}\
+        <nav class=\"pageNavigation\">{page_navigation}</nav></div></div>{scripts}</body></html>",
+        lang = encode_attribute(&metadata.language),
+        title = encode_minimal(&title),
+        head = head,
+        assets = ASSET_DIR,
+        style = STYLE_FILE,
+        index = INDEX_FILE,
+        site_title = encode_minimal(if site_title.is_empty() {
+            "Home"
+        } else {
+            &site_title
+        }),
+        search_box = search_box,
+        navigation = navigation,
+        content = content,
+        page_navigation = page_navigation,
+        scripts = scripts
+    )
+}
+
+/// Returns the title of a page for links. Pages without a title are the index page
+fn get_link_title<'a>(page: &'a Page, site_title: &'a str) -> &'a str {
+    if !page.title.is_empty() {
+        &page.title
+    } else if !site_title.is_empty() {
+        site_title
+    } else {
+        "Home"
+    }
+}
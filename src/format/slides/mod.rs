@@ -0,0 +1,207 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::{Block, Document, Metadata, Quote, Section};
+use crate::format::html::html_writer::HTMLWriter;
+use crate::format::html::to_html::{ToHtml, MATHJAX_URL};
+use crate::format::site::{collect_pages, Page};
+use crate::format::style::{get_css_for_theme, get_slides_css_for_theme, CodeStyle};
+use crate::format::{RenderResult, Renderer};
+use minify::html::minify;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+/// The script for the keyboard navigation between slides
+const SLIDES_SCRIPT: &str = include_str!("../assets/slides.js");
+
+/// The metadata key of quotes that contain speaker notes
+const NOTES_KEY: &str = "notes";
+
+/// A single slide with the speaker notes that were taken from its content
+struct Slide {
+    blocks: Vec<Block>,
+    notes: Vec<Quote>,
+}
+
+/// Renders the document to a self-contained html slide deck with one slide per section
+/// of the configured level. Quotes with the `notes` metadata flag are rendered as speaker notes.
+pub fn render_to_slides(document: Document, writer: Box<dyn Write>) -> io::Result<()> {
+    let settings = document.config.lock().clone();
+    let mut pages = Vec::new();
+    let mut front_matter = Vec::new();
+    collect_pages(
+        document.elements.iter(),
+        1,
+        settings.slides.split_level.max(1),
+        &mut pages,
+        &mut front_matter,
+    );
+    let slides: Vec<Slide> = pages.into_iter().map(Slide::from).collect();
+    let front_matter = Slide::from(front_matter);
+
+    let mut writer = HTMLWriter::new(writer, settings.style.theme.clone());
+    writer.set_safe_mode(settings.safe_mode);
+    writer.set_code_style(CodeStyle::load(&settings, &document.get_root_dir()));
+    let metadata = &settings.metadata;
+    writer.write("<!DOCTYPE html><html lang=\"".to_string())?;
+    writer.write_attribute(metadata.language.clone())?;
+    writer.write("\"><head><meta charset=\"UTF-8\">".to_string())?;
+    writer.write(
+        "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">".to_string(),
+    )?;
+
+    if let Some(title) = &metadata.title {
+        writer.write("<title>".to_string())?;
+        writer.write_escaped(title.clone())?;
+        writer.write("</title>".to_string())?;
+    }
+    if let Some(author) = &metadata.author {
+        writer.write("<meta name=\"author\" content=\"".to_string())?;
+        writer.write_attribute(author.clone())?;
+        writer.write("\">".to_string())?;
+    }
+    writer.write("<style>".to_string())?;
+    writer.write(minify(&get_css_for_theme(settings.style.theme.clone())))?;
+    writer.write(get_slides_css_for_theme(settings.style.theme.clone()))?;
+    writer.write(writer.get_code_style().get_css())?;
+    writer.write("</style>".to_string())?;
+
+    for stylesheet in &document.stylesheets {
+        if let Some(data) = &stylesheet.lock().data {
+            writer.write("<style>".to_string())?;
+            writer.write_style_content(minify(&String::from_utf8_lossy(data)))?;
+            writer.write("</style>".to_string())?;
+        }
+    }
+    if settings.features.include_mathjax {
+        writer.write(format!(
+            "<script id=\"MathJax-script\" type=\"text/javascript\" async src={}></script>",
+            MATHJAX_URL
+        ))?;
+    }
+    writer.write("</head><body class=\"slides\">".to_string())?;
+    let mut index = 0;
+
+    if metadata.title.is_some() || !front_matter.blocks.is_empty() {
+        writer.write(format!(
+            "<div class=\"slide titleSlide\" id=\"slide-{}\">",
+            index
+        ))?;
+        if let Some(title) = &metadata.title {
+            writer.write("<h1>".to_string())?;
+            writer.write_escaped(title.clone())?;
+            writer.write("</h1>".to_string())?;
+        }
+        if let Some(author) = &metadata.author {
+            writer.write("<p class=\"author\">".to_string())?;
+            writer.write_escaped(author.clone())?;
+            writer.write("</p>".to_string())?;
+        }
+        front_matter.to_html(&mut writer)?;
+        writer.write("</div>".to_string())?;
+        index += 1;
+    }
+    for slide in &slides {
+        writer.write(format!("<div class=\"slide\" id=\"slide-{}\">", index))?;
+        slide.to_html(&mut writer)?;
+        writer.write("</div>".to_string())?;
+        index += 1;
+    }
+    writer.write("<div class=\"slideCounter\"></div><script>".to_string())?;
+    writer.write(SLIDES_SCRIPT.to_string())?;
+    writer.write("</script></body></html>".to_string())?;
+
+    writer.flush()
+}
+
+/// Renders documents to html slide decks
+pub struct SlidesRenderer;
+
+impl Renderer for SlidesRenderer {
+    fn name(&self) -> &str {
+        "slides"
+    }
+
+    fn extension(&self) -> &str {
+        "html"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        render_to_slides(document, writer)?;
+
+        Ok(())
+    }
+}
+
+impl From<Page> for Slide {
+    fn from(page: Page) -> Self {
+        Self::from(page.blocks)
+    }
+}
+
+impl From<Vec<Block>> for Slide {
+    fn from(blocks: Vec<Block>) -> Self {
+        let mut notes = Vec::new();
+        let blocks = take_notes(blocks, &mut notes);
+
+        Self { blocks, notes }
+    }
+}
+
+impl ToHtml for Slide {
+    fn to_html(&self, writer: &mut HTMLWriter) -> io::Result<()> {
+        for block in &self.blocks {
+            block.to_html(writer)?;
+        }
+        if !self.notes.is_empty() {
+            writer.write("<aside class=\"notes\">".to_string())?;
+            for quote in &self.notes {
+                writer.write("<p>".to_string())?;
+                for line in &quote.text {
+                    line.to_html(writer)?;
+                    writer.write("<br/>".to_string())?;
+                }
+                writer.write("</p>".to_string())?;
+            }
+            writer.write("</aside>".to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the quotes that contain speaker notes from the blocks and its sections
+fn take_notes(blocks: Vec<Block>, notes: &mut Vec<Quote>) -> Vec<Block> {
+    let mut remaining = Vec::new();
+
+    for block in blocks {
+        match block {
+            Block::Quote(quote) if is_notes(&quote) => notes.push(quote),
+            Block::Section(section) => remaining.push(Block::Section(Section {
+                elements: take_notes(section.elements, notes),
+                ..section
+            })),
+            block => remaining.push(block),
+        }
+    }
+
+    remaining
+}
+
+/// Returns if the quote is marked as speaker notes
+fn is_notes(quote: &Quote) -> bool {
+    quote
+        .metadata
+        .as_ref()
+        .map(|m| m.get_bool(NOTES_KEY))
+        .unwrap_or(false)
+}
@@ -4,46 +4,307 @@
  * See LICENSE for more information.
  */
 
-use crate::settings::style_settings::Theme;
+use crate::settings::style_settings::{CodeHighlighting, Theme};
+use crate::settings::Settings;
+use crate::utils::sanitizing::is_inside_root;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
+use syntect::highlighting::{Theme as SyntaxTheme, ThemeSet};
+use syntect::html::{css_for_theme_with_class_style, ClassStyle};
+use syntect::parsing::{SyntaxDefinition, SyntaxSet, SyntaxSetBuilder};
+
+/// The class style of highlighted code. All classes are prefixed to avoid collisions with the page style
+pub const CODE_CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "syntax-" };
 
 /// Returns the css of a theme compiled from sass
 pub fn get_css_for_theme(theme: Theme) -> String {
     let start = Instant::now();
-    let vars = match theme {
+    let vars = get_variables_for_theme(theme);
+    let style = format!("{}\n{}", vars, include_str!("assets/base.scss"));
+
+    let css = compile_sass(&*style);
+
+    log::debug!("Compiled style in {} ms", start.elapsed().as_millis());
+
+    css
+}
+
+/// Returns the css for the navigation of multi page sites compiled from sass
+pub fn get_site_css_for_theme(theme: Theme) -> String {
+    let vars = get_variables_for_theme(theme);
+    let style = format!("{}\n{}", vars, include_str!("assets/site.scss"));
+
+    compile_sass(&style)
+}
+
+/// Returns the css for slide decks compiled from sass
+pub fn get_slides_css_for_theme(theme: Theme) -> String {
+    let vars = get_variables_for_theme(theme);
+    let style = format!("{}\n{}", vars, include_str!("assets/slides.scss"));
+
+    compile_sass(&style)
+}
+
+/// The colors of a theme for formats that can't use the css
+#[derive(Clone, Debug)]
+pub struct ThemeColors {
+    pub background: String,
+    pub text: String,
+    pub secondary: String,
+    pub code_background: String,
+    pub quote_background: String,
+    pub table_background: String,
+}
+
+/// Returns the colors of a theme as hex values without the leading #
+pub fn get_colors_for_theme(theme: Theme) -> ThemeColors {
+    let names = [
+        "background-color",
+        "primary-color",
+        "secondary-color",
+        "code-background",
+        "quote-background",
+        "table-background-alt",
+    ];
+    let mut style = format!("{}\n.colors{{", get_variables_for_theme(theme));
+    for name in &names {
+        // ie-hex-str always returns the color in the #AARRGGBB format
+        style.push_str(&format!("{}:ie-hex-str(${});", name, name));
+    }
+    style.push('}');
+
+    let css = compile_sass(&style);
+    let color = |name: &str| -> String {
+        css.split(&format!("{}:#", name))
+            .nth(1)
+            .map(|c| c.chars().skip(2).take(6).collect())
+            .unwrap_or_else(|| "000000".to_string())
+    };
+
+    ThemeColors {
+        background: color("background-color"),
+        text: color("primary-color"),
+        secondary: color("secondary-color"),
+        code_background: color("code-background"),
+        quote_background: color("quote-background"),
+        table_background: color("table-background-alt"),
+    }
+}
+
+/// Returns the sass variables of a theme
+fn get_variables_for_theme(theme: Theme) -> &'static str {
+    match theme {
         Theme::GitHub => include_str!("assets/light-github.scss"),
         Theme::SolarizedDark => include_str!("assets/dark-solarized.scss"),
         Theme::SolarizedLight => include_str!("assets/light-solarized.scss"),
         Theme::OceanDark => include_str!("assets/dark-ocean.scss"),
         Theme::OceanLight => include_str!("assets/light-ocean.scss"),
         Theme::MagicDark => include_str!("assets/dark-magic.scss"),
-    };
-    let style = format!("{}\n{}", vars, include_str!("assets/base.scss"));
+    }
+}
 
-    let css = compile_sass(&*style);
+/// The name of the code theme that matches the magic dark theme
+const MAGIC_DARK_CODE_THEME: &str = "Magic (dark)";
 
-    log::debug!("Compiled style in {} ms", start.elapsed().as_millis());
+lazy_static::lazy_static! {
+    static ref SYNTAXES: Arc<SyntaxSet> = Arc::new(SyntaxSet::load_defaults_nonewlines());
+    static ref CODE_THEMES: ThemeSet = load_default_code_themes();
+}
 
-    css
+/// The syntaxes and themes code blocks are highlighted with
+#[derive(Clone, Debug)]
+pub struct CodeStyle {
+    pub syntax_set: Arc<SyntaxSet>,
+    pub theme: SyntaxTheme,
+    pub light_theme: SyntaxTheme,
+    pub dark_theme: SyntaxTheme,
+    pub highlighting: CodeHighlighting,
 }
 
-/// Returns the syntax theme for a given theme
-pub fn get_code_theme_for_theme(theme: Theme) -> (syntect::highlighting::Theme, SyntaxSet) {
-    lazy_static::lazy_static! { static ref PS: SyntaxSet = SyntaxSet::load_defaults_nonewlines(); }
-    lazy_static::lazy_static! { static ref TS: ThemeSet = ThemeSet::load_defaults(); }
+impl CodeStyle {
+    /// Returns the built-in code style of a theme
+    pub fn for_theme(theme: Theme) -> Self {
+        let (name, light, dark) = get_code_theme_names_for_theme(theme);
 
-    let theme = match theme {
-        Theme::GitHub => "InspiredGitHub",
-        Theme::SolarizedDark => "Solarized (dark)",
-        Theme::SolarizedLight => "Solarized (light)",
-        Theme::OceanDark => "base16-ocean.dark",
-        Theme::OceanLight => "base16-ocean.light",
-        Theme::MagicDark => "base16-ocean.dark",
-    };
+        Self {
+            syntax_set: Arc::clone(&SYNTAXES),
+            theme: CODE_THEMES.themes[name].clone(),
+            light_theme: CODE_THEMES.themes[light].clone(),
+            dark_theme: CODE_THEMES.themes[dark].clone(),
+            highlighting: CodeHighlighting::Inline,
+        }
+    }
+
+    /// Loads the code style with the syntaxes and code themes of the settings.
+    /// Paths of manifests are relative to the manifest, other relative paths
+    /// are resolved from the given directory.
+    pub fn load(settings: &Settings, base_dir: &Path) -> Self {
+        let style = &settings.style;
+        let mut code_style = Self::for_theme(style.theme.clone());
+        code_style.highlighting = style.code_highlighting.clone();
+
+        let resolve = |path: &String| {
+            let path = base_dir.join(path);
+            if settings.safe_mode && !is_inside_root(&path, base_dir) {
+                log::error!(
+                    "{:?} is ignored: Files outside of the project root are disabled in safe mode",
+                    path
+                );
+                None
+            } else {
+                Some(path)
+            }
+        };
+
+        if !style.syntaxes.is_empty() {
+            let mut builder = SyntaxSet::clone(&SYNTAXES).into_builder();
+            for path in style.syntaxes.iter().filter_map(resolve) {
+                if let Err(e) = add_syntaxes(&mut builder, &path) {
+                    log::error!("Failed to load syntax {:?}: {}", path, e);
+                }
+            }
+            code_style.syntax_set = Arc::new(builder.build());
+        }
+        let mut themes = HashMap::new();
+        for path in style.code_themes.iter().filter_map(resolve) {
+            if let Err(e) = add_code_themes(&mut themes, &path) {
+                log::error!("Failed to load code theme {:?}: {}", path, e);
+            }
+        }
+        let find_theme = |name: &String| {
+            let theme = themes.get(name).or_else(|| CODE_THEMES.themes.get(name));
+            if theme.is_none() {
+                log::error!("Unknown code theme \"{}\"", name);
+            }
+            theme.cloned()
+        };
+
+        if let Some(theme) = style.code_theme.as_ref().and_then(find_theme) {
+            code_style.theme = theme.clone();
+            code_style.light_theme = theme;
+        }
+        if let Some(theme) = style.code_theme_dark.as_ref().and_then(find_theme) {
+            code_style.dark_theme = theme;
+        }
+
+        code_style
+    }
+
+    /// Returns the css for code that is highlighted with classes.
+    /// The css is empty if the colors are written inline.
+    /// The css of the themes isn't compiled with sass as user supplied themes
+    /// don't have to be valid scss.
+    pub fn get_css(&self) -> String {
+        match self.highlighting {
+            CodeHighlighting::Inline => String::new(),
+            CodeHighlighting::Classes => get_code_theme_css(&self.theme),
+            CodeHighlighting::LightDark => {
+                // the background of code blocks is set by the page style and needs to be overridden
+                let dark_background = self
+                    .dark_theme
+                    .settings
+                    .background
+                    .map(|c| {
+                        format!(
+                            "code pre.syntax-code{{background-color:#{:02x}{:02x}{:02x} !important;}}",
+                            c.r, c.g, c.b
+                        )
+                    })
+                    .unwrap_or_default();
+
+                format!(
+                    "{}\n@media (prefers-color-scheme: dark) {{\n{}\n{}\n}}",
+                    get_code_theme_css(&self.light_theme),
+                    get_code_theme_css(&self.dark_theme),
+                    dark_background
+                )
+            }
+        }
+    }
+}
+
+/// Returns the names of the code theme of a theme
+/// and the light and dark code themes that belong to it
+fn get_code_theme_names_for_theme(theme: Theme) -> (&'static str, &'static str, &'static str) {
+    match theme {
+        Theme::GitHub => ("InspiredGitHub", "InspiredGitHub", "base16-ocean.dark"),
+        Theme::SolarizedDark => ("Solarized (dark)", "Solarized (light)", "Solarized (dark)"),
+        Theme::SolarizedLight => ("Solarized (light)", "Solarized (light)", "Solarized (dark)"),
+        Theme::OceanDark => (
+            "base16-ocean.dark",
+            "base16-ocean.light",
+            "base16-ocean.dark",
+        ),
+        Theme::OceanLight => (
+            "base16-ocean.light",
+            "base16-ocean.light",
+            "base16-ocean.dark",
+        ),
+        Theme::MagicDark => (
+            MAGIC_DARK_CODE_THEME,
+            "base16-ocean.light",
+            MAGIC_DARK_CODE_THEME,
+        ),
+    }
+}
+
+/// Returns the css of a code theme for code that is highlighted with classes
+fn get_code_theme_css(theme: &SyntaxTheme) -> String {
+    // the name of the theme is written into a css comment
+    let mut theme = theme.clone();
+    theme.name = theme.name.map(|name| name.replace("*/", ""));
+
+    css_for_theme_with_class_style(&theme, CODE_CLASS_STYLE)
+}
+
+/// Returns the default code themes of syntect and the code themes of snekdown
+fn load_default_code_themes() -> ThemeSet {
+    let mut themes = ThemeSet::load_defaults();
+    let magic_dark =
+        ThemeSet::load_from_reader(&mut Cursor::new(include_str!("assets/magic-dark.tmTheme")))
+            .unwrap();
+    themes
+        .themes
+        .insert(MAGIC_DARK_CODE_THEME.to_string(), magic_dark);
+
+    themes
+}
+
+/// Adds a syntax definition file or all syntax definitions of a directory
+fn add_syntaxes(builder: &mut SyntaxSetBuilder, path: &Path) -> Result<(), Box<dyn Error>> {
+    if path.is_dir() {
+        builder.add_from_folder(path, false)?;
+    } else {
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string());
+        let syntax =
+            SyntaxDefinition::load_from_str(&fs::read_to_string(path)?, false, name.as_deref())?;
+        builder.add(syntax);
+    }
+
+    Ok(())
+}
+
+/// Adds a code theme file or all code themes of a directory by their file names
+fn add_code_themes(
+    themes: &mut HashMap<String, SyntaxTheme>,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if path.is_dir() {
+        themes.extend(ThemeSet::load_from_folder(path)?.themes);
+    } else {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        themes.insert(name, ThemeSet::get_theme(path)?);
+    }
 
-    return (TS.themes[theme].clone(), PS.clone());
+    Ok(())
 }
 
 fn compile_sass(sass: &str) -> String {
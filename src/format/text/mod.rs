@@ -0,0 +1,50 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::Document;
+use crate::format::text::text_writer::TextWriter;
+use crate::format::text::to_text::ToText;
+use crate::format::{RenderResult, Renderer};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub mod text_writer;
+pub mod to_text;
+
+/// Renders the document to plain text wrapped at the configured width.
+/// Links are written as numbered references at the end of the text.
+pub fn render_to_text(document: Document, inner: Box<dyn Write>) -> io::Result<()> {
+    let width = document.config.lock().text.wrap_width;
+    let mut writer = TextWriter::new(inner, width);
+    document.to_text(&mut writer)?;
+
+    writer.flush()
+}
+
+/// Renders documents to plain text
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn extension(&self) -> &str {
+        "txt"
+    }
+
+    fn render(
+        &mut self,
+        document: Document,
+        writer: Box<dyn Write>,
+        _: Option<&Path>,
+    ) -> RenderResult<()> {
+        render_to_text(document, writer)?;
+
+        Ok(())
+    }
+}
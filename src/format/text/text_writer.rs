@@ -0,0 +1,157 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::io;
+use std::io::Write;
+
+pub struct TextWriter {
+    inner: Box<dyn Write>,
+    width: usize,
+    prefixes: Vec<String>,
+    buffer: String,
+    links: Vec<String>,
+}
+
+impl TextWriter {
+    /// Creates a new writer that wraps text at the given width.
+    /// A width of 0 disables wrapping.
+    pub fn new(inner: Box<dyn Write>, width: usize) -> Self {
+        Self {
+            inner,
+            width,
+            prefixes: Vec::new(),
+            buffer: String::new(),
+            links: Vec::new(),
+        }
+    }
+
+    /// Adds inline text to the block that is currently being built
+    pub fn push_text(&mut self, text: &str) {
+        self.buffer.push_str(text);
+    }
+
+    /// Adds a hard line break to the block that is currently being built
+    pub fn line_break(&mut self) {
+        self.buffer.push('\n');
+    }
+
+    /// Returns if the block that is currently being built is empty or ends with a line break
+    pub fn ends_with_break(&self) -> bool {
+        self.buffer.is_empty() || self.buffer.ends_with('\n')
+    }
+
+    /// Returns the text of the current block and starts a new one
+    pub fn take_text(&mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+
+    /// Writes the current block wrapped at the configured width.
+    /// The first line starts with the marker and the following lines
+    /// are indented by the width of the marker.
+    pub fn write_block(&mut self, marker: &str) -> io::Result<()> {
+        let text = self.take_text();
+        let indent = " ".repeat(text_width(marker));
+        let available = self
+            .available_width()
+            .saturating_sub(text_width(marker))
+            .max(1);
+        let mut first = true;
+
+        for hard_line in text.trim_end().split('\n') {
+            for line in wrap_line(hard_line, available) {
+                let start = if first { marker } else { &indent };
+                first = false;
+                self.write_line(&format!("{}{}", start, line))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a single line without wrapping it
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line = format!("{}{}", self.prefixes.concat(), line);
+        self.inner.write_all(line.trim_end().as_bytes())?;
+
+        self.inner.write_all(b"\n")
+    }
+
+    /// Writes an empty line to separate blocks
+    pub fn write_empty_line(&mut self) -> io::Result<()> {
+        self.write_line("")
+    }
+
+    /// Adds a prefix that is written at the start of every following line.
+    /// Used for list item indentation and quotes.
+    pub fn push_prefix(&mut self, prefix: &str) {
+        self.prefixes.push(prefix.to_string());
+    }
+
+    /// Removes the last added prefix
+    pub fn pop_prefix(&mut self) {
+        self.prefixes.pop();
+    }
+
+    /// Returns the number of characters that fit in a line after the prefixes
+    pub fn available_width(&self) -> usize {
+        if self.width == 0 {
+            usize::MAX
+        } else {
+            self.width
+                .saturating_sub(text_width(&self.prefixes.concat()))
+                .max(1)
+        }
+    }
+
+    /// Adds a link and returns the number it is referenced with
+    pub fn add_link(&mut self, url: &str) -> usize {
+        if let Some(index) = self.links.iter().position(|l| l == url) {
+            index + 1
+        } else {
+            self.links.push(url.to_string());
+            self.links.len()
+        }
+    }
+
+    /// Returns all links in the order they were first referenced
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+
+    /// Flushes the writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the number of characters in the text
+pub fn text_width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Splits the line into lines that are at most the given width long.
+/// Words that are longer than the width are put on their own line.
+pub fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in line.split_whitespace() {
+        if current.is_empty() {
+            current.push_str(word);
+        } else if text_width(&current) + 1 + text_width(word) <= width {
+            current.push(' ');
+            current.push_str(word);
+        } else {
+            lines.push(std::mem::take(&mut current));
+            current.push_str(word);
+        }
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
@@ -0,0 +1,496 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use crate::elements::*;
+use crate::format::text::text_writer::{text_width, TextWriter};
+use crate::format::PlaceholderTemplate;
+use crate::references::glossary::{GlossaryDisplay, GlossaryReference};
+use crate::references::templates::{Template, TemplateVariable};
+use crate::utils::entities::decode_entity;
+use std::io;
+
+/// The characters headings are underlined with by their level
+const HEADING_UNDERLINES: [char; 4] = ['=', '-', '~', '.'];
+
+pub trait ToText {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()>;
+}
+
+impl ToText for Element {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        match self {
+            Element::Block(block) => block.to_text(writer),
+            Element::Inline(inline) => inline.to_text(writer),
+            Element::Line(line) => line.to_text(writer),
+        }
+    }
+}
+
+impl ToText for Line {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        match self {
+            Line::Text(text) => text.to_text(writer),
+            Line::Ruler(ruler) => ruler.to_text(writer),
+            Line::Centered(centered) => centered.to_text(writer),
+            Line::RefLink(_) => self.as_raw_text().to_text(writer),
+            Line::Anchor(a) => a.inner.to_text(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToText for Inline {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        match self {
+            Inline::Url(url) => url.to_text(writer),
+            Inline::Bold(BoldText { value })
+            | Inline::Italic(ItalicText { value })
+            | Inline::Underlined(UnderlinedText { value })
+            | Inline::Striked(StrikedText { value })
+            | Inline::Superscript(SuperscriptText { value }) => {
+                for inline in value {
+                    inline.to_text(writer)?;
+                }
+                Ok(())
+            }
+            Inline::Colored(colored) => colored.value.to_text(writer),
+            Inline::Image(img) => img.to_text(writer),
+            Inline::Placeholder(placeholder) => placeholder.read().unwrap().to_text(writer),
+            Inline::Checkbox(checkbox) => checkbox.to_text(writer),
+            Inline::Emoji(emoji) => {
+                writer.push_text(&emoji.value.to_string());
+                Ok(())
+            }
+            Inline::BibReference(bibref) => {
+                writer.push_text(&format!("[{}]", bibref.read().unwrap().get_formatted()));
+                Ok(())
+            }
+            Inline::TemplateVar(var) => var.read().unwrap().to_text(writer),
+            Inline::Math(m) => {
+                writer.push_text(m.source.trim());
+                Ok(())
+            }
+            Inline::LineBreak => {
+                writer.line_break();
+                Ok(())
+            }
+            Inline::CharacterCode(code) => code.to_text(writer),
+            Inline::GlossaryReference(gloss) => gloss.lock().to_text(writer),
+            Inline::Arrow(a) => a.to_text(writer),
+            Inline::Anchor(a) => a.inner.to_text(writer),
+            _ => {
+                writer.push_text(&self.as_plain_text().value);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ToText for Block {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        match self {
+            Block::Paragraph(para) => para.to_text(writer),
+            Block::List(list) => list.to_text(writer),
+            Block::Table(table) => table.to_text(writer),
+            Block::CodeBlock(code) => code.to_text(writer),
+            Block::Quote(quote) => quote.to_text(writer),
+            Block::Section(section) => section.to_text(writer),
+            Block::Placeholder(placeholder) => placeholder.read().unwrap().to_text(writer),
+            Block::MathBlock(m) => write_preformatted(writer, m.source.trim()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ToText for MetadataValue {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        match self {
+            MetadataValue::Placeholder(ph) => ph.read().unwrap().to_text(writer),
+            MetadataValue::Template(t) => t.to_text(writer),
+            _ => {
+                writer.push_text(&self.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ToText for Document {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        for element in &self.elements {
+            element.to_text(writer)?;
+        }
+
+        if !writer.links().is_empty() {
+            write_heading(writer, "Links", 2)?;
+            let links = writer.links().to_vec();
+
+            for (index, link) in links.iter().enumerate() {
+                writer.push_text(link);
+                writer.write_block(&format!("[{}] ", index + 1))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for Section {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        self.header.line.to_text(writer)?;
+        let title = writer.take_text().replace('\n', " ");
+        write_heading(writer, title.trim(), self.header.size)?;
+
+        for element in &self.elements {
+            element.to_text(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a heading that is underlined with a character depending on its level
+fn write_heading(writer: &mut TextWriter, title: &str, size: u8) -> io::Result<()> {
+    let index = (size.max(1) as usize - 1).min(HEADING_UNDERLINES.len() - 1);
+    let underline = HEADING_UNDERLINES[index]
+        .to_string()
+        .repeat(text_width(title).min(writer.available_width()));
+
+    writer.write_line(title)?;
+    writer.write_line(&underline)?;
+
+    writer.write_empty_line()
+}
+
+impl ToText for Paragraph {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        for element in &self.elements {
+            if !writer.ends_with_break() {
+                writer.push_text(" ");
+            }
+            element.to_text(writer)?;
+        }
+        let text = writer.take_text();
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        writer.push_text(&text);
+        writer.write_block("")?;
+
+        writer.write_empty_line()
+    }
+}
+
+impl ToText for List {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        write_list(writer, self.ordered, &self.items)?;
+
+        writer.write_empty_line()
+    }
+}
+
+/// Writes the items of a list with the children indented below each item
+fn write_list(writer: &mut TextWriter, ordered: bool, items: &[ListItem]) -> io::Result<()> {
+    for (index, item) in items.iter().enumerate() {
+        let marker = if ordered {
+            format!("{}. ", index + 1)
+        } else {
+            "- ".to_string()
+        };
+        item.text.to_text(writer)?;
+        writer.write_block(&marker)?;
+
+        if let Some(first) = item.children.first() {
+            writer.push_prefix(&" ".repeat(text_width(&marker)));
+            write_list(writer, first.ordered, &item.children)?;
+            writer.pop_prefix();
+        }
+    }
+
+    Ok(())
+}
+
+impl ToText for Table {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        let mut rows = Vec::new();
+
+        for row in std::iter::once(&self.header).chain(self.rows.iter()) {
+            let mut cells = Vec::new();
+            for cell in &row.cells {
+                cell.text.to_text(writer)?;
+                let text = writer.take_text();
+                cells.push(text.split_whitespace().collect::<Vec<&str>>().join(" "));
+            }
+            rows.push(cells);
+        }
+        let column_count = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let widths = (0..column_count)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|r| r.get(i))
+                    .map(|c| text_width(c))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<usize>>();
+        let separator = |c: char| {
+            widths.iter().fold("+".to_string(), |line, width| {
+                format!("{}{}+", line, c.to_string().repeat(width + 2))
+            })
+        };
+
+        writer.write_line(&separator('-'))?;
+        for (index, row) in rows.iter().enumerate() {
+            let mut line = "|".to_string();
+            for (column, width) in widths.iter().enumerate() {
+                let cell = row.get(column).map(|c| c.as_str()).unwrap_or("");
+                line.push_str(&format!(
+                    " {}{} |",
+                    cell,
+                    " ".repeat(width - text_width(cell))
+                ));
+            }
+            writer.write_line(&line)?;
+
+            if index == 0 {
+                writer.write_line(&separator('='))?;
+            }
+        }
+        if rows.len() > 1 {
+            writer.write_line(&separator('-'))?;
+        }
+
+        writer.write_empty_line()
+    }
+}
+
+impl ToText for CodeBlock {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        if let Some(file_name) = self.get_file_name() {
+            writer.write_line(&file_name)?;
+        }
+        write_preformatted(writer, &self.get_numbered_lines().join("\n"))?;
+
+        if let Some(caption) = self.get_caption() {
+            writer.push_text(&caption);
+            writer.write_block("")?;
+            writer.write_empty_line()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes preformatted text indented and without wrapping it
+fn write_preformatted(writer: &mut TextWriter, text: &str) -> io::Result<()> {
+    writer.push_prefix("    ");
+    for line in text.trim_matches('\n').lines() {
+        writer.write_line(line)?;
+    }
+    writer.pop_prefix();
+
+    writer.write_empty_line()
+}
+
+impl ToText for Quote {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        writer.push_prefix("> ");
+        for line in &self.text {
+            line.to_text(writer)?;
+            if !writer.ends_with_break() {
+                writer.line_break();
+            }
+        }
+        writer.write_block("")?;
+
+        if let Some(meta) = &self.metadata {
+            meta.to_text(writer)?;
+            writer.write_block("-- ")?;
+        }
+        writer.pop_prefix();
+
+        writer.write_empty_line()
+    }
+}
+
+impl ToText for Ruler {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        let width = writer.available_width().min(80);
+        writer.write_line(&"-".repeat(width))?;
+
+        writer.write_empty_line()
+    }
+}
+
+impl ToText for TextLine {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        for text in &self.subtext {
+            text.to_text(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for Image {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        writer.push_text("[Image");
+        if let Some(description) = &self.url.description {
+            writer.push_text(": ");
+            for inline in description {
+                inline.to_text(writer)?;
+            }
+        }
+        writer.push_text("]");
+        if !self.url.url.starts_with('#') {
+            let index = writer.add_link(&self.url.url);
+            writer.push_text(&format!(" [{}]", index));
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for Url {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        if let Some(description) = &self.description {
+            for desc in description {
+                desc.to_text(writer)?;
+            }
+            if !self.url.starts_with('#') {
+                let index = writer.add_link(&self.url);
+                writer.push_text(&format!(" [{}]", index));
+            }
+        } else {
+            writer.push_text(&self.url);
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for Placeholder {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            value.to_text(writer)
+        } else {
+            log::debug!("Unknown placeholder [[{}]]", self.name.clone());
+            writer.push_text(&format!("[[{}]]", self.name));
+            Ok(())
+        }
+    }
+}
+
+impl ToText for InlineMetadata {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        if let Some(MetadataValue::String(format)) = self.data.get("display") {
+            let mut template = PlaceholderTemplate::new(format.clone());
+            self.data
+                .iter()
+                .for_each(|(k, v)| template.add_replacement(k, &v.to_string()));
+
+            writer.push_text(&template.render());
+        } else {
+            for (k, v) in &self.data {
+                writer.push_text(&format!("{}={},", k, v.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToText for Centered {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        self.line.to_text(writer)?;
+        let text = writer.take_text();
+        let available = writer.available_width();
+
+        for line in text.trim_end().split('\n') {
+            let line = line.trim();
+            let padding = available.saturating_sub(text_width(line)) / 2;
+            writer.write_line(&format!("{}{}", " ".repeat(padding), line))?;
+        }
+
+        writer.write_empty_line()
+    }
+}
+
+impl ToText for Checkbox {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        if self.value {
+            writer.push_text("[x]");
+        } else {
+            writer.push_text("[ ]");
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for Template {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        for element in &self.text {
+            element.to_text(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for TemplateVariable {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        if let Some(value) = &self.value {
+            writer.push_text(&self.prefix);
+            value.to_text(writer)?;
+            writer.push_text(&self.suffix);
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for CharacterCode {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        match decode_entity(&self.code) {
+            Some(decoded) => writer.push_text(&decoded),
+            None => writer.push_text(&format!("&{};", self.code)),
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for GlossaryReference {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        if let Some(entry) = &self.entry {
+            let entry = entry.lock();
+            match self.display {
+                GlossaryDisplay::Short => writer.push_text(&entry.short),
+                GlossaryDisplay::Long => writer.push_text(&entry.long),
+            }
+        } else {
+            writer.push_text(&format!("~{}", self.short));
+        }
+
+        Ok(())
+    }
+}
+
+impl ToText for Arrow {
+    fn to_text(&self, writer: &mut TextWriter) -> io::Result<()> {
+        let arrow = match self {
+            Arrow::RightArrow => "\u{27F6}",
+            Arrow::LeftArrow => "\u{27F5}",
+            Arrow::LeftRightArrow => "\u{27F7}",
+            Arrow::BigRightArrow => "\u{27F9}",
+            Arrow::BigLeftArrow => "\u{27F8}",
+            Arrow::BigLeftRightArrow => "\u{27FA}",
+        };
+        writer.push_text(arrow);
+
+        Ok(())
+    }
+}
@@ -9,15 +9,14 @@ use env_logger::Env;
 use log::{Level, LevelFilter};
 use notify::{watcher, RecursiveMode, Watcher};
 use snekdown::elements::Document;
-use snekdown::format::html::html_writer::HTMLWriter;
-use snekdown::format::html::to_html::ToHtml;
+use snekdown::format::{RenderResult, RendererRegistry};
 use snekdown::parser::ParserOptions;
 use snekdown::settings::Settings;
 use snekdown::utils::caching::CacheStorage;
 use snekdown::Parser;
 use std::fs::{File, OpenOptions};
-use std::io::{stdout, BufWriter, Write};
-use std::path::PathBuf;
+use std::io::{sink, stdout, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::sync::mpsc::channel;
 use std::time::{Duration, Instant};
@@ -52,17 +51,29 @@ struct RenderOptions {
     #[structopt(parse(from_os_str))]
     input: PathBuf,
 
-    /// Path for the output file
+    /// Paths for the output files. The outputs of the manifest are used if none are given
     #[structopt(parse(from_os_str))]
-    output: Option<PathBuf>,
+    output: Vec<PathBuf>,
 
     /// If the output should be written to stdout instead of the output file
     #[structopt(long = "stdout")]
     stdout: bool,
 
-    /// the output format
-    #[structopt(short, long, default_value = "html")]
+    /// the output formats in the order of the output files.
+    /// Inferred from the extension of the output file if not set
+    #[structopt(short, long, number_of_values = 1)]
+    format: Vec<String>,
+
+    /// Renders the document in safe mode. Imports outside of the project root,
+    /// remote downloads and raw html are disabled
+    #[structopt(long)]
+    safe: bool,
+}
+
+/// A format and the path it is rendered to
+struct Target {
     format: String,
+    output: Option<PathBuf>,
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -101,7 +112,10 @@ fn main() {
 
     match &opt.sub_command {
         SubCommand::Render(opt) => {
-            let _ = render(&opt);
+            let (_, result) = render(&opt, &mut RendererRegistry::with_defaults());
+            if result.is_err() {
+                exit(1)
+            }
         }
         SubCommand::Watch(opt) => watch(&opt),
         SubCommand::ClearCache => {
@@ -153,24 +167,35 @@ fn init() {
 
 /// Watches a file with all of its imports and renders on change
 fn watch(opt: &WatchOptions) {
-    let parser = render(&opt.render_options);
+    let mut registry = RendererRegistry::with_defaults();
+    let (parser, _) = render(&opt.render_options, &mut registry);
     let (tx, rx) = channel();
     let mut watcher = watcher(tx, Duration::from_millis(opt.debounce)).unwrap();
 
-    for path in parser.get_paths() {
+    for path in get_watched_paths(&opt.render_options, &parser) {
         watcher.watch(path, RecursiveMode::NonRecursive).unwrap();
     }
     while let Ok(_) = rx.recv() {
         println!("---");
-        let parser = render(&opt.render_options);
-        for path in parser.get_paths() {
+        let (parser, _) = render(&opt.render_options, &mut registry);
+        for path in get_watched_paths(&opt.render_options, &parser) {
             watcher.watch(path, RecursiveMode::NonRecursive).unwrap();
         }
     }
 }
 
-/// Renders the document to the output path
-fn render(opt: &RenderOptions) -> Parser {
+/// Returns the files that are watched for changes. Json documents aren't parsed
+/// and have no imports so only the input file itself is watched.
+fn get_watched_paths(opt: &RenderOptions, parser: &Parser) -> Vec<PathBuf> {
+    if is_json_document(&opt.input) {
+        vec![opt.input.clone()]
+    } else {
+        parser.get_paths()
+    }
+}
+
+/// Renders the document to all outputs reusing the parsed document for every format
+fn render(opt: &RenderOptions, registry: &mut RendererRegistry) -> (Parser, RenderResult<()>) {
     if !opt.input.exists() {
         log::error!(
             "The input file {} could not be found",
@@ -182,64 +207,162 @@ fn render(opt: &RenderOptions) -> Parser {
 
     let start = Instant::now();
 
-    let mut parser = Parser::with_defaults(ParserOptions::default().add_path(opt.input.clone()));
-    let document = parser.parse();
+    let mut parser = Parser::with_defaults(
+        ParserOptions::default()
+            .add_path(opt.input.clone())
+            .safe_mode(opt.safe),
+    );
+    let document = if is_json_document(&opt.input) {
+        read_json(&opt.input, opt.safe)
+    } else {
+        parser.parse()
+    };
 
     log::info!("Parsing + Processing took: {:?}", start.elapsed());
-    let start_render = Instant::now();
-
-    if let Some(output) = &opt.output {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(output)
-            .unwrap();
+    let targets = get_targets(opt, &document, registry);
 
-        render_format(opt, document, BufWriter::new(file));
-    } else {
-        if !opt.stdout {
-            log::error!("No output file specified");
+    if targets.is_empty() {
+        log::error!("No output file specified");
+        exit(1)
+    }
+    for target in &targets {
+        if registry.get(&target.format).is_none() {
+            log::error!(
+                "Unknown format {}. Available formats are: {}",
+                target.format,
+                registry.names().join(", ")
+            );
             exit(1)
         }
-        render_format(opt, document, BufWriter::new(stdout()));
     }
-
-    log::info!("Rendering took: {:?}", start_render.elapsed());
+    let mut result = Ok(());
+
+    for target in &targets {
+        let start_render = Instant::now();
+
+        match render_target(target, document.clone(), registry) {
+            Ok(_) => log::info!(
+                "Rendering {} took: {:?}",
+                target.format,
+                start_render.elapsed()
+            ),
+            Err(e) => {
+                log::error!("Failed to render {}: {}", target.format, e);
+                result = Err(e);
+            }
+        }
+    }
     log::info!("Total: {:?}", start.elapsed());
 
-    parser
+    (parser, result)
 }
 
-#[cfg(not(feature = "pdf"))]
-fn render_format<W: Write + 'static>(opt: &RenderOptions, document: Document, writer: W) {
-    match opt.format.as_str() {
-        "html" => render_html(document, writer),
-        _ => log::error!("Unknown format {}", opt.format),
+/// Renders the document to the output of the target
+fn render_target(
+    target: &Target,
+    document: Document,
+    registry: &mut RendererRegistry,
+) -> RenderResult<()> {
+    let renderer = registry
+        .get(&target.format)
+        .ok_or_else(|| format!("Unknown format {}", target.format))?;
+
+    match &target.output {
+        Some(output) if renderer.writes_directory() => {
+            renderer.render(document, Box::new(sink()), Some(output))
+        }
+        Some(output) => {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .truncate(true)
+                .create(true)
+                .open(output)?;
+
+            renderer.render(document, Box::new(BufWriter::new(file)), Some(output))
+        }
+        None if renderer.writes_directory() => Err("No output directory specified".into()),
+        None => renderer.render(document, Box::new(BufWriter::new(stdout())), None),
     }
 }
 
-#[cfg(feature = "pdf")]
-fn render_format<W: Write + 'static>(opt: &RenderOptions, document: Document, writer: W) {
-    match opt.format.as_str() {
-        "html" => render_html(document, writer),
-        "pdf" => render_pdf(document, writer),
-        _ => log::error!("Unknown format {}", opt.format),
+/// Returns the targets given as arguments or the outputs of the manifest if there are none.
+/// Outputs of the manifest are relative to the input file.
+fn get_targets(
+    opt: &RenderOptions,
+    document: &Document,
+    registry: &RendererRegistry,
+) -> Vec<Target> {
+    let get_format = |format: Option<&String>, output: &Path| {
+        format
+            .cloned()
+            .or_else(|| registry.get_format_for_path(output))
+            .unwrap_or_else(|| "html".to_string())
+    };
+
+    if !opt.output.is_empty() {
+        if opt.format.len() > opt.output.len() {
+            log::warn!("Ignoring formats without an output file");
+        }
+        opt.output
+            .iter()
+            .enumerate()
+            .map(|(index, output)| Target {
+                format: get_format(opt.format.get(index), output),
+                output: Some(output.clone()),
+            })
+            .collect()
+    } else if opt.stdout {
+        vec![Target {
+            format: opt
+                .format
+                .first()
+                .cloned()
+                .unwrap_or_else(|| "html".to_string()),
+            output: None,
+        }]
+    } else {
+        let base_dir = opt.input.parent().unwrap_or_else(|| Path::new(""));
+
+        document
+            .config
+            .lock()
+            .outputs
+            .iter()
+            .map(|o| {
+                let output = base_dir.join(&o.path);
+                Target {
+                    format: get_format(o.format.as_ref(), &output),
+                    output: Some(output),
+                }
+            })
+            .collect()
     }
 }
 
-fn render_html<W: Write + 'static>(document: Document, writer: W) {
-    let mut writer = HTMLWriter::new(Box::new(writer), document.config.lock().style.theme.clone());
-    document.to_html(&mut writer).unwrap();
-    writer.flush().unwrap();
+/// Returns if the input is a json document that has been rendered with the json format
+fn is_json_document(input: &Path) -> bool {
+    input
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase() == "json")
+        .unwrap_or(false)
 }
 
-#[cfg(feature = "pdf")]
-fn render_pdf<W: Write + 'static>(document: Document, mut writer: W) {
-    use snekdown::format::chromium_pdf::render_to_pdf;
+/// Reads a document from its json representation
+fn read_json(input: &Path, safe: bool) -> Document {
+    use snekdown::format::json::{read_json_document, read_untrusted_json_document};
 
-    let result = render_to_pdf(document).expect("Failed to render pdf!");
-    writer.write_all(&result).unwrap();
-    writer.flush().unwrap();
+    let reader = BufReader::new(File::open(input).unwrap());
+    let result = if safe {
+        read_untrusted_json_document(reader, &input.canonicalize().unwrap())
+    } else {
+        read_json_document(reader)
+    };
+    match result {
+        Ok(document) => document,
+        Err(e) => {
+            log::error!("Failed to read json document: {}", e);
+            exit(1)
+        }
+    }
 }
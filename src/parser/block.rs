@@ -138,17 +138,32 @@ impl ParseBlock for Parser {
         self.ctm
             .assert_sequence(&SQ_CODE_BLOCK, Some(start_index))?;
         self.ctm.seek_one()?;
-        let language = self.ctm.get_string_until_any(&[LB], &[])?;
+        let mut language = self.ctm.get_string_until_any(&[META_OPEN, LB], &[])?;
+        let metadata = self.parse_inline_metadata().ok();
+        if metadata.is_none() && !self.ctm.check_char(&LB) {
+            language.push_str(&self.ctm.get_string_until_any(&[LB], &[])?);
+        } else {
+            self.ctm.get_string_until_any(&[LB], &[])?;
+        }
         self.ctm.seek_one()?;
-        let text = self.ctm.get_string_until_sequence(&[&SQ_CODE_BLOCK], &[])?;
+        let mut text = self.ctm.get_string_until_sequence(&[&SQ_CODE_BLOCK], &[])?;
 
         for _ in 0..2 {
             self.ctm.try_seek();
         }
+        if metadata
+            .as_ref()
+            .map(|m| m.get_bool("dedent"))
+            .unwrap_or(false)
+        {
+            text = strip_common_indentation(&text);
+        }
 
         Ok(CodeBlock {
-            language,
+            language: language.trim().to_string(),
             code: text,
+            metadata,
+            number: None,
         })
     }
 
@@ -163,7 +178,8 @@ impl ParseBlock for Parser {
             self.ctm.try_seek();
         }
         Ok(MathBlock {
-            expression: asciimath_rs::parse(text),
+            expression: asciimath_rs::parse(text.clone()),
+            source: text,
         })
     }
 
@@ -358,7 +374,29 @@ impl ParseBlock for Parser {
             ImportType::Stylesheet(_) => Ok(None),
             ImportType::Bibliography(_) => Ok(None),
             ImportType::Manifest(_) => Ok(None),
+            ImportType::Layout => Ok(None),
             _ => Err(self.ctm.err().into()),
         }
     }
 }
+
+/// Removes the indentation that all non-empty lines have in common
+fn strip_common_indentation(text: &str) -> String {
+    let is_indentation = |c: char| c == ' ' || c == '\t';
+    let indentation = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start_matches(is_indentation).len())
+        .min()
+        .unwrap_or(0);
+    let mut dedented = text
+        .lines()
+        .map(|l| l.get(indentation..).unwrap_or(""))
+        .collect::<Vec<&str>>()
+        .join("\n");
+    if text.ends_with('\n') {
+        dedented.push('\n');
+    }
+
+    dedented
+}
@@ -297,7 +297,8 @@ impl ParseInline for Parser {
         self.ctm.seek_one()?;
 
         Ok(Math {
-            expression: asciimath_rs::parse(content),
+            expression: asciimath_rs::parse(content.clone()),
+            source: content,
         })
     }
 
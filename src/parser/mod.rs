@@ -12,6 +12,7 @@ use self::block::ParseBlock;
 use crate::elements::tokens::LB;
 use crate::elements::{Document, ImportAnchor};
 use crate::settings::SettingsError;
+use crate::utils::sanitizing::is_inside_root;
 use charred::tapemachine::{CharTapeMachine, TapeError};
 use crossbeam_utils::sync::WaitGroup;
 use regex::Regex;
@@ -87,6 +88,13 @@ impl ParserOptions {
 
         self
     }
+
+    /// Enables the safe mode for untrusted documents
+    pub fn safe_mode(self, enabled: bool) -> Self {
+        self.document.config.lock().safe_mode = enabled;
+
+        self
+    }
 }
 
 pub struct Parser {
@@ -179,6 +187,19 @@ impl Parser {
         path
     }
 
+    /// Returns the directory of the root document
+    fn get_project_root(&self) -> PathBuf {
+        self.options
+            .paths
+            .lock()
+            .unwrap()
+            .first()
+            .and_then(|p| p.parent())
+            .map(|p| p.to_path_buf())
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
     /// starts up a new thread to parse the imported document
     fn import_document(&mut self, path: PathBuf) -> ParseResult<Arc<RwLock<ImportAnchor>>> {
         if !path.exists() || !path.is_file() {
@@ -242,6 +263,12 @@ impl Parser {
             .map_err(ParseError::from)
     }
 
+    /// Sets the imported file as the layout of the html output
+    fn import_layout(&mut self, path: PathBuf) {
+        let path = path.canonicalize().unwrap_or(path);
+        self.options.document.config.lock().html.layout = Some(path.to_string_lossy().to_string());
+    }
+
     /// Imports a glossary
     fn import_glossary(&self, path: PathBuf) -> ParseResult<()> {
         let contents = self.import_text_file(path)?;
@@ -274,6 +301,16 @@ impl Parser {
             );
             return ImportType::None;
         }
+        if self.options.document.config.lock().safe_mode
+            && !is_inside_root(&path, &self.get_project_root())
+        {
+            log::error!(
+                "Import of \"{}\" failed: Imports outside of the project root are disabled in safe mode.\n\t--> {}\n",
+                path.to_str().unwrap(),
+                self.get_position_string(),
+            );
+            return ImportType::None;
+        }
         if let Some(fname) = path
             .file_name()
             .and_then(|f| Some(f.to_str().unwrap().to_string()))
@@ -311,6 +348,10 @@ impl Parser {
             Some(s) if s == "glossary".to_string() => {
                 ImportType::Glossary(self.import_glossary(path))
             }
+            Some(s) if s == "layout" => {
+                self.import_layout(path);
+                ImportType::Layout
+            }
             _ => {
                 lazy_static::lazy_static! {
                     static ref BIB_NAME: Regex = Regex::new(r".*\.bib\.toml$").unwrap();
@@ -414,5 +455,6 @@ pub(crate) enum ImportType {
     Bibliography(ParseResult<()>),
     Manifest(ParseResult<()>),
     Glossary(ParseResult<()>),
+    Layout,
     None,
 }
@@ -49,7 +49,7 @@ pub fn create_bib_list(entries: Vec<BibliographyEntryReference>) -> List {
 }
 
 /// Returns the list item for a bib entry
-fn get_item_for_entry(entry: BibliographyEntryReference) -> ListItem {
+pub(crate) fn get_item_for_entry(entry: BibliographyEntryReference) -> ListItem {
     let entry = entry.lock();
 
     match &entry.bib_type {
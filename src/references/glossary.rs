@@ -8,6 +8,7 @@ use crate::elements::{
     Anchor, BoldText, Inline, ItalicText, Line, List, ListItem, PlainText, TextLine,
 };
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -27,7 +28,7 @@ pub struct GlossaryManager {
 }
 
 /// A single glossary entry
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GlossaryEntry {
     pub short: String,
     pub long: String,
@@ -36,7 +37,7 @@ pub struct GlossaryEntry {
 }
 
 /// A single glossary reference
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GlossaryReference {
     pub short: String,
     pub display: GlossaryDisplay,
@@ -45,7 +46,7 @@ pub struct GlossaryReference {
 
 /// A glossary display value that determines which value
 /// of a glossary entry will be rendered
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum GlossaryDisplay {
     Short,
     Long,
@@ -131,9 +132,8 @@ impl GlossaryManager {
         }
     }
 
-    /// Creates a sorted glossary list from the glossary entries
-    pub fn create_glossary_list(&self) -> List {
-        let mut list = List::new();
+    /// Returns all entries that are referenced in the document sorted by their short name
+    pub fn get_assigned_entries(&self) -> Vec<Arc<Mutex<GlossaryEntry>>> {
         let mut entries = self
             .entries
             .values()
@@ -152,7 +152,15 @@ impl GlossaryManager {
                 Ordering::Equal
             }
         });
-        for entry in &entries {
+
+        entries
+    }
+
+    /// Creates a sorted glossary list from the glossary entries
+    pub fn create_glossary_list(&self) -> List {
+        let mut list = List::new();
+
+        for entry in &self.get_assigned_entries() {
             let entry = entry.lock();
             let mut line = TextLine::new();
             line.subtext.push(bold_text!(entry.short.clone()));
@@ -43,10 +43,13 @@ const P_TIME: &str = "time";
 const P_DATETIME: &str = "datetime";
 const P_AUTHOR: &str = "author";
 const P_TITLE: &str = "title";
+const P_LISTING: &str = "listing:";
 
 impl ProcessPlaceholders for Document {
     /// parses all placeholders and assigns values to them
     fn process_placeholders(&mut self) {
+        let listings = self.get_listing_numbers();
+
         self.placeholders.iter().for_each(|p| {
             let mut pholder = p.write().unwrap();
             match pholder.name.to_lowercase().as_str() {
@@ -83,6 +86,17 @@ impl ProcessPlaceholders for Document {
                         pholder.set_value(inline!(Inline::Plain(PlainText { value })))
                     }
                 }
+                name if name.starts_with(P_LISTING) => {
+                    let key = pholder.name[P_LISTING.len()..].to_string();
+                    if let Some(number) = listings.get(&key) {
+                        pholder.set_value(inline!(Inline::Url(Url::new(
+                            Some(vec![Inline::Plain(PlainText {
+                                value: format!("{} {}", LISTING_LABEL, number)
+                            })]),
+                            format!("#{}", key)
+                        ))))
+                    }
+                }
                 _ => {
                     if let Some(value) = self
                         .config
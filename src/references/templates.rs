@@ -5,6 +5,7 @@
  */
 
 use crate::elements::{Block, Element, Inline, Line, ListItem};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
@@ -16,13 +17,13 @@ pub trait GetTemplateVariables {
     fn get_template_variables(&self) -> Vec<Arc<RwLock<TemplateVariable>>>;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Template {
     pub(crate) text: Vec<Element>,
     pub(crate) variables: HashMap<String, Arc<RwLock<TemplateVariable>>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TemplateVariable {
     pub(crate) prefix: String,
     pub(crate) name: String,
@@ -7,10 +7,12 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct FeatureSettings {
     pub embed_external: bool,
     pub smart_arrows: bool,
     pub include_mathjax: bool,
+    pub include_search: bool,
 }
 
 impl Default for FeatureSettings {
@@ -19,6 +21,7 @@ impl Default for FeatureSettings {
             embed_external: true,
             smart_arrows: true,
             include_mathjax: true,
+            include_search: false,
         }
     }
 }
@@ -7,10 +7,12 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct ImageSettings {
     pub format: Option<String>,
     pub max_width: Option<u32>,
     pub max_height: Option<u32>,
+    pub responsive_widths: Vec<u32>,
 }
 
 impl Default for ImageSettings {
@@ -19,6 +21,7 @@ impl Default for ImageSettings {
             format: None,
             max_height: None,
             max_width: None,
+            responsive_widths: Vec::new(),
         }
     }
 }
@@ -7,6 +7,7 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct ImportSettings {
     pub ignored_imports: Vec<String>,
     pub included_stylesheets: Vec<String>,
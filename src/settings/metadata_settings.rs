@@ -7,6 +7,7 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct MetadataSettings {
     pub title: Option<String>,
     pub author: Option<String>,
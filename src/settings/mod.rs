@@ -6,11 +6,16 @@
 
 use crate::elements::{Metadata, MetadataValue};
 use crate::settings::feature_settings::FeatureSettings;
+use crate::settings::html_settings::HtmlSettings;
 use crate::settings::image_settings::ImageSettings;
 use crate::settings::import_settings::ImportSettings;
 use crate::settings::metadata_settings::MetadataSettings;
+use crate::settings::output_settings::OutputSettings;
 use crate::settings::pdf_settings::PDFSettings;
+use crate::settings::site_settings::SiteSettings;
+use crate::settings::slides_settings::SlidesSettings;
 use crate::settings::style_settings::StyleSettings;
+use crate::settings::text_settings::TextSettings;
 use config::{ConfigError, Source};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -18,14 +23,19 @@ use std::error::Error;
 use std::fmt::{self, Display};
 use std::io;
 use std::mem;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub mod feature_settings;
+pub mod html_settings;
 pub mod image_settings;
 pub mod import_settings;
 pub mod metadata_settings;
+pub mod output_settings;
 pub mod pdf_settings;
+pub mod site_settings;
+pub mod slides_settings;
 pub mod style_settings;
+pub mod text_settings;
 
 pub type SettingsResult<T> = Result<T, SettingsError>;
 
@@ -67,13 +77,22 @@ impl From<toml::ser::Error> for SettingsError {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
 pub struct Settings {
+    /// Safe mode is set when rendering and is never read from or written to manifests
+    #[serde(skip)]
+    pub safe_mode: bool,
+    pub outputs: Vec<OutputSettings>,
     pub metadata: MetadataSettings,
     pub features: FeatureSettings,
     pub imports: ImportSettings,
     pub pdf: PDFSettings,
     pub images: ImageSettings,
     pub style: StyleSettings,
+    pub html: HtmlSettings,
+    pub text: TextSettings,
+    pub site: SiteSettings,
+    pub slides: SlidesSettings,
     pub custom_attributes: HashMap<String, String>,
 }
 
@@ -98,8 +117,9 @@ impl Settings {
         let mut settings = config::Config::default();
         settings
             .merge(Self::default())?
-            .merge(config::File::from(path))?;
-        let settings: Self = settings.try_into()?;
+            .merge(config::File::from(path.clone()))?;
+        let mut settings: Self = settings.try_into()?;
+        settings.resolve_paths(&path);
 
         Ok(settings)
     }
@@ -107,16 +127,27 @@ impl Settings {
     /// Merges the current settings with the settings from the given path
     /// returning updated settings
     pub fn merge(&mut self, path: PathBuf) -> SettingsResult<()> {
+        let safe_mode = self.safe_mode;
         let mut settings = config::Config::default();
         settings
             .merge(self.clone())?
-            .merge(config::File::from(path))?;
+            .merge(config::File::from(path.clone()))?;
         let mut settings: Self = settings.try_into()?;
+        settings.resolve_paths(&path);
+        // the safe mode isn't part of the manifest and is kept from the current settings
+        settings.safe_mode = safe_mode;
         mem::swap(self, &mut settings); // replace the old settings with the new ones
 
         Ok(())
     }
 
+    /// Resolves the relative paths of the settings from the directory of the settings file
+    fn resolve_paths(&mut self, path: &Path) {
+        if let Some(dir) = path.parent() {
+            self.style.resolve_paths(dir);
+        }
+    }
+
     pub fn append_metadata<M: Metadata>(&mut self, metadata: M) {
         let entries = metadata.get_string_map();
         for (key, value) in entries {
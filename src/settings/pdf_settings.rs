@@ -7,6 +7,7 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct PDFSettings {
     pub display_header_footer: bool,
     pub header_template: Option<String>,
@@ -14,10 +15,33 @@ pub struct PDFSettings {
     pub page_height: Option<f32>,
     pub page_width: Option<f32>,
     pub page_scale: f32,
+    pub paper_size: Option<PaperSize>,
+    pub landscape: bool,
+    pub page_ranges: Option<String>,
+    pub prefer_css_page_size: bool,
+    pub browser_path: Option<String>,
+    pub browser_launch_timeout: u64,
+    pub mathjax_timeout: u64,
     pub margin: PDFMarginSettings,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum PaperSize {
+    A0,
+    A1,
+    A2,
+    A3,
+    A4,
+    A5,
+    A6,
+    Letter,
+    Legal,
+    Tabloid,
+    Ledger,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct PDFMarginSettings {
     pub top: Option<f32>,
     pub bottom: Option<f32>,
@@ -48,7 +72,50 @@ impl Default for PDFSettings {
             page_height: None,
             page_width: None,
             page_scale: 1.0,
+            paper_size: None,
+            landscape: false,
+            page_ranges: None,
+            prefer_css_page_size: false,
+            browser_path: None,
+            browser_launch_timeout: 30,
+            mathjax_timeout: 60,
             margin: Default::default(),
         }
     }
 }
+
+impl PDFSettings {
+    /// Returns the width and height of the page in inches.
+    /// Explicitly configured dimensions take precedence over the paper size.
+    pub fn page_dimensions(&self) -> (Option<f32>, Option<f32>) {
+        let (width, height) = match self.paper_size {
+            Some(size) => {
+                let (width, height) = size.dimensions();
+                (Some(width), Some(height))
+            }
+            None => (None, None),
+        };
+
+        (self.page_width.or(width), self.page_height.or(height))
+    }
+}
+
+impl PaperSize {
+    /// Returns the width and height of the paper in inches. All sizes are in portrait
+    /// orientation except for ledger which is the landscape version of tabloid.
+    pub fn dimensions(&self) -> (f32, f32) {
+        match self {
+            PaperSize::A0 => (33.11, 46.81),
+            PaperSize::A1 => (23.39, 33.11),
+            PaperSize::A2 => (16.54, 23.39),
+            PaperSize::A3 => (11.69, 16.54),
+            PaperSize::A4 => (8.27, 11.69),
+            PaperSize::A5 => (5.83, 8.27),
+            PaperSize::A6 => (4.13, 5.83),
+            PaperSize::Letter => (8.5, 11.0),
+            PaperSize::Legal => (8.5, 14.0),
+            PaperSize::Tabloid => (11.0, 17.0),
+            PaperSize::Ledger => (17.0, 11.0),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SiteSettings {
+    pub split_level: u8,
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        Self { split_level: 1 }
+    }
+}
@@ -5,11 +5,18 @@
  */
 
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct StyleSettings {
     pub bib_ref_display: String,
     pub theme: Theme,
+    pub code_highlighting: CodeHighlighting,
+    pub code_theme: Option<String>,
+    pub code_theme_dark: Option<String>,
+    pub code_themes: Vec<String>,
+    pub syntaxes: Vec<String>,
 }
 
 impl Default for StyleSettings {
@@ -17,6 +24,20 @@ impl Default for StyleSettings {
         Self {
             bib_ref_display: "{{number}}".to_string(),
             theme: Theme::GitHub,
+            code_highlighting: CodeHighlighting::Inline,
+            code_theme: None,
+            code_theme_dark: None,
+            code_themes: Vec::new(),
+            syntaxes: Vec::new(),
+        }
+    }
+}
+
+impl StyleSettings {
+    /// Resolves relative paths of syntaxes and code themes from the given directory
+    pub(crate) fn resolve_paths(&mut self, base_dir: &Path) {
+        for path in self.syntaxes.iter_mut().chain(self.code_themes.iter_mut()) {
+            *path = base_dir.join(&path).to_string_lossy().to_string();
         }
     }
 }
@@ -30,3 +51,14 @@ pub enum Theme {
     OceanLight,
     MagicDark,
 }
+
+/// How code blocks are highlighted in html output
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum CodeHighlighting {
+    /// The colors are written as inline styles
+    Inline,
+    /// The tokens get css classes and the stylesheet of the theme is included once
+    Classes,
+    /// Like `Classes` with a light and dark stylesheet that follow the color scheme of the reader
+    LightDark,
+}
@@ -0,0 +1,19 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TextSettings {
+    pub wrap_width: usize,
+}
+
+impl Default for TextSettings {
+    fn default() -> Self {
+        Self { wrap_width: 80 }
+    }
+}
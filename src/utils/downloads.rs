@@ -5,11 +5,12 @@
  */
 
 use crate::utils::caching::CacheStorage;
+use crate::utils::sanitizing::is_inside_root;
 use indicatif::{ProgressBar, ProgressStyle};
 use parking_lot::Mutex;
 use rayon::prelude::*;
 use std::fs::read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 /// A manager for downloading urls in parallel
@@ -36,6 +37,22 @@ impl DownloadManager {
         pending
     }
 
+    /// Removes all downloads that don't point to a local file inside the root directory
+    pub fn retain_inside(&mut self, root: &Path) {
+        self.downloads.retain(|d| {
+            let path = &d.lock().path;
+            let inside = is_inside_root(Path::new(path), root);
+            if !inside {
+                log::warn!(
+                    "Skipping download of {}: Not a file inside the project root",
+                    path
+                );
+            }
+
+            inside
+        });
+    }
+
     /// Downloads all download entries
     pub fn download_all(&self) {
         let pb = Arc::new(Mutex::new(ProgressBar::new(self.downloads.len() as u64)));
@@ -0,0 +1,64 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use regex::{Captures, Regex};
+
+/// Entities that are used in generated MathML but aren't part of html 4
+const EXTRA_ENTITIES: &[(&str, char)] = &[
+    ("plus", '+'),
+    ("lbrace", '{'),
+    ("rbrace", '}'),
+    ("Rarrtl", '\u{2916}'),
+    ("Star", '\u{22C6}'),
+    ("compfn", '\u{2218}'),
+    ("Copf", '\u{2102}'),
+    ("Nopf", '\u{2115}'),
+    ("Qopf", '\u{211A}'),
+    ("Ropf", '\u{211D}'),
+    ("Zopf", '\u{2124}'),
+    ("dtdot", '\u{22F1}'),
+    ("xvee", '\u{22C1}'),
+    ("xwedge", '\u{22C0}'),
+    ("xcap", '\u{22C2}'),
+    ("xcup", '\u{22C3}'),
+    ("vDash", '\u{22A8}'),
+    ("vdash", '\u{22A2}'),
+    ("top", '\u{22A4}'),
+    ("pr", '\u{227A}'),
+    ("sc", '\u{227B}'),
+    ("prcue", '\u{227C}'),
+    ("sccue", '\u{227D}'),
+    ("ltimes", '\u{22C9}'),
+    ("rtimes", '\u{22CA}'),
+    ("bowtie", '\u{22C8}'),
+    ("setminus", '\u{2216}'),
+    ("conint", '\u{222E}'),
+    ("because", '\u{2235}'),
+    ("diamond", '\u{22C4}'),
+];
+
+/// Returns the decoded value of a named html entity
+pub fn decode_entity(name: &str) -> Option<String> {
+    if let Some((_, c)) = EXTRA_ENTITIES.iter().find(|(n, _)| *n == name) {
+        return Some(c.to_string());
+    }
+
+    htmlescape::decode_html(&format!("&{};", name)).ok()
+}
+
+/// Decodes all named and numeric entities in the given text.
+/// Unknown entities are kept as they are.
+pub fn decode_entities(text: &str) -> String {
+    lazy_static::lazy_static! {
+        static ref ENTITY: Regex = Regex::new(r"&(#?[a-zA-Z0-9]+);").unwrap();
+    }
+
+    ENTITY
+        .replace_all(text, |c: &Captures| {
+            decode_entity(&c[1]).unwrap_or_else(|| c[0].to_string())
+        })
+        .to_string()
+}
@@ -7,16 +7,17 @@
 use crate::elements::Metadata;
 use crate::utils::caching::CacheStorage;
 use crate::utils::downloads::download_path;
+use crate::utils::sanitizing::is_inside_root;
 use image::imageops::FilterType;
 use image::io::Reader as ImageReader;
-use image::{GenericImageView, ImageFormat, ImageResult};
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageResult};
 use indicatif::{ProgressBar, ProgressStyle};
 use mime::Mime;
 use parking_lot::Mutex;
 use rayon::prelude::*;
 use std::io;
 use std::io::Cursor;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 #[derive(Clone, Debug)]
@@ -51,6 +52,27 @@ impl ImageConverter {
         image
     }
 
+    /// Adds an image that has been created outside of the converter
+    pub fn add_pending_image(&mut self, image: Arc<Mutex<PendingImage>>) {
+        self.images.push(image);
+    }
+
+    /// Removes all images that aren't local files inside the root directory
+    pub fn retain_inside(&mut self, root: &Path) {
+        self.images.retain(|i| {
+            let path = &i.lock().path;
+            let inside = is_inside_root(path, root);
+            if !inside {
+                log::warn!(
+                    "Skipping image {:?}: Not a file inside the project root",
+                    path
+                );
+            }
+
+            inside
+        });
+    }
+
     /// Converts all images
     pub fn convert_all(&mut self) {
         let pb = Arc::new(Mutex::new(ProgressBar::new(self.images.len() as u64)));
@@ -68,12 +90,41 @@ impl ImageConverter {
         });
         pb.lock().finish_and_clear();
     }
+
+    /// Creates the resized variants of all converted images for responsive output
+    pub fn create_all_variants(&self, variant_widths: &[u32]) {
+        let pb = Arc::new(Mutex::new(ProgressBar::new(self.images.len() as u64)));
+        pb.lock().set_style(
+            ProgressStyle::default_bar()
+                .template("Creating image variants: [{bar:40.cyan/blue}]")
+                .progress_chars("=> "),
+        );
+        self.images.par_iter().for_each(|image| {
+            let mut image = image.lock();
+            if let Err(e) = image.create_variants(variant_widths) {
+                log::error!("Failed to create variants of image {:?}: {}", image.path, e)
+            }
+            pb.lock().tick();
+        });
+        pb.lock().finish_and_clear();
+    }
+}
+
+/// A smaller variant of an image
+#[derive(Clone, Debug)]
+pub struct ImageVariant {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
 pub struct PendingImage {
     pub path: PathBuf,
     pub data: Option<Vec<u8>>,
+    pub dimensions: Option<(u32, u32)>,
+    pub variants: Vec<ImageVariant>,
+    format: Option<ImageFormat>,
     cache: CacheStorage,
     pub mime: Mime,
     brightness: Option<i32>,
@@ -90,6 +141,9 @@ impl PendingImage {
         Self {
             path,
             data: None,
+            dimensions: None,
+            variants: Vec::new(),
+            format: None,
             cache: CacheStorage::new(),
             mime,
             brightness: None,
@@ -131,26 +185,82 @@ impl PendingImage {
         let output_path = self.get_output_path(format, target_size);
         self.mime = get_mime(&output_path);
 
-        if self.cache.has_file(&output_path) {
-            self.data = Some(self.cache.read(&output_path)?)
-        } else {
-            self.convert_image(format, target_size)?;
+        let data = self.convert_cached(&output_path, &mut None, format, target_size)?;
+        let dimensions = ImageReader::new(Cursor::new(&data))
+            .with_guessed_format()?
+            .into_dimensions()?;
+        self.data = Some(data);
+        self.dimensions = Some(dimensions);
+        self.format = Some(format);
+        self.variants.clear();
 
-            if let Some(data) = &self.data {
-                self.cache.write(&output_path, data)?;
-            }
+        Ok(())
+    }
+
+    /// Creates a resized variant of the converted image for every given width
+    /// that is smaller than the image. Existing variants are kept.
+    pub fn create_variants(&mut self, variant_widths: &[u32]) -> ImageResult<()> {
+        let (format, (width, height)) = match (self.format, self.dimensions) {
+            (Some(format), Some(dimensions)) if self.variants.is_empty() => (format, dimensions),
+            _ => return Ok(()),
+        };
+        let mut variant_widths: Vec<u32> = variant_widths
+            .iter()
+            .cloned()
+            .filter(|w| *w > 0 && *w < width)
+            .collect();
+        variant_widths.sort_unstable();
+        variant_widths.dedup();
+
+        // the source image is only decoded if one of the variants isn't cached
+        let mut source = None;
+
+        for variant_width in variant_widths {
+            let variant_size = (
+                variant_width,
+                ((height as u64 * variant_width as u64) / width as u64).max(1) as u32,
+            );
+            let variant_path = self.get_output_path(format, Some(variant_size));
+            let data =
+                self.convert_cached(&variant_path, &mut source, format, Some(variant_size))?;
+            self.variants.push(ImageVariant {
+                width: variant_size.0,
+                height: variant_size.1,
+                data,
+            });
         }
 
         Ok(())
     }
 
+    /// Returns the converted image from the cache or converts it and stores it in the cache
+    fn convert_cached(
+        &self,
+        output_path: &PathBuf,
+        source: &mut Option<DynamicImage>,
+        format: ImageFormat,
+        target_size: Option<(u32, u32)>,
+    ) -> ImageResult<Vec<u8>> {
+        if self.cache.has_file(output_path) {
+            return Ok(self.cache.read(output_path)?);
+        }
+        if source.is_none() {
+            *source = Some(ImageReader::open(self.get_path()?)?.decode()?);
+        }
+        let data = self.convert_image(source.as_ref().unwrap(), format, target_size)?;
+        self.cache.write(output_path, &data)?;
+
+        Ok(data)
+    }
+
     /// Converts the image
     fn convert_image(
-        &mut self,
+        &self,
+        image: &DynamicImage,
         format: ImageFormat,
         target_size: Option<(u32, u32)>,
-    ) -> ImageResult<()> {
-        let mut image = ImageReader::open(self.get_path()?)?.decode()?;
+    ) -> ImageResult<Vec<u8>> {
+        let mut image = image.clone();
 
         if let Some((width, height)) = target_size {
             let dimensions = image.dimensions();
@@ -184,9 +294,8 @@ impl PendingImage {
         let mut writer = Cursor::new(data);
 
         image.write_to(&mut writer, format)?;
-        self.data = Some(writer.into_inner());
 
-        Ok(())
+        Ok(writer.into_inner())
     }
 
     /// Returns the path of the file
@@ -6,6 +6,8 @@
 
 pub mod caching;
 pub mod downloads;
+pub mod entities;
 pub mod image_converting;
 pub mod macros;
 pub mod parsing;
+pub mod sanitizing;
@@ -0,0 +1,213 @@
+/*
+ * Snekdown - Custom Markdown flavour and parser
+ * Copyright (C) 2021  Trivernis
+ * See LICENSE for more information.
+ */
+
+use std::path::Path;
+
+/// Url schemes that are allowed in safe mode
+const SAFE_SCHEMES: &[&str] = &["http", "https", "mailto", "ftp", "tel"];
+
+/// Functions that are allowed for colors in safe mode
+const COLOR_FUNCTIONS: &[&str] = &["rgb(", "rgba(", "hsl(", "hsla("];
+
+/// Returns if the path points to an existing file inside the root directory
+pub fn is_inside_root(path: &Path, root: &Path) -> bool {
+    match (path.canonicalize(), root.canonicalize()) {
+        (Ok(path), Ok(root)) => path.starts_with(root),
+        _ => false,
+    }
+}
+
+/// Returns if the url is relative or uses a scheme that can't execute code
+pub fn is_safe_url(url: &str) -> bool {
+    // browsers ignore whitespace and control characters in the scheme
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let scheme_end = url.find(&[':', '/', '?', '#'][..]);
+
+    match scheme_end {
+        Some(index) if url[index..].starts_with(':') => {
+            let scheme = url[..index].to_lowercase();
+            SAFE_SCHEMES.contains(&scheme.as_str())
+        }
+        _ => true,
+    }
+}
+
+/// Returns if the value is a color that can't break out of a css declaration
+pub fn is_safe_color(color: &str) -> bool {
+    let color = color.trim().to_lowercase();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else if let Some(function) = COLOR_FUNCTIONS.iter().find(|f| color.starts_with(*f)) {
+        color[function.len()..]
+            .strip_suffix(')')
+            .map(|args| {
+                args.chars()
+                    .all(|c| c.is_ascii_digit() || " ,.%".contains(c))
+            })
+            .unwrap_or(false)
+    } else {
+        !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic())
+    }
+}
+
+/// Returns if the value is a plain css length like `10px` or `50%`
+pub fn is_safe_length(length: &str) -> bool {
+    let length = length.trim();
+
+    !length.is_empty()
+        && length
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '%')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Creates an empty directory for a test inside the temporary directory
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "snekdown-sanitizing-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn it_allows_safe_urls() {
+        assert!(is_safe_url("https://example.com/image.png"));
+        assert!(is_safe_url("HTTP://example.com"));
+        assert!(is_safe_url("mailto:someone@example.com"));
+        assert!(is_safe_url("images/image.png"));
+        assert!(is_safe_url("/images/image.png"));
+        assert!(is_safe_url("#section"));
+        assert!(is_safe_url("page?time=10:00"));
+        assert!(is_safe_url(""));
+    }
+
+    #[test]
+    fn it_rejects_urls_that_can_execute_code() {
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("JavaScript:alert(1)"));
+        assert!(!is_safe_url("vbscript:msgbox(1)"));
+        assert!(!is_safe_url("data:text/html,<script>alert(1)</script>"));
+        assert!(!is_safe_url("file:///etc/passwd"));
+    }
+
+    #[test]
+    fn it_rejects_obfuscated_schemes() {
+        assert!(!is_safe_url(" javascript:alert(1)"));
+        assert!(!is_safe_url("java\tscript:alert(1)"));
+        assert!(!is_safe_url("java\nscript:alert(1)"));
+        assert!(!is_safe_url("java\rscript:alert(1)"));
+        assert!(!is_safe_url("\u{0}javascript:alert(1)"));
+        assert!(!is_safe_url("java\u{1}script:alert(1)"));
+        assert!(!is_safe_url("javascript\u{a0}:alert(1)"));
+    }
+
+    #[test]
+    fn it_allows_safe_colors() {
+        assert!(is_safe_color("#fff"));
+        assert!(is_safe_color("#00FF00aa"));
+        assert!(is_safe_color("red"));
+        assert!(is_safe_color(" DarkBlue "));
+        assert!(is_safe_color("rgb(255, 0, 0)"));
+        assert!(is_safe_color("rgba(255,0,0,0.5)"));
+        assert!(is_safe_color("hsl(120, 100%, 50%)"));
+    }
+
+    #[test]
+    fn it_rejects_colors_that_break_out_of_the_declaration() {
+        assert!(!is_safe_color(""));
+        assert!(!is_safe_color("#fff;background:url(x)"));
+        assert!(!is_safe_color("red;position:fixed"));
+        assert!(!is_safe_color("red}body{display:none"));
+        assert!(!is_safe_color("rgb(1,2,3);x"));
+        assert!(!is_safe_color("rgb(1,2,3"));
+        assert!(!is_safe_color("url(https://example.com)"));
+        assert!(!is_safe_color("expression(alert(1))"));
+        assert!(!is_safe_color("red\" onload=\"alert(1)"));
+    }
+
+    #[test]
+    fn it_allows_safe_lengths() {
+        assert!(is_safe_length("10px"));
+        assert!(is_safe_length("50%"));
+        assert!(is_safe_length("1.5em"));
+        assert!(is_safe_length(" 100 "));
+        assert!(is_safe_length("auto"));
+    }
+
+    #[test]
+    fn it_rejects_unsafe_lengths() {
+        assert!(!is_safe_length(""));
+        assert!(!is_safe_length("10px;color:red"));
+        assert!(!is_safe_length("10px}"));
+        assert!(!is_safe_length("calc(100% - 1px)"));
+        assert!(!is_safe_length("url(x)"));
+        assert!(!is_safe_length("10px\" onload=\"alert(1)"));
+    }
+
+    #[test]
+    fn it_allows_files_inside_the_root() {
+        let dir = test_dir("inside");
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(root.join("document.md"), "").unwrap();
+        fs::write(root.join("images/image.png"), "").unwrap();
+
+        assert!(is_inside_root(&root.join("document.md"), &root));
+        assert!(is_inside_root(&root.join("images/image.png"), &root));
+        assert!(is_inside_root(&root.join("images/../document.md"), &root));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn it_rejects_files_outside_of_the_root() {
+        let dir = test_dir("outside");
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(dir.join("root-sibling")).unwrap();
+        fs::write(dir.join("secret.txt"), "").unwrap();
+        fs::write(dir.join("root-sibling/secret.txt"), "").unwrap();
+
+        assert!(!is_inside_root(&dir.join("secret.txt"), &root));
+        assert!(!is_inside_root(&root.join("../secret.txt"), &root));
+        assert!(!is_inside_root(&dir.join("root-sibling/secret.txt"), &root));
+        assert!(!is_inside_root(&root.join("missing.md"), &root));
+        assert!(!is_inside_root(
+            Path::new("https://example.com/style.css"),
+            &root
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_rejects_symlinks_pointing_outside_of_the_root() {
+        let dir = test_dir("symlink");
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(dir.join("secret.txt"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("link.txt")).unwrap();
+
+        assert!(!is_inside_root(&root.join("link.txt"), &root));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}